@@ -0,0 +1,26 @@
+#import bevy_sprite::mesh2d_vertex_output::VertexOutput
+
+@group(2) @binding(0) var<uniform> scale: u32;
+@group(2) @binding(1) var source: texture_2d<f32>;
+
+// Average the block of supersampled texels behind this pixel, which is a box
+// filter over premultiplied colour.
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let origin = vec2<u32>(in.position.xy) * scale;
+    var sum = vec4(0.0);
+    for (var y = 0u; y < scale; y++) {
+        for (var x = 0u; x < scale; x++) {
+            sum += textureLoad(source, origin + vec2(x, y), 0);
+        }
+    }
+    let color = sum / f32(scale * scale);
+#ifdef STRAIGHT_ALPHA
+    if color.a <= 0.0 {
+        return vec4(0.0);
+    }
+    return vec4(color.rgb / color.a, color.a);
+#else
+    return color;
+#endif
+}
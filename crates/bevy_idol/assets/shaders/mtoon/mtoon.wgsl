@@ -19,6 +19,18 @@
 #import "shaders/mtoon/mtoon_functions.wgsl" as mtoon_functions
 #import "shaders/mtoon/mtoon_lighting.wgsl"::{ShadeInput, shade_input_new, shade}
 
+#ifdef VERTEX_UVS
+// The UVs of a texture's set, falling back to the first set for meshes
+// without a second.
+fn uv_set(in: VertexOutput, set: u32) -> vec2<f32> {
+#ifdef VERTEX_UVS_B
+    return mtoon_functions::select_uv_set(in.uv, in.uv_b, set);
+#else
+    return in.uv;
+#endif
+}
+#endif
+
 @fragment
 fn fragment(
     in: VertexOutput,
@@ -34,7 +46,7 @@ fn fragment(
     let is_orthographic = view.clip_from_view[3].w == 1.0;
     let V = pbr_functions::calculate_view(in.world_position, is_orthographic);
 #ifdef VERTEX_UVS
-    var uv = in.uv;
+    let uv = uv_set(in, mtoon_bindings::material.base_color_uv_set);
 #endif
 
 #ifdef VERTEX_COLORS
@@ -46,16 +58,20 @@ fn fragment(
     }
     if ((mtoon_bindings::material.flags & mtoon_types::MTOON_FLAGS_SHADE_SHIFT_TEXTURE_BIT) != 0u) {
         shading_shift += mtoon_bindings::material.shading_shift_scale * textureSampleBias(
-            mtoon_bindings::shading_shift_texture, mtoon_bindings::shading_shift_sampler, uv, view.mip_bias).r;
+            mtoon_bindings::shading_shift_texture, mtoon_bindings::shading_shift_sampler,
+            uv_set(in, mtoon_bindings::material.shading_shift_uv_set), view.mip_bias).r;
     }
     if ((mtoon_bindings::material.flags & mtoon_types::MTOON_FLAGS_SHADE_COLOR_TEXTURE_BIT) != 0u) {
-        shade_color *= textureSampleBias(mtoon_bindings::shade_color_texture, mtoon_bindings::shade_color_sampler, uv, view.mip_bias);
+        shade_color *= textureSampleBias(mtoon_bindings::shade_color_texture, mtoon_bindings::shade_color_sampler,
+            uv_set(in, mtoon_bindings::material.shade_color_uv_set), view.mip_bias);
     }
     if ((mtoon_bindings::material.flags & mtoon_types::MTOON_FLAGS_EMISSIVE_TEXTURE_BIT) != 0u) {
-        emissive *= textureSampleBias(mtoon_bindings::emissive_texture, mtoon_bindings::emissive_sampler, uv, view.mip_bias);
+        emissive *= textureSampleBias(mtoon_bindings::emissive_texture, mtoon_bindings::emissive_sampler,
+            uv_set(in, mtoon_bindings::material.emissive_uv_set), view.mip_bias);
     }
     if ((mtoon_bindings::material.flags & mtoon_types::MTOON_FLAGS_NORMAL_TEXTURE_BIT) != 0u) {
-        Nt = textureSampleBias(mtoon_bindings::normal_map_texture, mtoon_bindings::normal_map_sampler, uv, view.mip_bias).rgb;
+        Nt = textureSampleBias(mtoon_bindings::normal_map_texture, mtoon_bindings::normal_map_sampler,
+            uv_set(in, mtoon_bindings::material.normal_map_uv_set), view.mip_bias).rgb;
     }
 #endif
 
@@ -88,6 +104,7 @@ fn fragment(
 #ifdef VERTEX_UVS
         uv,
         Nt,
+        mtoon_bindings::material.normal_scale,
 #endif
         view.mip_bias,
     );
@@ -96,6 +113,11 @@ fn fragment(
     var shading = shade(shade_input);
     var output_color = vec4(shading + emissive.rgb, alpha);
 
+#ifdef MTOON_OUTLINE
+    let outline_lighting = mix(vec3(1.0), shading, mtoon_bindings::outline.lighting_mix_factor);
+    output_color = vec4(mtoon_bindings::outline.color_factor * outline_lighting, alpha);
+#endif
+
     if (fog.mode != FOG_MODE_OFF && (mtoon_bindings::material.flags & mtoon_types::MTOON_FLAGS_FOG_ENABLED_BIT) != 0u) {
         output_color = pbr_functions::apply_fog(fog, output_color, in.world_position.xyz, view.world_position.xyz);
     }
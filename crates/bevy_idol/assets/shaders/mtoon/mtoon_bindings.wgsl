@@ -1,4 +1,4 @@
-#import "shaders/mtoon/mtoon_types.wgsl"::MToonMaterial
+#import "shaders/mtoon/mtoon_types.wgsl"::{MToonMaterial, MToonOutline}
 
 @group(2) @binding(0)
 var<uniform> material: MToonMaterial;
@@ -35,3 +35,11 @@ var uv_animation_mask_texture: texture_2d<f32>;
 @group(2) @binding(16)
 var uv_animation_mask_sampler: sampler;
 
+#ifdef MTOON_OUTLINE
+@group(2) @binding(100)
+var<uniform> outline: MToonOutline;
+@group(2) @binding(101)
+var outline_width_multiply_texture: texture_2d<f32>;
+@group(2) @binding(102)
+var outline_width_multiply_sampler: sampler;
+#endif
@@ -2,6 +2,15 @@
 #import "shaders/mtoon/mtoon_types.wgsl"::MToonMaterial
 #import "shaders/mtoon/mtoon_bindings.wgsl" as mtoon_bindings
 
+// The UVs of a texture's set: `uv_b` for the second set, or `uv` for the
+// first.
+fn select_uv_set(uv: vec2<f32>, uv_b: vec2<f32>, set: u32) -> vec2<f32> {
+    if set == 1u {
+        return uv_b;
+    }
+    return uv;
+}
+
 fn alpha_discard(material: MToonMaterial, output_color: vec4<f32>) -> f32 {
     var color = output_color;
     let alpha_mode = material.flags & mtoon_types::MTOON_FLAGS_ALPHA_MODE_RESERVED_BITS;
@@ -34,6 +43,7 @@ fn apply_normal_mapping(
 #ifdef VERTEX_UVS
     uv: vec2<f32>,
     texture_normal: vec3<f32>,
+    normal_scale: f32,
 #endif
     mip_bias: f32,
 ) -> vec3<f32> {
@@ -54,6 +64,7 @@ fn apply_normal_mapping(
         } else {
             Nt = Nt * 2.0 - 1.0;
         }
+        Nt = vec3(Nt.xy * normal_scale, Nt.z);
         N = Nt.x * T + Nt.y * B + Nt.z * N;
     }
 #endif
@@ -0,0 +1,136 @@
+// The vertex shader for MToon outlines: Bevy's mesh vertex shader, with
+// each vertex pushed out along its normal to form the outline's hull.
+
+#import bevy_pbr::{
+    mesh_functions,
+    skinning,
+    morph::morph,
+    forward_io::{Vertex, VertexOutput},
+    view_transformations::position_world_to_clip,
+}
+#import bevy_pbr::mesh_view_bindings::view
+
+#import "shaders/mtoon/mtoon_bindings.wgsl" as mtoon_bindings
+#import "shaders/mtoon/mtoon_functions.wgsl" as mtoon_functions
+
+#ifdef MORPH_TARGETS
+fn morph_vertex(vertex_in: Vertex) -> Vertex {
+    var vertex = vertex_in;
+    let weight_count = bevy_pbr::morph::layer_count();
+    for (var i: u32 = 0u; i < weight_count; i ++) {
+        let weight = bevy_pbr::morph::weight_at(i);
+        if weight == 0.0 {
+            continue;
+        }
+        vertex.position += weight * morph(vertex.index, bevy_pbr::morph::position_offset, i);
+#ifdef VERTEX_NORMALS
+        vertex.normal += weight * morph(vertex.index, bevy_pbr::morph::normal_offset, i);
+#endif
+#ifdef VERTEX_TANGENTS
+        vertex.tangent += vec4(weight * morph(vertex.index, bevy_pbr::morph::tangent_offset, i), 0.0);
+#endif
+    }
+    return vertex;
+}
+#endif
+
+#ifdef VERTEX_UVS
+// The UVs of a texture's set, falling back to the first set for meshes
+// without a second, as in mtoon.wgsl.
+fn uv_set(vertex: Vertex, set: u32) -> vec2<f32> {
+#ifdef VERTEX_UVS_B
+    return mtoon_functions::select_uv_set(vertex.uv, vertex.uv_b, set);
+#else
+    return vertex.uv;
+#endif
+}
+#endif
+
+// The outline width at a vertex, scaled by the green channel of the width
+// multiply texture.
+fn outline_width(vertex: Vertex) -> f32 {
+    var width = mtoon_bindings::outline.width_factor;
+#ifdef VERTEX_UVS
+    width *= textureSampleLevel(
+        mtoon_bindings::outline_width_multiply_texture,
+        mtoon_bindings::outline_width_multiply_sampler,
+        uv_set(vertex, mtoon_bindings::outline.width_multiply_uv_set),
+        0.0,
+    ).g;
+#endif
+    return width;
+}
+
+@vertex
+fn vertex(vertex_no_morph: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+
+#ifdef MORPH_TARGETS
+    var vertex = morph_vertex(vertex_no_morph);
+#else
+    var vertex = vertex_no_morph;
+#endif
+
+#ifdef SKINNED
+    var world_from_local = skinning::skin_model(vertex.joint_indices, vertex.joint_weights);
+#else
+    var world_from_local = mesh_functions::get_world_from_local(vertex_no_morph.instance_index);
+#endif
+
+    var world_normal = vec3<f32>(0.0);
+#ifdef VERTEX_NORMALS
+#ifdef SKINNED
+    world_normal = skinning::skin_normals(world_from_local, vertex.normal);
+#else
+    world_normal = mesh_functions::mesh_normal_local_to_world(
+        vertex.normal,
+        vertex_no_morph.instance_index
+    );
+#endif
+    out.world_normal = world_normal;
+#endif
+
+    let width = outline_width(vertex);
+    out.world_position = mesh_functions::mesh_position_local_to_world(world_from_local, vec4<f32>(vertex.position, 1.0));
+
+#ifdef OUTLINE_WIDTH_WORLD
+    if dot(world_normal, world_normal) > 0.0 {
+        out.world_position += vec4(normalize(world_normal) * width, 0.0);
+    }
+#endif
+
+    out.position = position_world_to_clip(out.world_position.xyz);
+
+#ifdef OUTLINE_WIDTH_SCREEN
+    // The width is a fraction of the screen height, which spans 2 in clip
+    // space, so widen x by the aspect ratio to keep the outline even.
+    var clip_normal = (view.clip_from_world * vec4(world_normal, 0.0)).xy;
+    if dot(clip_normal, clip_normal) > 0.0 {
+        clip_normal = normalize(clip_normal);
+        clip_normal.x *= view.viewport.w / view.viewport.z;
+        out.position += vec4(clip_normal * 2.0 * width * out.position.w, 0.0, 0.0);
+    }
+#endif
+
+#ifdef VERTEX_UVS
+    out.uv = vertex.uv;
+#endif
+#ifdef VERTEX_UVS_B
+    out.uv_b = vertex.uv_b;
+#endif
+#ifdef VERTEX_TANGENTS
+    out.world_tangent = mesh_functions::mesh_tangent_local_to_world(
+        world_from_local,
+        vertex.tangent,
+        vertex_no_morph.instance_index
+    );
+#endif
+#ifdef VERTEX_COLORS
+    out.color = vertex.color;
+#endif
+#ifdef VERTEX_OUTPUT_INSTANCE_INDEX
+    out.instance_index = vertex_no_morph.instance_index;
+#endif
+
+    return out;
+}
@@ -26,6 +26,7 @@ struct MToonMaterial {
     shading_shift_factor: f32,
     shading_shift_scale: f32,
     shading_toony_factor: f32,
+    normal_scale: f32,
     gi_equalization_factor: f32,
     matcap_factor: vec3<f32>,
     parametric_rim_color_factor: vec3<f32>,
@@ -35,5 +36,17 @@ struct MToonMaterial {
     uv_animation_scroll_x_speed_factor: f32,
     uv_animation_scroll_y_speed_factor: f32,
     uv_animation_rotation_speed_factor: f32,
+    base_color_uv_set: u32,
+    emissive_uv_set: u32,
+    shade_color_uv_set: u32,
+    shading_shift_uv_set: u32,
+    normal_map_uv_set: u32,
 };
 
+
+struct MToonOutline {
+    width_factor: f32,
+    color_factor: vec3<f32>,
+    lighting_mix_factor: f32,
+    width_multiply_uv_set: u32,
+};
@@ -0,0 +1,36 @@
+#import bevy_pbr::forward_io::VertexOutput
+#import bevy_pbr::mesh_view_bindings as view_bindings
+#import bevy_pbr::mesh_view_types as mesh_view_types
+#import bevy_pbr::shadows as shadows
+
+struct ShadowCatcherMaterial {
+    color: vec4<f32>,
+};
+
+@group(2) @binding(0)
+var<uniform> material: ShadowCatcherMaterial;
+
+// Only draws where directional light shadows fall, so the rest of the surface
+// stays fully transparent.
+@fragment
+fn fragment(
+    in: VertexOutput,
+) -> @location(0) vec4<f32> {
+    let view_z = dot(vec4<f32>(
+        view_bindings::view.view_from_world[0].z,
+        view_bindings::view.view_from_world[1].z,
+        view_bindings::view.view_from_world[2].z,
+        view_bindings::view.view_from_world[3].z
+    ), in.world_position);
+    let N = normalize(in.world_normal);
+
+    var lit = 1.0;
+    let n_directional_lights = view_bindings::lights.n_directional_lights;
+    for (var i: u32 = 0u; i < n_directional_lights; i = i + 1u) {
+        if ((view_bindings::lights.directional_lights[i].flags & mesh_view_types::DIRECTIONAL_LIGHT_FLAGS_SHADOWS_ENABLED_BIT) != 0u) {
+            lit = min(lit, shadows::fetch_directional_shadow(i, in.world_position, N, view_z));
+        }
+    }
+
+    return vec4(material.color.rgb, material.color.a * (1.0 - lit));
+}
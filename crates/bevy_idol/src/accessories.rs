@@ -0,0 +1,191 @@
+use std::path::Path;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::Deserialize;
+
+use bevy_vrm::extensions::vrm::{Humanoid, HumanoidBone};
+use idol_api::{Accessory, ApiError};
+
+#[derive(Deserialize)]
+struct AccessoryConfig {
+    accessories: Vec<Accessory>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum AccessoryKind {
+    Scene,
+    Mesh,
+}
+
+impl AccessoryKind {
+    fn from_path(path: &str) -> Result<AccessoryKind, ApiError> {
+        let extension = Path::new(path).extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("gltf" | "glb") => Ok(AccessoryKind::Scene),
+            Some("obj") => Ok(AccessoryKind::Mesh),
+            _ => Err(ApiError::invalid_argument(format!("unsupported accessory format: {path}"))),
+        }
+    }
+}
+
+struct AttachedAccessory {
+    accessory: Accessory,
+    kind: AccessoryKind,
+    bone: HumanoidBone,
+    entity: Option<Entity>,
+}
+
+/// Props attached to the avatar's bones, by name.
+///
+/// Accessories are spawned as children of their bone, so they are despawned
+/// along with the avatar's scene. They are attached again whenever their bone
+/// changes, such as after switching avatars.
+#[derive(Default, Resource)]
+pub struct Accessories {
+    attached: HashMap<String, AttachedAccessory>,
+}
+
+impl Accessories {
+    /// Load accessories to attach at startup, from a JSON file of the form
+    /// `{"accessories": [...]}`.
+    pub fn from_file(path: &Path) -> anyhow::Result<Accessories> {
+        let contents = std::fs::read(path)?;
+        let config: AccessoryConfig = serde_json::from_slice(&contents)?;
+        let mut accessories = Accessories::default();
+        for accessory in config.accessories {
+            let bone = parse_bone(&accessory.bone)
+                .map_err(|err| anyhow::anyhow!("{}", err.message))?;
+            let kind = AccessoryKind::from_path(&accessory.path)
+                .map_err(|err| anyhow::anyhow!("{}", err.message))?;
+            accessories.attached.insert(accessory.name.clone(), AttachedAccessory {
+                accessory,
+                kind,
+                bone,
+                entity: None,
+            });
+        }
+        Ok(accessories)
+    }
+}
+
+#[derive(Component)]
+pub struct AccessoryAttachment {
+    pub name: String,
+}
+
+pub fn parse_bone(name: &str) -> Result<HumanoidBone, ApiError> {
+    serde_json::from_value(serde_json::Value::String(name.to_string()))
+        .map_err(|_| ApiError::invalid_argument(format!("unknown humanoid bone: {name}")))
+}
+
+fn spawn_accessory(
+    commands: &mut Commands,
+    attached: &AttachedAccessory,
+    assets: &AssetServer,
+    materials: &mut Assets<StandardMaterial>,
+) -> Entity {
+    let accessory = &attached.accessory;
+    let transform = Transform {
+        translation: accessory.translation,
+        rotation: accessory.rotation,
+        scale: accessory.scale,
+    };
+    let common = (
+        Name::from(format!("Accessory: {}", accessory.name)),
+        AccessoryAttachment {
+            name: accessory.name.clone(),
+        },
+    );
+
+    match attached.kind {
+        AccessoryKind::Scene => commands.spawn((common, SceneBundle {
+            scene: assets.load(format!("{}#Scene0", accessory.path)),
+            transform,
+            ..default()
+        })).id(),
+        AccessoryKind::Mesh => commands.spawn((common, PbrBundle {
+            mesh: assets.load(accessory.path.clone()),
+            material: materials.add(StandardMaterial::default()),
+            transform,
+            ..default()
+        })).id(),
+    }
+}
+
+/// Adds and removes accessories, for the API.
+#[derive(SystemParam)]
+pub struct AccessoryParams<'w, 's> {
+    commands: Commands<'w, 's>,
+    accessories: ResMut<'w, Accessories>,
+    humanoids: Query<'w, 's, &'static Humanoid>,
+}
+
+impl<'w, 's> AccessoryParams<'w, 's> {
+    /// Attach (or replace) an accessory.
+    ///
+    /// The bone is checked against the loaded humanoids, if there are any.
+    pub fn set(&mut self, accessory: Accessory) -> Result<(), ApiError> {
+        let bone = parse_bone(&accessory.bone)?;
+        let kind = AccessoryKind::from_path(&accessory.path)?;
+        if !self.humanoids.is_empty() && !self.humanoids.iter().any(|h| h.bones.contains_key(&bone)) {
+            return Err(ApiError::not_found(format!("the avatar has no {} bone", accessory.bone)));
+        }
+
+        self.remove(&accessory.name).ok();
+        self.accessories.attached.insert(accessory.name.clone(), AttachedAccessory {
+            accessory,
+            kind,
+            bone,
+            entity: None,
+        });
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<(), ApiError> {
+        let Some(attached) = self.accessories.attached.remove(name) else {
+            return Err(ApiError::not_found(format!("no accessory named {name}")));
+        };
+
+        if let Some(entity) = attached.entity.and_then(|e| self.commands.get_entity(e)) {
+            entity.despawn_recursive();
+        }
+        Ok(())
+    }
+}
+
+/// Spawn any accessories which aren't attached to the current avatar.
+pub fn attach_accessories(
+    mut commands: Commands,
+    assets: Res<AssetServer>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut accessories: ResMut<Accessories>,
+    humanoids: Query<&Humanoid>,
+    attachments: Query<&Parent, With<AccessoryAttachment>>,
+) {
+    let Some(humanoid) = humanoids.iter().next() else {
+        return;
+    };
+
+    for attached in accessories.attached.values_mut() {
+        let Some(bone_entity) = humanoid.bones.get(&attached.bone).copied() else {
+            continue;
+        };
+
+        let parent = attached.entity.and_then(|e| attachments.get(e).ok());
+        if parent.map(|p| p.get()) == Some(bone_entity) {
+            continue;
+        }
+
+        if let Some(entity) = attached.entity.take().and_then(|e| commands.get_entity(e)) {
+            entity.despawn_recursive();
+        }
+
+        let entity = spawn_accessory(&mut commands, attached, &assets, &mut materials);
+        commands.entity(bone_entity).add_child(entity);
+        attached.entity = Some(entity);
+    }
+}
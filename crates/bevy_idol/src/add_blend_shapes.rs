@@ -1,11 +1,21 @@
-use bevy::asset::{Assets, Handle};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use bevy::asset::{AssetId, Assets, Handle};
+use bevy::log::{error, info, warn};
 use bevy::math::Vec3;
-use bevy::prelude::{Commands, Component, Entity, Image, Mesh, Query, Res, ResMut, With};
-use bevy::render::mesh::morph::{MorphAttributes, MorphTargetImage};
+use bevy::prelude::{Component, Image, Mesh, Query, Res, ResMut};
+use bevy::render::mesh::morph::{MAX_MORPH_WEIGHTS, MorphAttributes, MorphTargetImage};
 use bevy::render::render_asset::RenderAssetUsages;
+use bevy::tasks::{AsyncComputeTaskPool, block_on, Task};
+use bevy::tasks::futures_lite::future;
+use bevy::utils::{HashMap, HashSet};
 use serde::{Deserialize, Serialize};
 use bevy_vrm::Vrm;
 
+use crate::avatars::AvatarSet;
+use crate::error::IdolError;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct Vec3Dto {
     pub x: f32,
@@ -53,8 +63,22 @@ pub struct BlendShapeLibrary {
     pub blend_shapes: Vec<BlendShape>,
 }
 
+impl BlendShape {
+    /// The largest displacement of any vertex, as a rough measure of how
+    /// noticeable the shape is.
+    pub fn impact(&self) -> f32 {
+        self.positions.iter().map(|p| p.length()).fold(0., f32::max)
+    }
+}
+
+fn is_blend_shape_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("json") || extension.eq_ignore_ascii_case("txt"))
+}
+
 impl BlendShapeLibrary {
-    pub fn from_slice(src: &[u8]) -> anyhow::Result<BlendShapeLibrary> {
+    pub fn from_slice(src: &[u8]) -> Result<BlendShapeLibrary, serde_json::Error> {
         let dto = serde_json::from_slice::<BlendShapesDto>(src)?;
         Ok(Self {
             blend_shapes: dto.blend_shapes.into_iter()
@@ -68,58 +92,214 @@ impl BlendShapeLibrary {
                 .collect(),
         })
     }
+
+    /// Load and merge blend shape files, in order. Directories are expanded
+    /// to the JSON files within them, sorted by name. HANATool saves JSON
+    /// with a `.txt` extension, so those are included too.
+    ///
+    /// A shape with the same name as one from an earlier file replaces it.
+    pub fn from_paths(paths: &[PathBuf]) -> Result<BlendShapeLibrary, IdolError> {
+        let mut files = Vec::new();
+        for path in paths {
+            if path.is_dir() {
+                let mut entries = std::fs::read_dir(path)
+                    .and_then(|entries| entries
+                        .map(|entry| entry.map(|e| e.path()))
+                        .collect::<Result<Vec<_>, _>>())
+                    .map_err(|err| IdolError::read(path, err))?;
+                entries.retain(|p| p.is_file() && is_blend_shape_file(p));
+                entries.sort();
+                files.extend(entries);
+            } else {
+                files.push(path.clone());
+            }
+        }
+
+        let mut library = BlendShapeLibrary {
+            blend_shapes: Vec::new(),
+        };
+        for file in &files {
+            let contents = std::fs::read(file)
+                .map_err(|err| IdolError::read(file, err))?;
+            let next = BlendShapeLibrary::from_slice(&contents)
+                .map_err(|err| IdolError::parse(file, err))?;
+            library.merge(next, file);
+        }
+        Ok(library)
+    }
+
+    fn merge(&mut self, other: BlendShapeLibrary, path: &Path) {
+        let names: HashSet<&str> = other.blend_shapes.iter().map(|s| s.name.as_str()).collect();
+        let mut overridden: Vec<_> = self.blend_shapes.iter()
+            .filter(|s| names.contains(s.name.as_str()))
+            .map(|s| s.name.clone())
+            .collect();
+        if !overridden.is_empty() {
+            overridden.sort();
+            overridden.dedup();
+            warn!("{} overrides blend shapes: {}", path.display(), overridden.join(", "));
+            self.blend_shapes.retain(|s| !names.contains(s.name.as_str()));
+        }
+
+        info!("loaded {} blend shapes from {}", other.blend_shapes.len(), path.display());
+        self.blend_shapes.extend(other.blend_shapes);
+    }
+}
+
+/// The morph targets built for one mesh, ready to be swapped in.
+struct BuiltMorphTargets {
+    image: Image,
+    names: Vec<String>,
 }
 
+/// Extra blend shapes to add to the meshes of an avatar, and every avatar in
+/// its `AvatarSet`.
+///
+/// Building the morph target images for large meshes is slow, so it happens
+/// on the async compute pool. Each mesh gets all of its new morph targets at
+/// once, when they are ready.
 #[derive(Component)]
 pub struct AddBlendShapes {
-    pub blend_shapes: Vec<BlendShape>,
+    pub blend_shapes: Arc<Vec<BlendShape>>,
+    /// Blend shapes added instead of `blend_shapes` to particular avatars.
+    pub avatar_blend_shapes: HashMap<AssetId<Vrm>, Arc<Vec<BlendShape>>>,
+    applied: HashSet<AssetId<Vrm>>,
+    pending: Vec<(AssetId<Mesh>, Task<Option<BuiltMorphTargets>>)>,
+    // Meshes started since the last time nothing was pending, for progress.
+    started: usize,
+}
+
+impl AddBlendShapes {
+    pub fn new(blend_shapes: Vec<BlendShape>) -> AddBlendShapes {
+        AddBlendShapes {
+            blend_shapes: Arc::new(blend_shapes),
+            avatar_blend_shapes: HashMap::new(),
+            applied: HashSet::new(),
+            pending: Vec::new(),
+            started: 0,
+        }
+    }
 }
 
 pub fn apply_blend_shapes(
-    mut commands: Commands,
     vrms: Res<Assets<Vrm>>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut images: ResMut<Assets<Image>>,
-    entities: Query<(Entity, &Handle<Vrm>, &AddBlendShapes), With<AddBlendShapes>>,
+    mut entities: Query<(&Handle<Vrm>, Option<&AvatarSet>, &mut AddBlendShapes)>,
 ) {
-    for (entity, vrm, to_add) in &entities {
-        let Some(vrm) = vrms.get(vrm) else {
-            continue;
-        };
-        commands.entity(entity).remove::<AddBlendShapes>();
+    for (active, avatar_set, mut to_add) in &mut entities {
+        let to_add = &mut *to_add;
+        let handles = std::iter::once(active)
+            .chain(avatar_set.into_iter().flat_map(|set| set.avatars.iter()));
+        for handle in handles {
+            if to_add.applied.contains(&handle.id()) {
+                continue;
+            }
 
-        for mesh in vrm.meshes.iter() {
-            let Some(mesh) = meshes.get_mut(mesh) else {
+            let Some(vrm) = vrms.get(handle) else {
                 continue;
             };
+            to_add.applied.insert(handle.id());
+            let blend_shapes = to_add.avatar_blend_shapes.get(&handle.id())
+                .unwrap_or(&to_add.blend_shapes);
+            for mesh in vrm.meshes.iter() {
+                let Some(task) = build_morph_targets(blend_shapes, meshes.get(mesh)) else {
+                    continue;
+                };
+                to_add.pending.push((mesh.id(), task));
+                to_add.started += 1;
+            }
+        }
 
-            // At the moment I think only the face will have morph targets.
-            if !mesh.has_morph_targets() {
-                continue;
+        if to_add.pending.is_empty() {
+            continue;
+        }
+
+        let mut finished = false;
+        to_add.pending.retain_mut(|(mesh_id, task)| {
+            let Some(built) = block_on(future::poll_once(task)) else {
+                return true;
+            };
+            finished = true;
+
+            let (Some(built), Some(mesh)) = (built, meshes.get_mut(*mesh_id)) else {
+                return false;
+            };
+            info!("adding morph targets: {:?}", &built.names);
+            mesh.set_morph_targets(images.add(built.image));
+            mesh.set_morph_target_names(built.names);
+            false
+        });
+
+        if finished {
+            let done = to_add.started - to_add.pending.len();
+            info!("added extra blend shapes to {}/{} meshes", done, to_add.started);
+            if to_add.pending.is_empty() {
+                to_add.started = 0;
             }
+        }
+    }
+}
+
+/// Start building the extra morph targets for a mesh, if it has any.
+fn build_morph_targets(
+    blend_shapes: &Arc<Vec<BlendShape>>,
+    mesh: Option<&Mesh>,
+) -> Option<Task<Option<BuiltMorphTargets>>> {
+    let mesh = mesh?;
 
-            // TODO: At the moment this will just replace all morph targets.
-            let mut morph_target_names = Vec::new();
-            let mut morph_targets = Vec::new();
-
-            let vertex_count = mesh.count_vertices();
-            for blend_shape in to_add.blend_shapes.iter()
-                .filter(|s| s.vertex_count as usize == vertex_count) {
-                let mut elements = vec![MorphAttributes::default(); vertex_count];
-                for (i, position) in blend_shape.indices.iter()
-                    .zip(blend_shape.positions.iter()) {
-                    elements[*i as usize].position = *position;
-                }
-
-                morph_target_names.push(blend_shape.name.to_string());
-                morph_targets.push(elements.into_iter());
+    // At the moment I think only the face will have morph targets.
+    if !mesh.has_morph_targets() {
+        return None;
+    }
+
+    let vertex_count = mesh.count_vertices();
+    let count = blend_shapes.iter()
+        .filter(|s| s.vertex_count as usize == vertex_count)
+        .count();
+    if count > MAX_MORPH_WEIGHTS {
+        let mut shapes: Vec<_> = blend_shapes.iter()
+            .filter(|s| s.vertex_count as usize == vertex_count)
+            .collect();
+        shapes.sort_by(|a, b| a.impact().total_cmp(&b.impact()));
+        let least: Vec<_> = shapes.iter()
+            .take(count - MAX_MORPH_WEIGHTS)
+            .map(|s| s.name.as_str())
+            .collect();
+        error!(
+            "not adding {count} extra blend shapes to a mesh with {vertex_count} vertices: at most \
+            {MAX_MORPH_WEIGHTS} morph targets are supported. Merge or drop some shapes, such as the \
+            least noticeable: {least:?}");
+        return None;
+    }
+
+    let blend_shapes = blend_shapes.clone();
+    Some(AsyncComputeTaskPool::get().spawn(async move {
+        // TODO: At the moment this will just replace all morph targets.
+        let mut names = Vec::new();
+        let mut morph_targets = Vec::new();
+
+        for blend_shape in blend_shapes.iter()
+            .filter(|s| s.vertex_count as usize == vertex_count) {
+            let mut elements = vec![MorphAttributes::default(); vertex_count];
+            for (i, position) in blend_shape.indices.iter()
+                .zip(blend_shape.positions.iter()) {
+                elements[*i as usize].position = *position;
             }
 
-            tracing::info!("Adding morph targets: {:?}", &morph_target_names);
-            let morph_image = MorphTargetImage::new(morph_targets.into_iter(), vertex_count, RenderAssetUsages::RENDER_WORLD)
-                .expect("failed to create morph target image");
-            mesh.set_morph_targets(images.add(morph_image.0));
-            mesh.set_morph_target_names(morph_target_names);
+            names.push(blend_shape.name.to_string());
+            morph_targets.push(elements.into_iter());
         }
-    }
+
+        match MorphTargetImage::new(morph_targets.into_iter(), vertex_count, RenderAssetUsages::RENDER_WORLD) {
+            Ok(image) => Some(BuiltMorphTargets {
+                image: image.0,
+                names,
+            }),
+            Err(err) => {
+                warn!("failed to create morph target image: {err}");
+                None
+            }
+        }
+    }))
 }
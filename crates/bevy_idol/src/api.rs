@@ -1,38 +1,84 @@
+use std::convert::Infallible;
 use std::sync::Arc;
 
 use axum::{Json, Router};
-use axum::extract::{DefaultBodyLimit, State};
+use axum::extract::{DefaultBodyLimit, Path, State};
 use axum::http::{HeaderMap, StatusCode};
-use axum::routing::put;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::routing::{delete, get, put};
 use axum_extra::TypedHeader;
-use bevy::prelude::{Assets, Image, Res, ResMut, Resource, StandardMaterial, Transform};
+use bevy::ecs::system::SystemParam;
+use bevy::log::warn;
+use bevy::prelude::DetectChanges;
+use bevy::prelude::{AssetServer, Assets, Handle, Image, Query, Res, ResMut, Resource, StandardMaterial, Transform, With};
 use bevy::render::render_asset::RenderAssetUsages;
 use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::render::view::ColorGrading;
+use bevy::tasks::futures_lite::{stream, Stream};
 use bytes::Bytes;
 use headers::ContentLength;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot, watch};
 
-use idol_api::{ApiError, SetCameraRequest, SetFacesRequest};
+use bevy_vrm::Vrm;
+use idol_api::{Accessory, ApiError, CalibrateRequest, GetBlendShapesResponse, GetCalibrationProfilesResponse, GetRigResponse, HealthResponse, PlayPhonemesRequest, SetAvatarRequest, SetBoneRequest, SetCalibrationProfileRequest, SetCameraRequest, SetExpressionPoseRequest, SetFacesRequest, SetFadeRequest, SetLookAtRequest, SetOutputColorRequest, SetPartRequest, SetPosesRequest, SetSmoothingRequest, TrackingEvent};
 
-use crate::tracking::Faces;
+use crate::accessories::{AccessoryParams, parse_bone};
+use crate::avatars::AvatarSet;
+use crate::blend_shape_matches::BlendShapeMatches;
+use crate::calibration::CalibrationParams;
+use crate::cameras::{output_color_grading, OutputCamera};
+use crate::expression_poses::ExpressionPoses;
+use crate::expressions::ExpressionWeights;
+use crate::fade::AvatarFade;
+use crate::look_at::LookAt;
+use crate::parts::PartParams;
+use crate::phonemes::PhonemePlayback;
+use crate::rig::RigQuery;
+use crate::smoothing::Smoothing;
+use crate::tracking_noise::TrackingNoise;
+use crate::tracking::{AxisConvention, Faces, Poses, TrackedTranslation, TrackingStatus};
 use crate::webcam::WebcamTexture;
 
 pub enum Command {
     SetFaces(SetFacesRequest),
+    SetPoses(SetPosesRequest),
     SetCamera(SetCameraRequest),
+    SetAvatar(SetAvatarRequest, oneshot::Sender<Result<(), ApiError>>),
+    SetLookAt(SetLookAtRequest),
+    PlayPhonemes(PlayPhonemesRequest),
+    SetExpressionPose(SetExpressionPoseRequest, oneshot::Sender<Result<(), ApiError>>),
+    SetOutputColor(SetOutputColorRequest),
+    SetSmoothing(SetSmoothingRequest),
+    SetFade(SetFadeRequest),
+    GetRig(oneshot::Sender<GetRigResponse>),
+    GetBlendShapes(oneshot::Sender<GetBlendShapesResponse>),
+    SetAccessory(Accessory, oneshot::Sender<Result<(), ApiError>>),
+    RemoveAccessory(String, oneshot::Sender<Result<(), ApiError>>),
+    SetPart(String, SetPartRequest, oneshot::Sender<Result<(), ApiError>>),
+    SetBone(SetBoneRequest, oneshot::Sender<Result<(), ApiError>>),
+    Calibrate(CalibrateRequest, oneshot::Sender<Result<(), ApiError>>),
+    GetCalibrationProfiles(oneshot::Sender<Result<GetCalibrationProfilesResponse, ApiError>>),
+    SetCalibrationProfile(SetCalibrationProfileRequest, oneshot::Sender<Result<(), ApiError>>),
+    DeleteCalibrationProfile(String, oneshot::Sender<Result<(), ApiError>>),
 }
 
 pub struct ApiState {
     tx: mpsc::UnboundedSender<Command>,
+    tracking: watch::Receiver<bool>,
+    face_axes: AxisConvention,
 }
 
 impl ApiState {
-    pub fn new() -> (Arc<Self>, ApiResource) {
+    pub fn new(face_axes: AxisConvention) -> (Arc<Self>, ApiResource) {
         let (tx, rx) = mpsc::unbounded_channel();
+        let (tracking_tx, tracking) = watch::channel(false);
         (Arc::new(Self {
             tx,
+            tracking,
+            face_axes,
         }), ApiResource {
-            rx
+            rx,
+            tracking_tx,
         })
     }
 }
@@ -68,28 +114,261 @@ async fn put_camera(
     Ok(StatusCode::OK)
 }
 
-async fn put_faces(State(state): State<Arc<ApiState>>, Json(faces): Json<SetFacesRequest>) {
+async fn put_faces(State(state): State<Arc<ApiState>>, Json(mut faces): Json<SetFacesRequest>) {
+    if state.face_axes != AxisConvention::MEDIAPIPE {
+        for face in &mut faces.faces {
+            face.transform = state.face_axes.convert(face.transform);
+        }
+    }
     state.tx.send(Command::SetFaces(faces)).ok();
 }
 
+async fn put_poses(State(state): State<Arc<ApiState>>, Json(poses): Json<SetPosesRequest>) {
+    state.tx.send(Command::SetPoses(poses)).ok();
+}
+
+async fn put_avatar(
+    State(state): State<Arc<ApiState>>,
+    Json(request): Json<SetAvatarRequest>,
+) -> Result<StatusCode, ApiError> {
+    let (tx, rx) = oneshot::channel();
+    state.tx.send(Command::SetAvatar(request, tx))
+        .map_err(|_| ApiError::unavailable())?;
+    rx.await.map_err(|_| ApiError::unavailable())??;
+    Ok(StatusCode::OK)
+}
+
+async fn put_look_at(State(state): State<Arc<ApiState>>, Json(request): Json<SetLookAtRequest>) {
+    state.tx.send(Command::SetLookAt(request)).ok();
+}
+
+async fn put_phonemes(State(state): State<Arc<ApiState>>, Json(request): Json<PlayPhonemesRequest>) {
+    state.tx.send(Command::PlayPhonemes(request)).ok();
+}
+
+async fn put_expression_pose(
+    State(state): State<Arc<ApiState>>,
+    Json(request): Json<SetExpressionPoseRequest>,
+) -> Result<StatusCode, ApiError> {
+    let (tx, rx) = oneshot::channel();
+    state.tx.send(Command::SetExpressionPose(request, tx))
+        .map_err(|_| ApiError::unavailable())?;
+    rx.await.map_err(|_| ApiError::unavailable())??;
+    Ok(StatusCode::OK)
+}
+
+async fn put_output_color(State(state): State<Arc<ApiState>>, Json(request): Json<SetOutputColorRequest>) {
+    state.tx.send(Command::SetOutputColor(request)).ok();
+}
+
+async fn put_smoothing(State(state): State<Arc<ApiState>>, Json(request): Json<SetSmoothingRequest>) {
+    state.tx.send(Command::SetSmoothing(request)).ok();
+}
+
+async fn put_fade(State(state): State<Arc<ApiState>>, Json(request): Json<SetFadeRequest>) {
+    state.tx.send(Command::SetFade(request)).ok();
+}
+
+async fn get_health(State(state): State<Arc<ApiState>>) -> Json<HealthResponse> {
+    Json(HealthResponse {
+        tracking: *state.tracking.borrow(),
+    })
+}
+
+async fn get_events(State(state): State<Arc<ApiState>>) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let events = stream::unfold((state.tracking.clone(), true), |(mut tracking, first)| async move {
+        if !first {
+            tracking.changed().await.ok()?;
+        }
+        let active = *tracking.borrow_and_update();
+        let event = Event::default()
+            .event("tracking")
+            .json_data(TrackingEvent { active })
+            .ok()?;
+        Some((Ok(event), (tracking, false)))
+    });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+async fn get_rig(State(state): State<Arc<ApiState>>) -> Result<Json<GetRigResponse>, ApiError> {
+    let (tx, rx) = oneshot::channel();
+    state.tx.send(Command::GetRig(tx))
+        .map_err(|_| ApiError::unavailable())?;
+    rx.await
+        .map(Json)
+        .map_err(|_| ApiError::unavailable())
+}
+
+async fn get_blend_shapes(State(state): State<Arc<ApiState>>) -> Result<Json<GetBlendShapesResponse>, ApiError> {
+    let (tx, rx) = oneshot::channel();
+    state.tx.send(Command::GetBlendShapes(tx))
+        .map_err(|_| ApiError::unavailable())?;
+    rx.await
+        .map(Json)
+        .map_err(|_| ApiError::unavailable())
+}
+
+async fn put_accessory(
+    State(state): State<Arc<ApiState>>,
+    Json(accessory): Json<Accessory>,
+) -> Result<StatusCode, ApiError> {
+    let (tx, rx) = oneshot::channel();
+    state.tx.send(Command::SetAccessory(accessory, tx))
+        .map_err(|_| ApiError::unavailable())?;
+    rx.await.map_err(|_| ApiError::unavailable())??;
+    Ok(StatusCode::OK)
+}
+
+async fn delete_accessory(
+    State(state): State<Arc<ApiState>>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let (tx, rx) = oneshot::channel();
+    state.tx.send(Command::RemoveAccessory(name, tx))
+        .map_err(|_| ApiError::unavailable())?;
+    rx.await.map_err(|_| ApiError::unavailable())??;
+    Ok(StatusCode::OK)
+}
+
+async fn put_part(
+    State(state): State<Arc<ApiState>>,
+    Path(name): Path<String>,
+    Json(request): Json<SetPartRequest>,
+) -> Result<StatusCode, ApiError> {
+    let (tx, rx) = oneshot::channel();
+    state.tx.send(Command::SetPart(name, request, tx))
+        .map_err(|_| ApiError::unavailable())?;
+    rx.await.map_err(|_| ApiError::unavailable())??;
+    Ok(StatusCode::OK)
+}
+
+async fn put_bone(
+    State(state): State<Arc<ApiState>>,
+    Json(request): Json<SetBoneRequest>,
+) -> Result<StatusCode, ApiError> {
+    let (tx, rx) = oneshot::channel();
+    state.tx.send(Command::SetBone(request, tx))
+        .map_err(|_| ApiError::unavailable())?;
+    rx.await.map_err(|_| ApiError::unavailable())??;
+    Ok(StatusCode::OK)
+}
+
+async fn put_calibration(
+    State(state): State<Arc<ApiState>>,
+    Json(request): Json<CalibrateRequest>,
+) -> Result<StatusCode, ApiError> {
+    let (tx, rx) = oneshot::channel();
+    state.tx.send(Command::Calibrate(request, tx))
+        .map_err(|_| ApiError::unavailable())?;
+    rx.await.map_err(|_| ApiError::unavailable())??;
+    Ok(StatusCode::OK)
+}
+
+async fn get_calibration_profiles(
+    State(state): State<Arc<ApiState>>,
+) -> Result<Json<GetCalibrationProfilesResponse>, ApiError> {
+    let (tx, rx) = oneshot::channel();
+    state.tx.send(Command::GetCalibrationProfiles(tx))
+        .map_err(|_| ApiError::unavailable())?;
+    rx.await
+        .map_err(|_| ApiError::unavailable())?
+        .map(Json)
+}
+
+async fn put_calibration_profile(
+    State(state): State<Arc<ApiState>>,
+    Json(request): Json<SetCalibrationProfileRequest>,
+) -> Result<StatusCode, ApiError> {
+    let (tx, rx) = oneshot::channel();
+    state.tx.send(Command::SetCalibrationProfile(request, tx))
+        .map_err(|_| ApiError::unavailable())?;
+    rx.await.map_err(|_| ApiError::unavailable())??;
+    Ok(StatusCode::OK)
+}
+
+async fn delete_calibration_profile(
+    State(state): State<Arc<ApiState>>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let (tx, rx) = oneshot::channel();
+    state.tx.send(Command::DeleteCalibrationProfile(name, tx))
+        .map_err(|_| ApiError::unavailable())?;
+    rx.await.map_err(|_| ApiError::unavailable())??;
+    Ok(StatusCode::OK)
+}
+
 pub fn new_api() -> Router<Arc<ApiState>> {
     Router::new()
         .route("/v1/camera", put(put_camera))
         .route("/v1/faces", put(put_faces))
+        .route("/v1/poses", put(put_poses))
+        .route("/v1/avatar", put(put_avatar))
+        .route("/v1/lookAt", put(put_look_at))
+        .route("/v1/phonemes", put(put_phonemes))
+        .route("/v1/expressionPoses", put(put_expression_pose))
+        .route("/v1/outputColor", put(put_output_color))
+        .route("/v1/smoothing", put(put_smoothing))
+        .route("/v1/fade", put(put_fade))
+        .route("/v1/health", get(get_health))
+        .route("/v1/events", get(get_events))
+        .route("/v1/rig", get(get_rig))
+        .route("/v1/blendShapes", get(get_blend_shapes))
+        .route("/v1/bone", put(put_bone))
+        .route("/v1/accessories", put(put_accessory))
+        .route("/v1/accessories/:name", delete(delete_accessory))
+        .route("/v1/parts/:name", put(put_part))
+        .route("/v1/calibration", put(put_calibration))
+        .route("/v1/calibration/profile", put(put_calibration_profile))
+        .route("/v1/calibration/profiles", get(get_calibration_profiles))
+        .route("/v1/calibration/profiles/:name", delete(delete_calibration_profile))
         .layer(DefaultBodyLimit::disable())
 }
 
 #[derive(Resource)]
 pub struct ApiResource {
     rx: mpsc::UnboundedReceiver<Command>,
+    tracking_tx: watch::Sender<bool>,
+}
+
+/// Let API clients know when tracking starts or stops.
+pub fn publish_tracking_status(
+    api: Res<ApiResource>,
+    status: Res<TrackingStatus>,
+) {
+    if status.is_changed() {
+        api.tracking_tx.send_if_modified(|active| std::mem::replace(active, status.active) != status.active);
+    }
+}
+
+/// Resources which the API adjusts directly.
+#[derive(SystemParam)]
+pub struct ApiSettings<'w> {
+    look_at: ResMut<'w, LookAt>,
+    phoneme_playback: ResMut<'w, PhonemePlayback>,
+    expression_poses: ResMut<'w, ExpressionPoses>,
+    smoothing: ResMut<'w, Smoothing>,
+    fade: ResMut<'w, AvatarFade>,
+    noise: ResMut<'w, TrackingNoise>,
+    translation: ResMut<'w, TrackedTranslation>,
+    calibration: CalibrationParams<'w>,
 }
 
 pub fn update_api(
     mut api: ResMut<ApiResource>,
     mut faces: ResMut<Faces>,
+    mut poses: ResMut<Poses>,
     webcam: Res<WebcamTexture>,
     mut images: ResMut<Assets<Image>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    mut avatars: Query<(&mut AvatarSet, &mut Handle<Vrm>)>,
+    mut settings: ApiSettings,
+    assets: Res<AssetServer>,
+    mut rig: RigQuery,
+    mut accessories: AccessoryParams,
+    mut output_cameras: Query<&mut ColorGrading, With<OutputCamera>>,
+    mut parts: PartParams,
+    blend_shape_matches: Res<BlendShapeMatches>,
+    expressions: Res<ExpressionWeights>,
 ) {
     while let Ok(command) = api.rx.try_recv() {
         match command {
@@ -103,6 +382,22 @@ pub fn update_api(
                         transform,
                     }
                 }));
+                for face in &mut faces.faces {
+                    settings.calibration.apply(face);
+                }
+                faces.calibrated = faces.faces.first().map(|face| face.transform);
+                for face in &mut faces.faces {
+                    settings.noise.apply(face);
+                    settings.translation.apply(face);
+                }
+            }
+            Command::SetPoses(request) => {
+                poses.poses.clear();
+                poses.poses.extend(request.poses.into_iter().map(|p| {
+                    crate::tracking::Pose {
+                        landmarks: p.landmarks,
+                    }
+                }));
             }
             Command::SetCamera(request) => {
                 // Convert to RGBA
@@ -115,6 +410,78 @@ pub fn update_api(
                 let _ = images.insert(&webcam.image, image.clone());
                 materials.get_mut(&webcam.material);
             }
+            Command::SetAvatar(request, reply) => {
+                let mut found = false;
+                for (mut set, mut vrm) in &mut avatars {
+                    found |= set.switch_to(request.index, &mut vrm);
+                }
+                let result = if found {
+                    Ok(())
+                } else {
+                    Err(ApiError::not_found(format!("no avatar with index {}", request.index)))
+                };
+                reply.send(result).ok();
+            }
+            Command::SetLookAt(request) => {
+                settings.look_at.target = request;
+            }
+            Command::PlayPhonemes(request) => {
+                match request.path {
+                    Some(path) => settings.phoneme_playback.play(assets.load(path), request.offset),
+                    None => settings.phoneme_playback.stop(),
+                }
+            }
+            Command::SetExpressionPose(request, reply) => {
+                let result = if settings.expression_poses.set(&request.name, request.weight) {
+                    Ok(())
+                } else {
+                    Err(ApiError::not_found(format!("no expression pose named {}", request.name)))
+                };
+                reply.send(result).ok();
+            }
+            Command::SetOutputColor(request) => {
+                for mut color_grading in &mut output_cameras {
+                    *color_grading = output_color_grading(request.exposure, request.gamma);
+                }
+            }
+            Command::SetSmoothing(request) => {
+                settings.smoothing.apply(&request);
+            }
+            Command::SetFade(request) => {
+                settings.fade.fade_to(request.opacity, request.duration);
+            }
+            Command::GetRig(reply) => {
+                reply.send(rig.snapshot()).ok();
+            }
+            Command::GetBlendShapes(reply) => {
+                reply.send(blend_shape_matches.snapshot(&expressions)).ok();
+            }
+            Command::SetAccessory(accessory, reply) => {
+                reply.send(accessories.set(accessory)).ok();
+            }
+            Command::RemoveAccessory(name, reply) => {
+                reply.send(accessories.remove(&name)).ok();
+            }
+            Command::SetBone(request, reply) => {
+                let result = parse_bone(&request.bone)
+                    .and_then(|bone| rig.set_rotation(bone, request.rotation));
+                reply.send(result).ok();
+            }
+            Command::SetPart(name, request, reply) => {
+                reply.send(parts.set_visible(&name, request.visible)).ok();
+            }
+            Command::Calibrate(request, reply) => {
+                reply.send(settings.calibration.calibrate(&request, faces.calibrated.as_ref())).ok();
+            }
+            Command::GetCalibrationProfiles(reply) => {
+                reply.send(settings.calibration.snapshot()).ok();
+            }
+            Command::SetCalibrationProfile(request, reply) => {
+                reply.send(settings.calibration.select(&request.name)).ok();
+            }
+            Command::DeleteCalibrationProfile(name, reply) => {
+                reply.send(settings.calibration.delete(&name)).ok();
+            }
         }
     }
 }
@@ -0,0 +1,119 @@
+use std::path::{Path, PathBuf};
+
+use bevy::asset::io::file::FileAssetReader;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::Deserialize;
+
+use bevy_vrm::morph_aliases::MorphAliases;
+use bevy_vrm::Vrm;
+use idol_api::SetSmoothingRequest;
+
+use crate::add_blend_shapes::BlendShape;
+use crate::crossfade::OutgoingAvatar;
+use crate::error::IdolError;
+use crate::remote_avatar::AVATAR_CACHE_SOURCE;
+use crate::smoothing::Smoothing;
+
+/// Settings for a single avatar, read from a sidecar file next to its
+/// `.vrm` (`foo.idol.json` for `foo.vrm`), which are merged over the global
+/// options for that avatar.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AvatarConfig {
+    /// Replaces `--morph-aliases` for this avatar.
+    pub morph_aliases: Option<MorphAliases>,
+    /// Blend shape files or directories, relative to the sidecar, added to
+    /// `--extra-blend-shapes` for this avatar.
+    #[serde(default)]
+    pub extra_blend_shapes: Vec<PathBuf>,
+    /// Smoothing changes, as sent to `PUT /v1/smoothing`, applied over the
+    /// global smoothing while this avatar is active.
+    #[serde(default)]
+    pub smoothing: Vec<SetSmoothingRequest>,
+}
+
+impl AvatarConfig {
+    /// The sidecar path for an avatar file.
+    pub fn sidecar_path(avatar: &Path) -> PathBuf {
+        avatar.with_extension("idol.json")
+    }
+
+    /// Read the sidecar for an avatar file, or `None` if it has none.
+    pub fn discover(avatar: &Path) -> Result<Option<AvatarConfig>, IdolError> {
+        let path = AvatarConfig::sidecar_path(avatar);
+        let data = match std::fs::read(&path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(IdolError::read(&path, err)),
+        };
+
+        let mut config: AvatarConfig = serde_json::from_slice(&data)
+            .map_err(|err| IdolError::parse(&path, err))?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for blend_shapes in &mut config.extra_blend_shapes {
+            *blend_shapes = dir.join(&*blend_shapes);
+        }
+        Ok(Some(config))
+    }
+}
+
+/// The file behind an avatar's asset path, either in the assets directory or
+/// the remote avatar cache.
+pub fn avatar_file(avatar: &str, cache_dir: &Path) -> PathBuf {
+    match avatar.strip_prefix(AVATAR_CACHE_SOURCE).and_then(|rest| rest.strip_prefix("://")) {
+        Some(file_name) => cache_dir.join(file_name),
+        None => FileAssetReader::get_base_path().join("assets").join(avatar),
+    }
+}
+
+/// The sidecar configs of every avatar which has one.
+#[derive(Debug, Resource)]
+pub struct AvatarConfigs {
+    /// Sidecar configs, by avatar asset path.
+    pub configs: HashMap<String, AvatarConfig>,
+    /// Each avatar's extra blend shapes, where its sidecar adds some.
+    pub blend_shapes: HashMap<String, Vec<BlendShape>>,
+    /// Smoothing before any avatar's changes are applied.
+    pub global_smoothing: Smoothing,
+    // Sidecar smoothing changes, by loaded avatar.
+    smoothing: HashMap<AssetId<Vrm>, Vec<SetSmoothingRequest>>,
+}
+
+impl AvatarConfigs {
+    pub fn new(global_smoothing: Smoothing) -> AvatarConfigs {
+        AvatarConfigs {
+            configs: HashMap::new(),
+            blend_shapes: HashMap::new(),
+            global_smoothing,
+            smoothing: HashMap::new(),
+        }
+    }
+
+    /// Remember which avatar a handle was loaded from, so its config can be
+    /// applied when it becomes active.
+    pub fn register(&mut self, path: &str, vrm: &Handle<Vrm>) {
+        if let Some(config) = self.configs.get(path) {
+            self.smoothing.insert(vrm.id(), config.smoothing.clone());
+        }
+    }
+}
+
+/// Reset smoothing to the global settings, overlaid with the active avatar's,
+/// whenever the active avatar changes. Changes made through the API last
+/// until the next switch.
+pub fn apply_avatar_smoothing(
+    configs: Res<AvatarConfigs>,
+    mut smoothing: ResMut<Smoothing>,
+    avatars: Query<&Handle<Vrm>, (Changed<Handle<Vrm>>, Without<OutgoingAvatar>)>,
+) {
+    let Some(vrm) = avatars.iter().next() else {
+        return;
+    };
+
+    let mut new_smoothing = configs.global_smoothing.clone();
+    for request in configs.smoothing.get(&vrm.id()).into_iter().flatten() {
+        new_smoothing.apply(request);
+    }
+    *smoothing = new_smoothing;
+}
@@ -0,0 +1,94 @@
+use bevy::asset::RecursiveDependencyLoadState;
+use bevy::log::{info, warn};
+use bevy::prelude::{AssetServer, ButtonInput, Component, Handle, KeyCode, Query, Res};
+
+use bevy_vrm::Vrm;
+
+/// A set of avatars which are all loaded up front, so that switching between
+/// them is instant.
+///
+/// Switching replaces the `Handle<Vrm>` on the avatar entity. Anything which
+/// is keyed on the VRM handle (such as extra blend shapes) is kept per avatar.
+#[derive(Component)]
+pub struct AvatarSet {
+    pub avatars: Vec<Handle<Vrm>>,
+    pub active: usize,
+    // Number of avatars which have finished loading (or failed).
+    loaded: usize,
+}
+
+impl AvatarSet {
+    pub fn new(avatars: Vec<Handle<Vrm>>) -> AvatarSet {
+        AvatarSet {
+            avatars,
+            active: 0,
+            loaded: 0,
+        }
+    }
+
+    pub fn switch_to(&mut self, index: usize, vrm: &mut Handle<Vrm>) -> bool {
+        let Some(handle) = self.avatars.get(index) else {
+            return false;
+        };
+
+        self.active = index;
+        *vrm = handle.clone();
+        true
+    }
+
+    fn switch_by(&mut self, offset: isize, vrm: &mut Handle<Vrm>) {
+        let count = self.avatars.len() as isize;
+        if count > 0 {
+            let index = (self.active as isize + offset).rem_euclid(count);
+            self.switch_to(index as usize, vrm);
+        }
+    }
+}
+
+pub fn switch_avatar_hotkeys(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut avatars: Query<(&mut AvatarSet, &mut Handle<Vrm>)>,
+) {
+    let offset = if keys.just_pressed(KeyCode::PageDown) {
+        1
+    } else if keys.just_pressed(KeyCode::PageUp) {
+        -1
+    } else {
+        return;
+    };
+
+    for (mut set, mut vrm) in &mut avatars {
+        set.switch_by(offset, &mut vrm);
+        info!("switched to avatar {}/{}", set.active + 1, set.avatars.len());
+    }
+}
+
+pub fn report_preload_progress(
+    assets: Res<AssetServer>,
+    mut avatars: Query<&mut AvatarSet>,
+) {
+    for mut set in &mut avatars {
+        if set.loaded == set.avatars.len() {
+            continue;
+        }
+
+        let mut loaded = 0;
+        let mut failed = 0;
+        for handle in &set.avatars {
+            match assets.get_recursive_dependency_load_state(handle) {
+                Some(RecursiveDependencyLoadState::Loaded) => loaded += 1,
+                Some(RecursiveDependencyLoadState::Failed) => failed += 1,
+                _ => {}
+            }
+        }
+
+        if loaded + failed != set.loaded {
+            set.loaded = loaded + failed;
+            if failed > 0 {
+                warn!("preloaded {}/{} avatars ({} failed)", loaded, set.avatars.len(), failed);
+            } else {
+                info!("preloaded {}/{} avatars", loaded, set.avatars.len());
+            }
+        }
+    }
+}
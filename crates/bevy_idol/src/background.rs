@@ -0,0 +1,185 @@
+use bevy::core_pipeline::Skybox;
+use bevy::prelude::*;
+use bevy::render::camera::ClearColorConfig;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat, TextureViewDescriptor, TextureViewDimension};
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Reflect)]
+pub enum BackgroundKind {
+    #[default]
+    Color,
+    /// A vertical gradient from `bottom` to `top`.
+    Gradient,
+    /// A cubemap, as six square faces stacked vertically in one image.
+    Skybox,
+}
+
+/// The background of the preview camera. The output camera always clears to
+/// transparent, so this never affects capture.
+#[derive(Debug, Clone, Component, Reflect)]
+#[reflect(Component)]
+pub struct PreviewBackground {
+    pub kind: BackgroundKind,
+    pub color: Color,
+    pub top: Color,
+    pub bottom: Color,
+    pub skybox: Option<Handle<Image>>,
+    /// Brightness of the gradient or skybox, in cd/m².
+    pub brightness: f32,
+    #[reflect(ignore)]
+    gradient: Option<Handle<Image>>,
+}
+
+impl Default for PreviewBackground {
+    fn default() -> Self {
+        PreviewBackground {
+            kind: BackgroundKind::Color,
+            color: ClearColor::default().0,
+            top: Color::srgb(0.55, 0.65, 0.8),
+            bottom: Color::srgb(0.15, 0.15, 0.17),
+            skybox: None,
+            brightness: 1000.,
+            gradient: None,
+        }
+    }
+}
+
+pub fn parse_color(s: &str) -> Result<Color, String> {
+    Srgba::hex(s)
+        .map(Color::from)
+        .map_err(|err| format!("invalid colour {s:?}: {err}"))
+}
+
+const GRADIENT_SIZE: u32 = 32;
+
+/// Build a cubemap which fades from `bottom` to `top` with height.
+fn gradient_cubemap(top: Color, bottom: Color) -> Image {
+    let top = top.to_linear();
+    let bottom = bottom.to_linear();
+    let mut data = Vec::with_capacity((GRADIENT_SIZE * GRADIENT_SIZE * 6 * 4) as usize);
+    for face in 0..6 {
+        for y in 0..GRADIENT_SIZE {
+            for x in 0..GRADIENT_SIZE {
+                let u = (x as f32 + 0.5) / GRADIENT_SIZE as f32 * 2. - 1.;
+                let v = (y as f32 + 0.5) / GRADIENT_SIZE as f32 * 2. - 1.;
+                // Faces are +X, -X, +Y, -Y, +Z, -Z.
+                let direction = match face {
+                    0 => Vec3::new(1., -v, -u),
+                    1 => Vec3::new(-1., -v, u),
+                    2 => Vec3::new(u, 1., v),
+                    3 => Vec3::new(u, -1., -v),
+                    4 => Vec3::new(u, -v, 1.),
+                    _ => Vec3::new(-u, -v, -1.),
+                };
+                let t = direction.normalize().y * 0.5 + 0.5;
+                let color = Color::from(bottom.mix(&top, t));
+                data.extend_from_slice(&color.to_srgba().to_u8_array());
+            }
+        }
+    }
+
+    let mut image = Image::new(
+        Extent3d {
+            width: GRADIENT_SIZE,
+            height: GRADIENT_SIZE,
+            depth_or_array_layers: 6,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    image.texture_view_descriptor = Some(TextureViewDescriptor {
+        dimension: Some(TextureViewDimension::Cube),
+        ..default()
+    });
+    image
+}
+
+pub fn update_preview_background(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut cameras: Query<(Entity, &mut Camera, &mut PreviewBackground), Changed<PreviewBackground>>,
+) {
+    for (entity, mut camera, mut background) in &mut cameras {
+        let skybox = match background.kind {
+            BackgroundKind::Color => {
+                camera.clear_color = ClearColorConfig::Custom(background.color);
+                None
+            }
+            BackgroundKind::Gradient => {
+                let image = gradient_cubemap(background.top, background.bottom);
+                let handle = match background.gradient.clone() {
+                    Some(handle) => {
+                        images.insert(&handle, image);
+                        handle
+                    }
+                    None => {
+                        let handle = images.add(image);
+                        background.bypass_change_detection().gradient = Some(handle.clone());
+                        handle
+                    }
+                };
+                Some(handle)
+            }
+            BackgroundKind::Skybox => background.skybox.clone(),
+        };
+
+        match skybox {
+            Some(image) => {
+                commands.entity(entity).insert(Skybox {
+                    image,
+                    brightness: background.brightness,
+                });
+            }
+            None => {
+                commands.entity(entity).remove::<Skybox>();
+            }
+        }
+    }
+}
+
+/// Skybox images load as a single 2D texture, so reinterpret them as
+/// cubemaps once loaded. Images which aren't six stacked squares fall back
+/// to the plain colour.
+pub fn prepare_skybox_images(
+    mut events: EventReader<AssetEvent<Image>>,
+    mut images: ResMut<Assets<Image>>,
+    mut backgrounds: Query<&mut PreviewBackground>,
+) {
+    for event in events.read() {
+        let AssetEvent::LoadedWithDependencies { id } = event else {
+            continue;
+        };
+        if !backgrounds.iter().any(|b| b.skybox.as_ref().is_some_and(|h| h.id() == *id)) {
+            continue;
+        }
+        let Some(image) = images.get_mut(*id) else {
+            continue;
+        };
+
+        if image.texture_descriptor.array_layer_count() == 1 {
+            if image.height() != image.width() * 6 {
+                warn!(
+                    "skybox image is {}x{}, expected six square faces stacked vertically; using the background colour",
+                    image.width(),
+                    image.height(),
+                );
+                for mut background in &mut backgrounds {
+                    if background.skybox.as_ref().is_some_and(|h| h.id() == *id) {
+                        background.skybox = None;
+                        background.kind = BackgroundKind::Color;
+                    }
+                }
+                continue;
+            }
+
+            image.reinterpret_stacked_2d_as_array(6);
+            image.texture_view_descriptor = Some(TextureViewDescriptor {
+                dimension: Some(TextureViewDimension::Cube),
+                ..default()
+            });
+        }
+    }
+}
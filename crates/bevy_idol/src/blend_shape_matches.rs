@@ -0,0 +1,106 @@
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+
+use idol_api::GetBlendShapesResponse;
+
+use crate::brows::combined_brow;
+use crate::expressions::{ExpressionWeights, MorphTargetIndices};
+use crate::eye_blinks::{derived_blinks, DerivedBlink};
+use crate::tracking::Faces;
+
+/// Seconds of tracking before unmatched blend shapes are reported, so the
+/// avatar and its extra blend shapes have time to load.
+const REPORT_DELAY: f32 = 10.;
+
+/// The tracked blend shape names seen this session, and which expressions
+/// have driven a morph target, so that tracked names which silently do
+/// nothing can be reported.
+#[derive(Debug, Default, Resource)]
+pub struct BlendShapeMatches {
+    tracked: HashSet<String>,
+    // Indices in `ExpressionWeights` which drive at least one morph target.
+    matched: HashSet<usize>,
+    // Derived blinks which at least one morph target follows.
+    matched_blinks: HashSet<DerivedBlink>,
+    reported: HashSet<String>,
+}
+
+impl BlendShapeMatches {
+    /// Record the expressions which a mesh's morph targets are driven by.
+    pub fn add_indices(&mut self, indices: &MorphTargetIndices) {
+        self.matched.extend(indices.indices.iter().flatten());
+        self.matched_blinks.extend(indices.blinks.iter().flatten());
+    }
+
+    /// Whether a tracked name has driven a morph target, either directly or
+    /// through an expression derived from it.
+    fn is_matched(&self, name: &str, expressions: &ExpressionWeights) -> bool {
+        let drives = |name: &str| expressions.index_of(name)
+            .map_or(false, |index| self.matched.contains(&index));
+        drives(name)
+            || combined_brow(name).is_some_and(drives)
+            || derived_blinks(name).iter().any(|blink| self.matched_blinks.contains(blink))
+    }
+
+    /// The tracked names which have never driven a morph target, sorted.
+    pub fn unmatched(&self, expressions: &ExpressionWeights) -> Vec<String> {
+        let mut unmatched = self.tracked.iter()
+            .filter(|name| !self.is_matched(name, expressions))
+            .cloned()
+            .collect::<Vec<_>>();
+        unmatched.sort();
+        unmatched
+    }
+
+    pub fn snapshot(&self, expressions: &ExpressionWeights) -> GetBlendShapesResponse {
+        let mut tracked = self.tracked.iter().cloned().collect::<Vec<_>>();
+        tracked.sort();
+        GetBlendShapesResponse {
+            tracked,
+            unmatched: self.unmatched(expressions),
+        }
+    }
+}
+
+pub fn record_tracked_blend_shapes(
+    faces: Res<Faces>,
+    mut matches: ResMut<BlendShapeMatches>,
+) {
+    if !faces.is_changed() {
+        return;
+    }
+
+    for face in &faces.faces {
+        for name in face.blend_shapes.keys() {
+            if !matches.tracked.contains(name) {
+                matches.tracked.insert(name.clone());
+            }
+        }
+    }
+}
+
+/// Warn about tracked blend shapes which drive nothing, once tracking has
+/// been running for a while, and again whenever new ones turn up.
+pub fn report_unmatched_blend_shapes(
+    time: Res<Time>,
+    expressions: Res<ExpressionWeights>,
+    mut matches: ResMut<BlendShapeMatches>,
+    mut first_tracked: Local<Option<f32>>,
+) {
+    if matches.tracked.is_empty() {
+        return;
+    }
+    let now = time.elapsed_seconds();
+    if now - *first_tracked.get_or_insert(now) < REPORT_DELAY {
+        return;
+    }
+
+    let new = matches.unmatched(&expressions).into_iter()
+        .filter(|name| !matches.reported.contains(name))
+        .collect::<Vec<_>>();
+    if new.is_empty() {
+        return;
+    }
+    warn!("tracked blend shapes which drive no morph target (check --morph-aliases): {}", new.join(", "));
+    matches.reported.extend(new);
+}
@@ -0,0 +1,92 @@
+use std::path::Path;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::expressions::ExpressionWeights;
+use crate::tracking::Faces;
+
+/// A morph which follows another, such as a squint which accompanies a blink.
+#[derive(Debug, Clone, Serialize, Deserialize, Reflect)]
+#[serde(rename_all = "camelCase")]
+pub struct BlinkLink {
+    /// The morph which drives the link.
+    pub source: String,
+    /// The morph which is driven.
+    pub target: String,
+    /// The weight of `target` when `source` is fully on.
+    pub ratio: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BlinkLinksConfig {
+    links: Vec<BlinkLink>,
+}
+
+fn builtin_link(source: &str, target: &str, ratio: f32) -> BlinkLink {
+    BlinkLink {
+        source: source.to_string(),
+        target: target.to_string(),
+        ratio,
+    }
+}
+
+/// Morphs which are driven along with blinks, for more natural eyes.
+///
+/// A linked morph never lowers the target's weight, and is skipped entirely
+/// while the target is tracked above [`TRACKED_THRESHOLD`].
+#[derive(Debug, Clone, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct BlinkLinks {
+    pub links: Vec<BlinkLink>,
+}
+
+impl Default for BlinkLinks {
+    fn default() -> Self {
+        BlinkLinks {
+            links: vec![
+                builtin_link("eyeBlinkLeft", "eyeSquintLeft", 0.25),
+                builtin_link("eyeBlinkRight", "eyeSquintRight", 0.25),
+                builtin_link("eyeBlinkLeft", "cheekSquintLeft", 0.1),
+                builtin_link("eyeBlinkRight", "cheekSquintRight", 0.1),
+            ],
+        }
+    }
+}
+
+impl BlinkLinks {
+    /// Load links from a JSON file of the form `{"links": [...]}`.
+    pub fn from_file(path: &Path) -> anyhow::Result<BlinkLinks> {
+        let contents = std::fs::read(path)?;
+        let config: BlinkLinksConfig = serde_json::from_slice(&contents)?;
+        Ok(BlinkLinks {
+            links: config.links,
+        })
+    }
+}
+
+/// Tracked weights at or below this leave the target to its links, since
+/// many trackers send every blend shape whether they drive it or not.
+pub const TRACKED_THRESHOLD: f32 = 0.01;
+
+pub fn apply_blink_links(
+    faces: Res<Faces>,
+    links: Res<BlinkLinks>,
+    mut expressions: ResMut<ExpressionWeights>,
+) {
+    let tracked = faces.faces.first().map(|face| &face.blend_shapes);
+    for link in &links.links {
+        let target_tracked = tracked
+            .and_then(|tracked| tracked.get(&link.target))
+            .is_some_and(|&weight| weight > TRACKED_THRESHOLD);
+        if target_tracked {
+            continue;
+        }
+
+        let linked = expressions.get(&link.source) * link.ratio;
+        if linked > expressions.get(&link.target) {
+            expressions.set(&link.target, linked.min(1.));
+        }
+    }
+}
@@ -0,0 +1,339 @@
+use bevy::prelude::*;
+use bevy::transform::helper::TransformHelper;
+use bevy::utils::HashMap;
+
+use bevy_vrm::extensions::vrm::{Humanoid, HumanoidBone};
+use bevy_vrm::ik::{solve_two_bone, TwoBoneSolution};
+use idol_api::FaceLandmark;
+
+use crate::tracking::Poses;
+
+/// Indices of the MediaPipe pose landmarks.
+pub mod landmarks {
+    pub const LEFT_SHOULDER: usize = 11;
+    pub const RIGHT_SHOULDER: usize = 12;
+    pub const LEFT_ELBOW: usize = 13;
+    pub const RIGHT_ELBOW: usize = 14;
+    pub const LEFT_WRIST: usize = 15;
+    pub const RIGHT_WRIST: usize = 16;
+    pub const LEFT_PINKY: usize = 17;
+    pub const RIGHT_PINKY: usize = 18;
+    pub const LEFT_INDEX: usize = 19;
+    pub const RIGHT_INDEX: usize = 20;
+}
+
+/// Convert a pose landmark into the avatar's space, where the avatar faces +Z
+/// and Y is up.
+pub fn landmark_position(landmarks: &[FaceLandmark], index: usize, min_visibility: f32) -> Option<Vec3> {
+    let landmark = landmarks.get(index)?;
+    if landmark.visibility.unwrap_or(1.) < min_visibility {
+        return None;
+    }
+
+    let p = landmark.position;
+    Some(Vec3::new(p.x, -p.y, -p.z))
+}
+
+#[derive(Debug, Clone, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct ArmTracking {
+    pub enabled: bool,
+    /// Landmarks less visible than this are ignored.
+    pub min_visibility: f32,
+    /// The smallest interior angle of the elbow, in degrees.
+    pub min_elbow_angle: f32,
+    /// The furthest the hand can bend from the forearm, in degrees.
+    pub max_wrist_angle: f32,
+}
+
+impl Default for ArmTracking {
+    fn default() -> Self {
+        ArmTracking {
+            enabled: false,
+            min_visibility: 0.5,
+            min_elbow_angle: 15.,
+            max_wrist_angle: 80.,
+        }
+    }
+}
+
+struct Arm {
+    upper: HumanoidBone,
+    lower: HumanoidBone,
+    hand: HumanoidBone,
+    shoulder: usize,
+    elbow: usize,
+    wrist: usize,
+    index: usize,
+    pinky: usize,
+}
+
+const ARMS: [Arm; 2] = [
+    Arm {
+        upper: HumanoidBone::LeftUpperArm,
+        lower: HumanoidBone::LeftLowerArm,
+        hand: HumanoidBone::LeftHand,
+        shoulder: landmarks::LEFT_SHOULDER,
+        elbow: landmarks::LEFT_ELBOW,
+        wrist: landmarks::LEFT_WRIST,
+        index: landmarks::LEFT_INDEX,
+        pinky: landmarks::LEFT_PINKY,
+    },
+    Arm {
+        upper: HumanoidBone::RightUpperArm,
+        lower: HumanoidBone::RightLowerArm,
+        hand: HumanoidBone::RightHand,
+        shoulder: landmarks::RIGHT_SHOULDER,
+        elbow: landmarks::RIGHT_ELBOW,
+        wrist: landmarks::RIGHT_WRIST,
+        index: landmarks::RIGHT_INDEX,
+        pinky: landmarks::RIGHT_PINKY,
+    },
+];
+
+struct ArmSolution {
+    upper: (Entity, Quat),
+    lower: (Entity, Quat),
+    hand: (Entity, Quat),
+    solution: TwoBoneSolution,
+    // World-space rotation to apply to the hand, after the arm.
+    hand_delta: Quat,
+}
+
+/// Drive the arms of each humanoid from the tracked pose, with two bone IK.
+///
+/// The tracked arm is scaled to the avatar's arm length, so only the
+/// direction from the shoulder to the wrist and elbow is used.
+pub fn update_arm_ik(
+    settings: Res<ArmTracking>,
+    poses: Res<Poses>,
+    humanoids: Query<(Entity, &Humanoid)>,
+    mut params: ParamSet<(TransformHelper, Query<&mut Transform>)>,
+) {
+    if !settings.enabled {
+        return;
+    }
+    let Some(pose) = poses.poses.first() else {
+        return;
+    };
+
+    for (root, humanoid) in &humanoids {
+        let mut solutions = Vec::new();
+        {
+            let helper = params.p0();
+            let Ok(root_global) = helper.compute_global_transform(root) else {
+                continue;
+            };
+            let root_rotation = root_global.compute_transform().rotation;
+
+            for arm in &ARMS {
+                let (Some(upper), Some(lower), Some(hand)) = (
+                    humanoid.bones.get(&arm.upper).copied(),
+                    humanoid.bones.get(&arm.lower).copied(),
+                    humanoid.bones.get(&arm.hand).copied(),
+                ) else {
+                    continue;
+                };
+                let (Ok(a), Ok(b), Ok(c)) = (
+                    helper.compute_global_transform(upper),
+                    helper.compute_global_transform(lower),
+                    helper.compute_global_transform(hand),
+                ) else {
+                    continue;
+                };
+                let landmark = |index| landmark_position(&pose.landmarks, index, settings.min_visibility);
+                let (Some(shoulder), Some(elbow), Some(wrist)) = (
+                    landmark(arm.shoulder),
+                    landmark(arm.elbow),
+                    landmark(arm.wrist),
+                ) else {
+                    continue;
+                };
+
+                let tracked_length = shoulder.distance(elbow) + elbow.distance(wrist);
+                if tracked_length <= f32::EPSILON {
+                    continue;
+                }
+                let avatar_length = a.translation().distance(b.translation())
+                    + b.translation().distance(c.translation());
+                let scale = avatar_length / tracked_length;
+                let to_world = |p: Vec3| a.translation() + root_rotation * ((p - shoulder) * scale);
+
+                let solution = solve_two_bone(
+                    a.translation(), b.translation(), c.translation(),
+                    to_world(wrist), to_world(elbow),
+                    settings.min_elbow_angle.to_radians());
+
+                // Point the hand towards the knuckles, if they're tracked.
+                let forearm = solution.root * solution.mid * (c.translation() - b.translation());
+                let knuckles = landmark(arm.index).zip(landmark(arm.pinky))
+                    .and_then(|(index, pinky)| (root_rotation * ((index + pinky) * 0.5 - wrist)).try_normalize());
+                let hand_delta = match (forearm.try_normalize(), knuckles) {
+                    (Some(forearm), Some(knuckles)) => {
+                        let (axis, angle) = Quat::from_rotation_arc(forearm, knuckles).to_axis_angle();
+                        Quat::from_axis_angle(axis, angle.min(settings.max_wrist_angle.to_radians()))
+                    }
+                    _ => Quat::IDENTITY,
+                };
+
+                solutions.push(ArmSolution {
+                    upper: (upper, a.compute_transform().rotation),
+                    lower: (lower, b.compute_transform().rotation),
+                    hand: (hand, c.compute_transform().rotation),
+                    solution,
+                    hand_delta,
+                });
+            }
+        }
+
+        let mut transforms = params.p1();
+        for arm in solutions {
+            let Ok([mut upper, mut lower, mut hand]) = transforms.get_many_mut(
+                [arm.upper.0, arm.lower.0, arm.hand.0]) else {
+                continue;
+            };
+
+            arm.solution.apply(&mut upper, arm.upper.1, &mut lower, arm.lower.1);
+            let hand_world = arm.solution.root * arm.solution.mid * arm.hand.1;
+            hand.rotation = hand.rotation * hand_world.inverse() * arm.hand_delta * hand_world;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct FootGrounding {
+    pub enabled: bool,
+    /// Height of the floor in world space.
+    pub ground_height: f32,
+    /// Extra pitch of the feet, in degrees. Positive values lift the heels.
+    pub foot_roll: f32,
+}
+
+impl Default for FootGrounding {
+    fn default() -> Self {
+        FootGrounding {
+            enabled: false,
+            ground_height: 0.,
+            foot_roll: 0.,
+        }
+    }
+}
+
+struct Leg {
+    upper: HumanoidBone,
+    lower: HumanoidBone,
+    foot: HumanoidBone,
+}
+
+const LEGS: [Leg; 2] = [
+    Leg {
+        upper: HumanoidBone::LeftUpperLeg,
+        lower: HumanoidBone::LeftLowerLeg,
+        foot: HumanoidBone::LeftFoot,
+    },
+    Leg {
+        upper: HumanoidBone::RightUpperLeg,
+        lower: HumanoidBone::RightLowerLeg,
+        foot: HumanoidBone::RightFoot,
+    },
+];
+
+/// The rest pose of a foot, relative to the humanoid root.
+#[derive(Clone, Copy)]
+struct FootRest {
+    ankle_height: f32,
+    rotation: Quat,
+}
+
+struct LegSolution {
+    upper: (Entity, Quat),
+    lower: (Entity, Quat),
+    foot: (Entity, Quat),
+    solution: TwoBoneSolution,
+    foot_world: Quat,
+}
+
+/// Keep each humanoid's feet planted on the floor with two bone IK.
+///
+/// This runs after everything else has posed the hips, so the legs bend to
+/// follow them. If the hips are too high for the feet to reach the floor, the
+/// legs straighten towards it instead.
+pub fn update_foot_ik(
+    settings: Res<FootGrounding>,
+    humanoids: Query<(Entity, &Humanoid)>,
+    mut rest_poses: Local<HashMap<Entity, FootRest>>,
+    mut params: ParamSet<(TransformHelper, Query<&mut Transform>)>,
+) {
+    if !settings.enabled {
+        return;
+    }
+
+    for (root, humanoid) in &humanoids {
+        let mut solutions = Vec::new();
+        {
+            let helper = params.p0();
+            let Ok(root_global) = helper.compute_global_transform(root) else {
+                continue;
+            };
+            let root_transform = root_global.compute_transform();
+
+            for leg in &LEGS {
+                let (Some(upper), Some(lower), Some(foot)) = (
+                    humanoid.bones.get(&leg.upper).copied(),
+                    humanoid.bones.get(&leg.lower).copied(),
+                    humanoid.bones.get(&leg.foot).copied(),
+                ) else {
+                    continue;
+                };
+                let (Ok(a), Ok(b), Ok(c)) = (
+                    helper.compute_global_transform(upper),
+                    helper.compute_global_transform(lower),
+                    helper.compute_global_transform(foot),
+                ) else {
+                    continue;
+                };
+                let c_rotation = c.compute_transform().rotation;
+
+                // The first time we see a foot, the avatar is in its rest pose
+                // and standing on its root.
+                let rest = *rest_poses.entry(foot).or_insert_with(|| FootRest {
+                    ankle_height: c.translation().y - root_transform.translation.y,
+                    rotation: root_transform.rotation.inverse() * c_rotation,
+                });
+
+                let target = Vec3::new(
+                    c.translation().x,
+                    settings.ground_height + rest.ankle_height,
+                    c.translation().z,
+                );
+                let pole = b.translation() + root_transform.rotation * Vec3::Z;
+                let solution = solve_two_bone(
+                    a.translation(), b.translation(), c.translation(), target, pole, 0.);
+
+                let foot_world = root_transform.rotation * rest.rotation
+                    * Quat::from_rotation_x(settings.foot_roll.to_radians());
+                solutions.push(LegSolution {
+                    upper: (upper, a.compute_transform().rotation),
+                    lower: (lower, b.compute_transform().rotation),
+                    foot: (foot, c_rotation),
+                    solution,
+                    foot_world,
+                });
+            }
+        }
+
+        let mut transforms = params.p1();
+        for leg in solutions {
+            let Ok([mut upper, mut lower, mut foot]) = transforms.get_many_mut(
+                [leg.upper.0, leg.lower.0, leg.foot.0]) else {
+                continue;
+            };
+
+            leg.solution.apply(&mut upper, leg.upper.1, &mut lower, leg.lower.1);
+            // Keep the sole flat on the floor, regardless of the leg.
+            let inherited = leg.solution.root * leg.solution.mid * leg.foot.1;
+            foot.rotation = foot.rotation * inherited.inverse() * leg.foot_world;
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use bevy::prelude::*;
+
+use idol_api::FaceLandmark;
+
+use crate::expressions::ExpressionWeights;
+use crate::tracking::Faces;
+
+/// Indices of the MediaPipe face mesh landmarks around the brows, on the
+/// subject's own left and right.
+mod landmarks {
+    pub const LEFT_BROW_INNER: usize = 336;
+    pub const LEFT_BROW_OUTER: usize = 300;
+    pub const LEFT_EYE_INNER: usize = 362;
+    pub const LEFT_EYE_OUTER: usize = 263;
+    pub const RIGHT_BROW_INNER: usize = 107;
+    pub const RIGHT_BROW_OUTER: usize = 70;
+    pub const RIGHT_EYE_INNER: usize = 133;
+    pub const RIGHT_EYE_OUTER: usize = 33;
+}
+
+/// The landmarks and blend shapes of one brow.
+struct BrowSide {
+    brow_inner: usize,
+    brow_outer: usize,
+    eye_inner: usize,
+    eye_outer: usize,
+    down: &'static str,
+    outer_up: &'static str,
+}
+
+const SIDES: [BrowSide; 2] = [
+    BrowSide {
+        brow_inner: landmarks::LEFT_BROW_INNER,
+        brow_outer: landmarks::LEFT_BROW_OUTER,
+        eye_inner: landmarks::LEFT_EYE_INNER,
+        eye_outer: landmarks::LEFT_EYE_OUTER,
+        down: "browDownLeft",
+        outer_up: "browOuterUpLeft",
+    },
+    BrowSide {
+        brow_inner: landmarks::RIGHT_BROW_INNER,
+        brow_outer: landmarks::RIGHT_BROW_OUTER,
+        eye_inner: landmarks::RIGHT_EYE_INNER,
+        eye_outer: landmarks::RIGHT_EYE_OUTER,
+        down: "browDownRight",
+        outer_up: "browOuterUpRight",
+    },
+];
+
+const INNER_UP: &str = "browInnerUp";
+
+/// Derive the ARKit brow blend shapes from face mesh landmarks, for trackers
+/// which send landmarks without them.
+///
+/// Each brow's height is its distance from the eye corner below it, as a
+/// fraction of the distance between the outer eye corners. The neutral
+/// height is learned as a slow average, since brows are at rest most of the
+/// time.
+#[derive(Debug, Clone, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct BrowLandmarks {
+    pub enabled: bool,
+    /// The rise, as a fraction of the eye span, of a fully raised brow.
+    pub raise_range: f32,
+    /// The drop, as a fraction of the eye span, of a fully furrowed brow.
+    pub furrow_range: f32,
+    /// Time constant, in seconds, of the neutral height average.
+    pub neutral_time: f32,
+    /// The neutral inner and outer height of the left and then right brow.
+    #[reflect(ignore)]
+    neutral: Option<[f32; 4]>,
+    /// When the neutral height was last updated, in seconds since startup.
+    #[reflect(ignore)]
+    last_sample: Option<f32>,
+}
+
+impl Default for BrowLandmarks {
+    fn default() -> Self {
+        BrowLandmarks {
+            enabled: true,
+            raise_range: 0.1,
+            furrow_range: 0.05,
+            neutral_time: 20.,
+            neutral: None,
+            last_sample: None,
+        }
+    }
+}
+
+/// The inner and outer heights of the left and then right brow.
+fn brow_heights(landmarks: &[FaceLandmark]) -> Option<[f32; 4]> {
+    let position = |index: usize| landmarks.get(index).map(|landmark| landmark.position);
+    let span = position(landmarks::LEFT_EYE_OUTER)?.distance(position(landmarks::RIGHT_EYE_OUTER)?);
+    if span <= f32::EPSILON {
+        return None;
+    }
+
+    let mut heights = [0.; 4];
+    for (side, heights) in SIDES.iter().zip(heights.chunks_exact_mut(2)) {
+        heights[0] = position(side.brow_inner)?.distance(position(side.eye_inner)?) / span;
+        heights[1] = position(side.brow_outer)?.distance(position(side.eye_outer)?) / span;
+    }
+    Some(heights)
+}
+
+pub fn derive_brow_blend_shapes(
+    time: Res<Time>,
+    mut settings: ResMut<BrowLandmarks>,
+    mut faces: ResMut<Faces>,
+) {
+    if !settings.enabled || !faces.is_changed() {
+        return;
+    }
+    let Some(face) = faces.bypass_change_detection().faces.first_mut() else {
+        return;
+    };
+    let tracked = SIDES.iter()
+        .flat_map(|side| [side.down, side.outer_up])
+        .chain([INNER_UP])
+        .any(|name| face.blend_shapes.contains_key(name));
+    if tracked {
+        return;
+    }
+    let Some(heights) = brow_heights(&face.landmarks) else {
+        return;
+    };
+
+    // Faces only change when a frame arrives, so average over the time since
+    // the last one rather than since the last app frame.
+    let now = time.elapsed_seconds();
+    let dt = settings.last_sample.replace(now).map_or(0., |last| now - last);
+    let t = 1. - (-dt / settings.neutral_time.max(f32::EPSILON)).exp();
+    let neutral = settings.neutral.get_or_insert(heights);
+    for (neutral, height) in neutral.iter_mut().zip(heights) {
+        *neutral += (height - *neutral) * t;
+    }
+    let neutral = *neutral;
+
+    let mut inner_up = 0.;
+    for (i, side) in SIDES.iter().enumerate() {
+        let inner = heights[i * 2] - neutral[i * 2];
+        let outer = heights[i * 2 + 1] - neutral[i * 2 + 1];
+        inner_up += (inner / settings.raise_range).clamp(0., 1.) / 2.;
+        face.blend_shapes.insert(side.outer_up.to_string(), (outer / settings.raise_range).clamp(0., 1.));
+        face.blend_shapes.insert(side.down.to_string(), (-inner / settings.furrow_range).clamp(0., 1.));
+    }
+    face.blend_shapes.insert(INNER_UP.to_string(), inner_up);
+}
+
+const BROW_DOWN: &str = "browDown";
+const BROW_OUTER_UP: &str = "browOuterUp";
+
+/// The expression for both sides which a one-sided brow blend shape is
+/// combined into, if any.
+pub fn combined_brow(name: &str) -> Option<&'static str> {
+    let [left, right] = &SIDES;
+    if name == left.down || name == right.down {
+        Some(BROW_DOWN)
+    } else if name == left.outer_up || name == right.outer_up {
+        Some(BROW_OUTER_UP)
+    } else {
+        None
+    }
+}
+
+fn combine_brows(expressions: &mut ExpressionWeights) {
+    let [left, right] = &SIDES;
+    for (name, left, right) in [(BROW_DOWN, left.down, right.down), (BROW_OUTER_UP, left.outer_up, right.outer_up)] {
+        if expressions.index_of(left).is_some() || expressions.index_of(right).is_some() {
+            let weight = expressions.get(left).max(expressions.get(right));
+            expressions.set(name, weight);
+        }
+    }
+}
+
+/// Combine each pair of brow blend shapes into one for both sides, so that
+/// avatars with only symmetric brow morphs (such as VRoid's) can alias them.
+pub fn combine_brow_expressions(mut expressions: ResMut<ExpressionWeights>) {
+    combine_brows(&mut expressions);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Face mesh landmarks with the eye corners a unit apart, and each brow
+    /// point at the given height above the eye corner below it.
+    fn face_landmarks(heights: [f32; 4]) -> Vec<FaceLandmark> {
+        let mut positions = vec![Vec3::ZERO; 478];
+        positions[landmarks::LEFT_EYE_INNER] = Vec3::new(0.2, 0., 0.);
+        positions[landmarks::LEFT_EYE_OUTER] = Vec3::new(0.5, 0., 0.);
+        positions[landmarks::RIGHT_EYE_INNER] = Vec3::new(-0.2, 0., 0.);
+        positions[landmarks::RIGHT_EYE_OUTER] = Vec3::new(-0.5, 0., 0.);
+        positions[landmarks::LEFT_BROW_INNER] = Vec3::new(0.2, heights[0], 0.);
+        positions[landmarks::LEFT_BROW_OUTER] = Vec3::new(0.5, heights[1], 0.);
+        positions[landmarks::RIGHT_BROW_INNER] = Vec3::new(-0.2, heights[2], 0.);
+        positions[landmarks::RIGHT_BROW_OUTER] = Vec3::new(-0.5, heights[3], 0.);
+        positions.into_iter()
+            .map(|position| FaceLandmark {
+                position,
+                presence: None,
+                visibility: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn brow_heights_are_relative_to_eye_span() {
+        let heights = brow_heights(&face_landmarks([0.1, 0.2, 0.3, 0.4])).unwrap();
+        for (height, expected) in heights.iter().zip([0.1, 0.2, 0.3, 0.4]) {
+            assert!((height - expected).abs() < 1e-5, "{heights:?}");
+        }
+
+        let mut scaled = face_landmarks([0.1, 0.2, 0.3, 0.4]);
+        for landmark in &mut scaled {
+            landmark.position *= 2.;
+        }
+        assert_eq!(brow_heights(&scaled), Some(heights));
+    }
+
+    #[test]
+    fn brow_heights_need_landmarks() {
+        assert_eq!(brow_heights(&face_landmarks([0.1; 4])[..300]), None);
+        assert_eq!(brow_heights(&[]), None);
+    }
+
+    #[test]
+    fn combine_takes_the_stronger_side() {
+        let mut expressions = ExpressionWeights::default();
+        expressions.set("browDownLeft", 0.2);
+        expressions.set("browDownRight", 0.6);
+        expressions.set("browOuterUpLeft", 0.5);
+        combine_brows(&mut expressions);
+        assert_eq!(expressions.get(BROW_DOWN), 0.6);
+        assert_eq!(expressions.get(BROW_OUTER_UP), 0.5);
+    }
+
+    #[test]
+    fn combine_skips_untracked_brows() {
+        let mut expressions = ExpressionWeights::default();
+        expressions.set("browInnerUp", 1.);
+        combine_brows(&mut expressions);
+        assert_eq!(expressions.index_of(BROW_DOWN), None);
+        assert_eq!(expressions.index_of(BROW_OUTER_UP), None);
+    }
+
+    #[test]
+    fn one_sided_brows_combine_into_both() {
+        assert_eq!(combined_brow("browDownRight"), Some(BROW_DOWN));
+        assert_eq!(combined_brow("browOuterUpLeft"), Some(BROW_OUTER_UP));
+        assert_eq!(combined_brow("browInnerUp"), None);
+    }
+}
@@ -0,0 +1,213 @@
+use std::path::PathBuf;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use idol_api::{ApiError, CalibrateRequest, ErrorCategory, GetCalibrationProfilesResponse};
+
+use crate::error::{IdolError, write_file};
+use crate::tracking::Face;
+
+/// The profile used when no name is given and there is no camera to key on.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Corrections for where a camera sits relative to the performer, applied
+/// to the tracked head transform before anything else uses it.
+#[derive(Debug, Clone, Resource, Reflect, Serialize, Deserialize)]
+#[reflect(Resource)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Calibration {
+    /// The tracked head rotation when looking straight ahead.
+    pub neutral_rotation: Quat,
+    /// The tracked head translation at rest, about which movement is scaled.
+    pub neutral_translation: Vec3,
+    /// Scale of tracked head movement, from the tracker's units to metres.
+    pub translation_scale: f32,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Calibration {
+            neutral_rotation: Quat::IDENTITY,
+            neutral_translation: Vec3::ZERO,
+            translation_scale: 1.,
+        }
+    }
+}
+
+impl Calibration {
+    pub fn apply(&self, face: &mut Face) {
+        let transform = &mut face.transform;
+        transform.rotation = self.neutral_rotation.inverse() * transform.rotation;
+        transform.translation = self.neutral_translation
+            + (transform.translation - self.neutral_translation) * self.translation_scale;
+    }
+
+    /// Take the transform of a face which has already been calibrated as the
+    /// new neutral pose.
+    pub fn set_neutral(&mut self, transform: &Transform) {
+        self.neutral_rotation = (self.neutral_rotation * transform.rotation).normalize();
+        if self.translation_scale.abs() > f32::EPSILON {
+            self.neutral_translation += (transform.translation - self.neutral_translation)
+                / self.translation_scale;
+        }
+    }
+}
+
+/// Calibrations saved to disk, one per camera setup, as `<name>.json` in
+/// `--calibration-dir`.
+#[derive(Debug, Resource)]
+pub struct CalibrationProfiles {
+    pub dir: PathBuf,
+    /// The profile which calibration changes are saved to.
+    pub active: String,
+}
+
+impl CalibrationProfiles {
+    pub fn new(dir: PathBuf, active: &str) -> CalibrationProfiles {
+        CalibrationProfiles {
+            dir,
+            active: profile_name(active),
+        }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", profile_name(name)))
+    }
+
+    /// The names of every saved profile, sorted.
+    pub fn list(&self) -> Result<Vec<String>, IdolError> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(IdolError::read(&self.dir, err)),
+        };
+
+        let mut names = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(|err| IdolError::read(&self.dir, err))?.path();
+            if path.extension().map_or(false, |extension| extension == "json") {
+                if let Some(name) = path.file_stem().and_then(|name| name.to_str()) {
+                    names.push(name.to_string());
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Read a profile, or `None` if it hasn't been saved.
+    pub fn load(&self, name: &str) -> Result<Option<Calibration>, IdolError> {
+        let path = self.path(name);
+        let data = match std::fs::read(&path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(IdolError::read(&path, err)),
+        };
+        serde_json::from_slice(&data)
+            .map(Some)
+            .map_err(|err| IdolError::parse(&path, err))
+    }
+
+    /// Save the calibration to the active profile.
+    pub fn save(&self, calibration: &Calibration) -> Result<(), IdolError> {
+        std::fs::create_dir_all(&self.dir).map_err(|err| IdolError::write(&self.dir, err))?;
+        let data = serde_json::to_vec_pretty(calibration)
+            .expect("calibration is always serializable");
+        write_file(self.path(&self.active), data)
+    }
+
+    /// Delete a profile, returning whether it existed.
+    pub fn delete(&self, name: &str) -> Result<bool, IdolError> {
+        let path = self.path(name);
+        match std::fs::remove_file(&path) {
+            Ok(()) => Ok(true),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(IdolError::write(&path, err)),
+        }
+    }
+}
+
+/// Turn a profile name or camera id, such as a device path, into a name
+/// which is safe to use as a file name.
+pub fn profile_name(name: &str) -> String {
+    let name: String = name.trim_start_matches("/dev/")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    if name.is_empty() {
+        DEFAULT_PROFILE.to_string()
+    } else {
+        name
+    }
+}
+
+/// Read the active profile at startup, falling back to no correction if it
+/// hasn't been saved yet or can't be read.
+pub fn load_active_profile(profiles: &CalibrationProfiles) -> Calibration {
+    match profiles.load(&profiles.active) {
+        Ok(Some(calibration)) => {
+            info!("using calibration profile {}", profiles.active);
+            calibration
+        }
+        Ok(None) => Calibration::default(),
+        Err(err) => {
+            warn!("{err}");
+            Calibration::default()
+        }
+    }
+}
+
+#[derive(SystemParam)]
+pub struct CalibrationParams<'w> {
+    calibration: ResMut<'w, Calibration>,
+    profiles: ResMut<'w, CalibrationProfiles>,
+}
+
+impl<'w> CalibrationParams<'w> {
+    pub fn apply(&self, face: &mut Face) {
+        self.calibration.apply(face);
+    }
+
+    /// Update the calibration from the current face's calibrated transform,
+    /// and save it.
+    pub fn calibrate(&mut self, request: &CalibrateRequest, face: Option<&Transform>) -> Result<(), ApiError> {
+        if request.neutral {
+            let face = face.ok_or_else(|| ApiError::with_message(
+                ErrorCategory::FailedPrecondition, "not_tracking", "no face is being tracked"))?;
+            self.calibration.set_neutral(face);
+        }
+        if let Some(scale) = request.translation_scale {
+            self.calibration.translation_scale = scale;
+        }
+        self.profiles.save(&self.calibration)
+            .map_err(|err| ApiError::unknown(err.to_string()))
+    }
+
+    /// Switch to a profile, loading its calibration if it has been saved.
+    pub fn select(&mut self, name: &str) -> Result<(), ApiError> {
+        let calibration = self.profiles.load(name)
+            .map_err(|err| ApiError::unknown(err.to_string()))?;
+        self.profiles.active = profile_name(name);
+        *self.calibration = calibration.unwrap_or_default();
+        Ok(())
+    }
+
+    /// Delete a saved profile. Deleting the active profile leaves its
+    /// calibration in use until the next switch.
+    pub fn delete(&mut self, name: &str) -> Result<(), ApiError> {
+        match self.profiles.delete(name) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(ApiError::not_found(format!("no calibration profile named {name}"))),
+            Err(err) => Err(ApiError::unknown(err.to_string())),
+        }
+    }
+
+    pub fn snapshot(&self) -> Result<GetCalibrationProfilesResponse, ApiError> {
+        Ok(GetCalibrationProfilesResponse {
+            profiles: self.profiles.list().map_err(|err| ApiError::unknown(err.to_string()))?,
+            active: self.profiles.active.clone(),
+        })
+    }
+}
@@ -1,7 +1,310 @@
-use bevy::prelude::Component;
+use bevy::core_pipeline::dof::DepthOfFieldSettings;
+use bevy::core_pipeline::experimental::taa::{TemporalAntiAliasBundle, TemporalAntiAliasSettings};
+use bevy::core_pipeline::fxaa::Fxaa;
+use bevy::core_pipeline::tonemapping::Tonemapping;
+use bevy::ecs::system::EntityCommands;
+use bevy::log::warn;
+use bevy::prelude::{Assets, Commands, Component, Entity, GlobalTransform, Msaa, OrthographicProjection, PerspectiveProjection, Projection, Query, Reflect, Res, ResMut, Resource, Time, Transform, With};
+use bevy::render::camera::ScalingMode;
+use bevy::render::view::{ColorGrading, ColorGradingGlobal, ColorGradingSection};
+use clap::ValueEnum;
+
+use bevy_vrm::extensions::mtoon::MToonMaterial;
+use bevy_vrm::extensions::vrm::{Humanoid, HumanoidBone};
+
+use crate::tracking::Faces;
 
 #[derive(Component)]
 pub struct PreviewCamera;
 
 #[derive(Component)]
 pub struct OutputCamera;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ProjectionKind {
+    #[default]
+    Perspective,
+    Orthographic,
+}
+
+/// Post-process anti-aliasing applied to a single camera, on top of MSAA.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum PostAntiAliasing {
+    #[default]
+    None,
+    Fxaa,
+    /// Temporal anti-aliasing, for PBR avatars. TAA cannot be combined with
+    /// MSAA, which is disabled whenever any camera uses it. MToon is drawn
+    /// forward without motion vectors, so TAA would ghost it, and cameras
+    /// fall back to FXAA once MToon materials load.
+    Taa,
+}
+
+impl PostAntiAliasing {
+    pub fn insert(self, entity: &mut EntityCommands) {
+        match self {
+            PostAntiAliasing::None => {}
+            PostAntiAliasing::Fxaa => {
+                entity.insert(Fxaa::default());
+            }
+            PostAntiAliasing::Taa => {
+                entity.insert(TemporalAntiAliasBundle::default());
+            }
+        }
+    }
+}
+
+/// The MSAA requested on the command line, restored if TAA falls back.
+#[derive(Debug, Clone, Copy, Resource)]
+pub struct TaaFallback {
+    pub msaa: Msaa,
+}
+
+/// Replace TAA with FXAA once an MToon material has loaded, since MToon
+/// writes no motion vectors and TAA would leave ghosts behind it.
+pub fn fall_back_from_taa(
+    mut commands: Commands,
+    fallback: Res<TaaFallback>,
+    mut msaa: ResMut<Msaa>,
+    mtoon_materials: Res<Assets<MToonMaterial>>,
+    cameras: Query<Entity, With<TemporalAntiAliasSettings>>,
+) {
+    if cameras.is_empty() || mtoon_materials.is_empty() {
+        return;
+    }
+
+    warn!("TAA ghosts with MToon materials, which have no motion vectors; using FXAA instead");
+    for entity in &cameras {
+        commands.entity(entity)
+            .remove::<TemporalAntiAliasBundle>()
+            .insert(Fxaa::default());
+    }
+    *msaa = fallback.msaa;
+}
+
+/// Tonemapping for a single camera. Toon avatars usually want none, as it
+/// shifts their flat colours, while PBR avatars look flat without it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum TonemappingKind {
+    #[default]
+    None,
+    Reinhard,
+    ReinhardLuminance,
+    Aces,
+    Agx,
+    SomewhatBoringDisplayTransform,
+    TonyMcMapface,
+    BlenderFilmic,
+}
+
+impl TonemappingKind {
+    pub fn tonemapping(self) -> Tonemapping {
+        match self {
+            TonemappingKind::None => Tonemapping::None,
+            TonemappingKind::Reinhard => Tonemapping::Reinhard,
+            TonemappingKind::ReinhardLuminance => Tonemapping::ReinhardLuminance,
+            TonemappingKind::Aces => Tonemapping::AcesFitted,
+            TonemappingKind::Agx => Tonemapping::AgX,
+            TonemappingKind::SomewhatBoringDisplayTransform => Tonemapping::SomewhatBoringDisplayTransform,
+            TonemappingKind::TonyMcMapface => Tonemapping::TonyMcMapface,
+            TonemappingKind::BlenderFilmic => Tonemapping::BlenderFilmic,
+        }
+    }
+}
+
+pub fn msaa_from_samples(samples: u32) -> anyhow::Result<Msaa> {
+    Ok(match samples {
+        0 | 1 => Msaa::Off,
+        2 => Msaa::Sample2,
+        4 => Msaa::Sample4,
+        8 => Msaa::Sample8,
+        n => anyhow::bail!("unsupported MSAA sample count: {n}"),
+    })
+}
+
+/// Field of view and clipping planes for a camera. Unset planes keep Bevy's
+/// defaults for the projection.
+#[derive(Debug, Clone, Copy)]
+pub struct CameraLens {
+    /// Vertical field of view of perspective projections, in degrees.
+    pub fov: f32,
+    pub near: Option<f32>,
+    pub far: Option<f32>,
+}
+
+impl CameraLens {
+    /// Build the projection for a camera.
+    ///
+    /// For orthographic projections, `ortho_height` is the vertical extent of
+    /// the view in world units, so the framing stays fixed regardless of window
+    /// size.
+    pub fn projection(self, kind: ProjectionKind, ortho_height: f32) -> Projection {
+        match kind {
+            ProjectionKind::Perspective => {
+                let default = PerspectiveProjection::default();
+                Projection::Perspective(PerspectiveProjection {
+                    fov: self.fov.to_radians(),
+                    near: self.near.unwrap_or(default.near),
+                    far: self.far.unwrap_or(default.far),
+                    ..default
+                })
+            }
+            ProjectionKind::Orthographic => {
+                let default = OrthographicProjection::default();
+                Projection::Orthographic(OrthographicProjection {
+                    near: self.near.unwrap_or(default.near),
+                    far: self.far.unwrap_or(default.far),
+                    scaling_mode: ScalingMode::FixedVertical(ortho_height),
+                    ..default
+                })
+            }
+        }
+    }
+
+    /// Check the near plane is closer than the far plane, once any defaults
+    /// for the projection are filled in, and in front of the camera for
+    /// perspective projections.
+    pub fn validate(self, kind: ProjectionKind) -> anyhow::Result<()> {
+        let (near, far) = match self.projection(kind, 1.) {
+            Projection::Perspective(perspective) => {
+                anyhow::ensure!(perspective.near > 0., "perspective near plane must be positive");
+                (perspective.near, perspective.far)
+            }
+            Projection::Orthographic(orthographic) => (orthographic.near, orthographic.far),
+        };
+        anyhow::ensure!(near < far, "near plane ({near}) must be closer than the far plane ({far})");
+        Ok(())
+    }
+}
+
+/// Exposure (in stops) and gamma applied to the output camera only, so the
+/// captured output can be matched to the preview in whatever consumes it.
+///
+/// Exposure 0 and gamma 1 leave the image unchanged.
+pub fn output_color_grading(exposure: f32, gamma: f32) -> ColorGrading {
+    ColorGrading::with_identical_sections(
+        ColorGradingGlobal {
+            exposure,
+            ..ColorGradingGlobal::default()
+        },
+        ColorGradingSection {
+            gamma,
+            ..ColorGradingSection::default()
+        },
+    )
+}
+
+/// Keep the output camera's depth of field focused on the avatar's head.
+pub fn update_depth_of_field(
+    humanoids: Query<&Humanoid>,
+    transforms: Query<&GlobalTransform>,
+    mut cameras: Query<(&GlobalTransform, &mut DepthOfFieldSettings), With<OutputCamera>>,
+) {
+    let Some(head) = humanoids.iter()
+        .find_map(|humanoid| humanoid.bones.get(&HumanoidBone::Head))
+        .and_then(|head| transforms.get(*head).ok()) else {
+        return;
+    };
+
+    for (camera_transform, mut dof) in &mut cameras {
+        let offset = head.translation() - camera_transform.translation();
+        let distance = offset.dot(*camera_transform.forward());
+        if distance > 0. {
+            dof.focal_distance = distance;
+        }
+    }
+}
+
+// Limits of the field of view while dolly zooming, in degrees.
+const DOLLY_ZOOM_MIN_FOV: f32 = 5.;
+const DOLLY_ZOOM_MAX_FOV: f32 = 120.;
+// How quickly the dolly zoom follows the tracked distance, per second.
+const DOLLY_ZOOM_SPEED: f32 = 8.;
+
+/// Convey the first tracked face leaning towards or away from the webcam
+/// with a dolly zoom of the output camera, rather than by moving the head.
+///
+/// The camera moves along its view direction while the field of view changes
+/// to keep the head of the first avatar with one the same size in frame,
+/// within the field of view limits. Other faces and avatars are ignored.
+#[derive(Debug, Clone, Component, Reflect)]
+pub struct DollyZoom {
+    /// How much the camera distance follows the tracked distance. 0 disables
+    /// the effect, 1 moves the camera in proportion to the head.
+    pub strength: f32,
+    /// Tracked distance of the head from the webcam which leaves the camera
+    /// where it started. If unset, the first tracked distance is used.
+    pub reference_distance: Option<f32>,
+    // Smoothed ratio of the tracked distance to the reference.
+    #[reflect(ignore)]
+    ratio: f32,
+    // The camera's transform and field of view before zooming.
+    #[reflect(ignore)]
+    rest: Option<(Transform, f32)>,
+}
+
+impl DollyZoom {
+    pub fn new(strength: f32, reference_distance: Option<f32>) -> DollyZoom {
+        DollyZoom {
+            strength,
+            reference_distance,
+            ratio: 1.,
+            rest: None,
+        }
+    }
+}
+
+pub fn update_dolly_zoom(
+    time: Res<Time>,
+    faces: Res<Faces>,
+    humanoids: Query<&Humanoid>,
+    globals: Query<&GlobalTransform>,
+    mut cameras: Query<(&mut Transform, &mut Projection, &mut DollyZoom), With<OutputCamera>>,
+) {
+    let head = humanoids.iter()
+        .find_map(|humanoid| humanoid.bones.get(&HumanoidBone::Head))
+        .and_then(|head| globals.get(*head).ok())
+        .map(|head| head.translation());
+
+    for (mut transform, mut projection, mut zoom) in &mut cameras {
+        let Projection::Perspective(perspective) = projection.as_mut() else {
+            continue;
+        };
+        let (rest, rest_fov) = *zoom.rest.get_or_insert((*transform, perspective.fov));
+
+        let target_ratio = match faces.faces.first() {
+            Some(face) if zoom.strength > 0. => {
+                let distance = face.transform.translation.length();
+                let reference = *zoom.reference_distance.get_or_insert(distance);
+                if reference > f32::EPSILON {
+                    (distance / reference).powf(zoom.strength)
+                } else {
+                    1.
+                }
+            }
+            _ => 1.,
+        };
+        zoom.ratio += (target_ratio - zoom.ratio) * (1. - (-DOLLY_ZOOM_SPEED * time.delta_seconds()).exp());
+        if zoom.strength <= 0. && (zoom.ratio - 1.).abs() < 1e-4 {
+            continue;
+        }
+
+        let Some(head) = head else {
+            continue;
+        };
+        let forward = rest.forward();
+        let rest_distance = (head - rest.translation).dot(*forward);
+        if rest_distance <= f32::EPSILON {
+            continue;
+        }
+
+        // Keep distance * tan(fov / 2) constant, so the head stays framed.
+        let extent = rest_distance * (rest_fov * 0.5).tan();
+        let fov = (2. * (extent / (rest_distance * zoom.ratio)).atan())
+            .clamp(DOLLY_ZOOM_MIN_FOV.to_radians(), DOLLY_ZOOM_MAX_FOV.to_radians());
+        let distance = extent / (fov * 0.5).tan();
+
+        transform.translation = rest.translation + forward * (rest_distance - distance);
+        perspective.fov = fov;
+    }
+}
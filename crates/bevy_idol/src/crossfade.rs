@@ -0,0 +1,92 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use bevy_vrm::{Vrm, VrmBundle};
+
+use crate::avatars::AvatarSet;
+use crate::limits::BoneLimits;
+
+/// Cross-fade between avatars when switching, rather than swapping
+/// instantly.
+#[derive(Debug, Clone, Default, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct AvatarCrossfade {
+    /// Length of the cross-fade, in seconds. Zero swaps instantly.
+    pub duration: f32,
+}
+
+/// The opacity of one avatar, on top of the global
+/// [`AvatarFade`](crate::fade::AvatarFade).
+#[derive(Debug, Clone, Copy, Component, Reflect)]
+#[reflect(Component)]
+pub struct AvatarOpacity(pub f32);
+
+/// A copy of the avatar being switched away from, which fades out while
+/// its replacement fades in and is then despawned.
+///
+/// It is a humanoid like any other, so tracking keeps driving it and the two
+/// poses match during the overlap.
+#[derive(Debug, Clone, Copy, Component)]
+pub struct OutgoingAvatar;
+
+/// Start a cross-fade whenever an avatar set switches avatar.
+///
+/// This must run after anything which switches avatars, but before
+/// [`bevy_vrm::spawn_vrms`] swaps in the new scene.
+pub fn start_crossfades(
+    mut commands: Commands,
+    settings: Res<AvatarCrossfade>,
+    mut previous: Local<HashMap<Entity, Handle<Vrm>>>,
+    avatars: Query<(Entity, &Handle<Vrm>, &Transform, Option<&BoneLimits>), With<AvatarSet>>,
+) {
+    for (entity, vrm, transform, limits) in &avatars {
+        let Some(old) = previous.insert(entity, vrm.clone()) else {
+            continue;
+        };
+        if settings.duration <= 0. || old == *vrm {
+            continue;
+        }
+
+        let mut outgoing = commands.spawn((
+            Name::from("Outgoing Avatar"),
+            VrmBundle {
+                vrm: old,
+                transform: *transform,
+                ..default()
+            },
+            AvatarOpacity(1.),
+            OutgoingAvatar,
+        ));
+        if let Some(limits) = limits {
+            outgoing.insert(limits.clone());
+        }
+        commands.entity(entity).insert(AvatarOpacity(0.));
+    }
+}
+
+pub fn update_crossfades(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<AvatarCrossfade>,
+    mut avatars: Query<(Entity, &mut AvatarOpacity, Has<OutgoingAvatar>)>,
+) {
+    let step = if settings.duration > 0. {
+        time.delta_seconds() / settings.duration
+    } else {
+        1.
+    };
+
+    for (entity, mut opacity, outgoing) in &mut avatars {
+        if outgoing {
+            opacity.0 -= step;
+            if opacity.0 <= 0. {
+                commands.entity(entity).despawn_recursive();
+            }
+        } else {
+            opacity.0 += step;
+            if opacity.0 >= 1. {
+                commands.entity(entity).remove::<AvatarOpacity>();
+            }
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use std::fmt::Write;
+
 use anyhow::{anyhow};
 use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
 use bevy::asset::io::Reader;
@@ -7,6 +9,8 @@ use bevy::render::render_asset::RenderAssetUsages;
 use bevy::render::render_resource::PrimitiveTopology;
 use bevy::utils::ConditionalSendFuture;
 
+use idol_api::FaceLandmark;
+
 #[derive(Default)]
 pub struct DebugMeshLoader;
 
@@ -115,3 +119,27 @@ impl AssetLoader for DebugMeshLoader {
         &["dobj"]
     }
 }
+
+/// Write landmarks as an OBJ file, the inverse of [`DebugMeshLoader`].
+///
+/// If `mesh` is given (normally the canonical face model), its triangles are
+/// written as faces, skipping any which refer to missing landmarks.
+pub fn landmarks_to_obj(landmarks: &[FaceLandmark], mesh: Option<&Mesh>) -> String {
+    let mut out = String::new();
+    for landmark in landmarks {
+        let p = landmark.position;
+        writeln!(&mut out, "v {} {} {}", p.x, p.y, p.z).unwrap();
+    }
+
+    let indices = mesh.and_then(|mesh| mesh.indices());
+    if let Some(indices) = indices {
+        let indices: Vec<usize> = indices.iter().collect();
+        for triangle in indices.chunks_exact(3) {
+            if triangle.iter().all(|&i| i < landmarks.len()) {
+                writeln!(&mut out, "f {} {} {}", triangle[0] + 1, triangle[1] + 1, triangle[2] + 1).unwrap();
+            }
+        }
+    }
+
+    out
+}
@@ -0,0 +1,59 @@
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+/// A recoverable failure, with a message aimed at whoever is running the
+/// app rather than at its developers.
+#[derive(Error, Debug)]
+pub enum IdolError {
+    #[error("failed to read {}: {source}", path.display())]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("failed to write {}: {source}", path.display())]
+    Write {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("{} is not valid: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    #[cfg(all(feature = "webcam-capture", target_os = "linux"))]
+    #[error("failed to open webcam {device}: {source}")]
+    Webcam {
+        device: String,
+        source: std::io::Error,
+    },
+}
+
+impl IdolError {
+    pub fn read(path: &Path, source: std::io::Error) -> IdolError {
+        IdolError::Read {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    pub fn write(path: &Path, source: std::io::Error) -> IdolError {
+        IdolError::Write {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    pub fn parse(path: &Path, source: serde_json::Error) -> IdolError {
+        IdolError::Parse {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+}
+
+/// Write a file, as [`std::fs::write`], naming it in any error.
+pub fn write_file(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> Result<(), IdolError> {
+    let path = path.as_ref();
+    std::fs::write(path, contents).map_err(|err| IdolError::write(path, err))
+}
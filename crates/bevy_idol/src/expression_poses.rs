@@ -0,0 +1,228 @@
+use std::path::Path;
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::expressions::ExpressionWeights;
+use crate::tracking::TrackingStatus;
+
+/// How an expression pose combines with the weights beneath it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Reflect)]
+#[serde(rename_all = "camelCase")]
+pub enum PoseBlendMode {
+    /// Take the larger of the pose and the existing weight.
+    #[default]
+    Max,
+    /// Add the pose to the existing weight.
+    Add,
+    /// Replace the existing weight with the pose.
+    Override,
+}
+
+/// A named set of blend shape weights which can be triggered as one.
+#[derive(Debug, Clone, Serialize, Deserialize, Reflect)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpressionPose {
+    pub name: String,
+    #[serde(default)]
+    pub mode: PoseBlendMode,
+    pub weights: HashMap<String, f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExpressionPosesConfig {
+    poses: Vec<ExpressionPose>,
+}
+
+fn builtin_pose(name: &str, weights: &[(&str, f32)]) -> ExpressionPose {
+    ExpressionPose {
+        name: name.to_string(),
+        mode: PoseBlendMode::Max,
+        weights: weights.iter().map(|(k, v)| (k.to_string(), *v)).collect(),
+    }
+}
+
+/// The poses which can be triggered, and how strongly each is applied.
+///
+/// The first nine poses can be toggled with the number keys.
+#[derive(Debug, Clone, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct ExpressionPoses {
+    pub poses: Vec<ExpressionPose>,
+    /// How long poses take to fade in and out, in seconds.
+    pub fade: f32,
+    targets: HashMap<String, f32>,
+    weights: HashMap<String, f32>,
+}
+
+impl Default for ExpressionPoses {
+    fn default() -> Self {
+        ExpressionPoses::new(vec![
+            builtin_pose("wink", &[
+                ("eyeBlinkLeft", 1.),
+                ("mouthSmileLeft", 0.6),
+                ("mouthSmileRight", 0.6),
+            ]),
+            builtin_pose("smile", &[
+                ("mouthSmileLeft", 1.),
+                ("mouthSmileRight", 1.),
+                ("cheekSquintLeft", 0.5),
+                ("cheekSquintRight", 0.5),
+            ]),
+            builtin_pose("surprised", &[
+                ("eyeWideLeft", 1.),
+                ("eyeWideRight", 1.),
+                ("browInnerUp", 1.),
+                ("jawOpen", 0.4),
+            ]),
+            builtin_pose("idle", &[
+                ("mouthSmileLeft", 0.25),
+                ("mouthSmileRight", 0.25),
+                ("eyeSquintLeft", 0.1),
+                ("eyeSquintRight", 0.1),
+            ]),
+        ])
+    }
+}
+
+impl ExpressionPoses {
+    pub fn new(poses: Vec<ExpressionPose>) -> ExpressionPoses {
+        ExpressionPoses {
+            poses,
+            fade: 0.15,
+            targets: HashMap::new(),
+            weights: HashMap::new(),
+        }
+    }
+
+    /// Load poses from a JSON file of the form `{"poses": [...]}`.
+    pub fn from_file(path: &Path) -> anyhow::Result<ExpressionPoses> {
+        let contents = std::fs::read(path)?;
+        let config: ExpressionPosesConfig = serde_json::from_slice(&contents)?;
+        Ok(ExpressionPoses::new(config.poses))
+    }
+
+    /// Set the weight a pose fades towards. Returns false if there is no pose
+    /// with that name.
+    pub fn set(&mut self, name: &str, weight: f32) -> bool {
+        if !self.poses.iter().any(|p| p.name == name) {
+            return false;
+        }
+
+        self.targets.insert(name.to_string(), weight.clamp(0., 1.));
+        true
+    }
+
+    pub fn toggle(&mut self, name: &str) -> bool {
+        let weight = if self.targets.get(name).copied().unwrap_or(0.) > 0. { 0. } else { 1. };
+        self.set(name, weight)
+    }
+}
+
+const POSE_KEYS: [KeyCode; 9] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+pub fn toggle_expression_poses(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut poses: ResMut<ExpressionPoses>,
+) {
+    for (index, key) in POSE_KEYS.iter().enumerate() {
+        if !keys.just_pressed(*key) {
+            continue;
+        }
+
+        if let Some(name) = poses.poses.get(index).map(|p| p.name.clone()) {
+            poses.toggle(&name);
+        }
+    }
+}
+
+pub fn apply_expression_poses(
+    time: Res<Time>,
+    mut poses: ResMut<ExpressionPoses>,
+    mut expressions: ResMut<ExpressionWeights>,
+) {
+    let poses = &mut *poses;
+    let step = if poses.fade > 0. { time.delta_seconds() / poses.fade } else { 1. };
+    for pose in &poses.poses {
+        let target = poses.targets.get(&pose.name).copied().unwrap_or(0.);
+        let weight = poses.weights.entry(pose.name.clone()).or_insert(0.);
+        *weight += (target - *weight).clamp(-step, step);
+        let weight = *weight;
+        if weight <= 0. {
+            continue;
+        }
+
+        blend_pose(pose, weight, &mut expressions);
+    }
+}
+
+fn blend_pose(pose: &ExpressionPose, weight: f32, expressions: &mut ExpressionWeights) {
+    for (name, value) in &pose.weights {
+        let current = expressions.get(name);
+        let blended = match pose.mode {
+            PoseBlendMode::Max => current.max(value * weight),
+            PoseBlendMode::Add => (current + value * weight).min(1.),
+            PoseBlendMode::Override => current + (value - current) * weight,
+        };
+        expressions.set(name, blended);
+    }
+}
+
+/// An expression pose held from startup until tracking first becomes active,
+/// and then faded out, so the avatar has something better than a blank stare
+/// while waiting.
+#[derive(Debug, Clone, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct IdlePose {
+    /// Name of the expression pose, or empty to disable.
+    pub pose: String,
+    /// How long the pose takes to fade out once tracking arrives, in seconds.
+    pub fade: f32,
+    weight: f32,
+    tracked: bool,
+}
+
+impl IdlePose {
+    pub fn new(pose: String, fade: f32) -> IdlePose {
+        IdlePose {
+            pose,
+            fade,
+            weight: 1.,
+            tracked: false,
+        }
+    }
+}
+
+pub fn apply_idle_pose(
+    time: Res<Time>,
+    tracking: Res<TrackingStatus>,
+    poses: Res<ExpressionPoses>,
+    mut idle: ResMut<IdlePose>,
+    mut expressions: ResMut<ExpressionWeights>,
+) {
+    if idle.pose.is_empty() || idle.weight <= 0. {
+        return;
+    }
+
+    idle.tracked |= tracking.active;
+    if idle.tracked {
+        let step = if idle.fade > 0. { time.delta_seconds() / idle.fade } else { 1. };
+        idle.weight = (idle.weight - step).max(0.);
+    }
+
+    if let Some(pose) = poses.poses.iter().find(|p| p.name == idle.pose) {
+        blend_pose(pose, idle.weight, &mut expressions);
+    }
+}
@@ -0,0 +1,172 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::eye_blinks::{DerivedBlink, DerivedBlinks, MeshBlinks};
+use crate::tracking::Faces;
+
+/// The blend shape weights to apply to the avatar this frame, by name.
+///
+/// These start each fixed timestep as the tracked face's blend shapes, and
+/// are then modified by each of the expression layers in turn. They are
+/// applied to the avatar every frame.
+///
+/// Each name keeps the same index once it has been seen, so meshes can map
+/// their morph targets to indices once, rather than looking up every name
+/// every frame. `generation` changes whenever a name is added.
+#[derive(Debug, Default, Resource)]
+pub struct ExpressionWeights {
+    indices: HashMap<String, usize>,
+    weights: Vec<f32>,
+    generation: u32,
+}
+
+impl ExpressionWeights {
+    pub fn get(&self, name: &str) -> f32 {
+        self.index_of(name).map_or(0., |index| self.weights[index])
+    }
+
+    pub fn set(&mut self, name: &str, weight: f32) {
+        match self.indices.get(name) {
+            Some(&index) => self.weights[index] = weight,
+            None => {
+                self.indices.insert(name.to_string(), self.weights.len());
+                self.weights.push(weight);
+                self.generation = self.generation.wrapping_add(1);
+            }
+        }
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.indices.get(name).copied()
+    }
+
+    pub fn get_index(&self, index: usize) -> f32 {
+        self.weights.get(index).copied().unwrap_or(0.)
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// Reset every weight to zero.
+    pub fn clear(&mut self) {
+        self.weights.fill(0.);
+    }
+
+    /// Reset the weights whose names match `predicate` to zero.
+    pub fn clear_matching(&mut self, predicate: impl Fn(&str) -> bool) {
+        for (name, &index) in &self.indices {
+            if predicate(name) {
+                self.weights[index] = 0.;
+            }
+        }
+    }
+}
+
+/// Maps each of a mesh's morph targets to its index in [`ExpressionWeights`].
+///
+/// Morph targets with an alias are driven by their canonical expression name
+/// if it has been seen, and otherwise by their own name. The blink morphs
+/// picked by [`MeshBlinks`] also follow the tracked blinks.
+///
+/// This is rebuilt when the mesh changes (such as when blend shapes are
+/// added), or when new expression names are seen.
+#[derive(Debug, Clone, Component)]
+pub struct MorphTargetIndices {
+    pub mesh: AssetId<Mesh>,
+    pub generation: u32,
+    pub indices: Vec<Option<usize>>,
+    /// The derived blink each morph target follows, if any.
+    pub blinks: Vec<Option<DerivedBlink>>,
+}
+
+impl MorphTargetIndices {
+    pub fn new(
+        mesh: AssetId<Mesh>,
+        names: &[String],
+        aliases: Option<&HashMap<String, String>>,
+        expressions: &ExpressionWeights,
+    ) -> MorphTargetIndices {
+        let canonical = |name: &String| aliases
+            .and_then(|aliases| aliases.get(name))
+            .map_or(name.as_str(), String::as_str);
+        let blinks = MeshBlinks::new(names.iter().map(canonical));
+        let index_of = |name: &String| aliases
+            .and_then(|aliases| aliases.get(name))
+            .and_then(|canonical| expressions.index_of(canonical))
+            .or_else(|| expressions.index_of(name));
+        MorphTargetIndices {
+            mesh,
+            generation: expressions.generation(),
+            indices: names.iter().map(index_of).collect(),
+            blinks: names.iter().map(|name| blinks.derived(canonical(name))).collect(),
+        }
+    }
+
+    /// The weight of each morph target, from the expressions and the derived
+    /// blinks, whichever is greater.
+    pub fn apply(&self, expressions: &ExpressionWeights, blinks: &DerivedBlinks, weights: &mut [f32]) {
+        let targets = self.indices.iter().zip(&self.blinks);
+        for ((index, blink), weight) in targets.zip(weights.iter_mut()) {
+            let expression = index.map_or(0., |index| expressions.get_index(index));
+            *weight = blink.map_or(expression, |blink| expression.max(blinks.get(blink)));
+        }
+    }
+
+    pub fn is_valid(&self, mesh: AssetId<Mesh>, expressions: &ExpressionWeights) -> bool {
+        self.mesh == mesh && self.generation == expressions.generation()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemSet)]
+pub enum ExpressionSystems {
+    /// Gather the tracked weights, in `FixedUpdate`.
+    Collect,
+    /// Layer other sources of expression on top, in `FixedUpdate`.
+    Layers,
+    /// Apply the final weights to the avatar, in `Update`.
+    Apply,
+}
+
+/// Which side of the face the tracker's left and right blend shapes refer to.
+#[derive(Debug, Clone, Default, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct TrackingSides {
+    /// Swap the left and right blend shapes, for trackers which name them from
+    /// the camera's perspective rather than the face's.
+    pub swap: bool,
+}
+
+/// The blend shape on the other side of the face, if `name` is one sided.
+fn other_side(name: &str) -> Option<String> {
+    if let Some(base) = name.strip_suffix("Left") {
+        Some(format!("{base}Right"))
+    } else {
+        name.strip_suffix("Right").map(|base| format!("{base}Left"))
+    }
+}
+
+// Held to force a tracked left blink, to check which eye the tracker's left is.
+const FORCE_BLINK_KEY: KeyCode = KeyCode::F10;
+
+pub fn collect_tracked_expressions(
+    faces: Res<Faces>,
+    sides: Res<TrackingSides>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut expressions: ResMut<ExpressionWeights>,
+) {
+    expressions.clear();
+    let mut set = |name: &str, weight: f32| match sides.swap.then(|| other_side(name)).flatten() {
+        Some(other) => expressions.set(&other, weight),
+        None => expressions.set(name, weight),
+    };
+
+    if let Some(face) = faces.faces.first() {
+        for (name, weight) in &face.blend_shapes {
+            set(name, *weight);
+        }
+    }
+    if keys.pressed(FORCE_BLINK_KEY) {
+        set("eyeBlinkLeft", 1.);
+    }
+}
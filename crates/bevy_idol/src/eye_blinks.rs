@@ -0,0 +1,154 @@
+use bevy::prelude::*;
+use clap::ValueEnum;
+
+use crate::expressions::ExpressionWeights;
+
+const EYE_BLINK_LEFT: &str = "eyeBlinkLeft";
+const EYE_BLINK_RIGHT: &str = "eyeBlinkRight";
+const BLINK: &str = "blink";
+const BLINK_LEFT: &str = "blinkLeft";
+const BLINK_RIGHT: &str = "blinkRight";
+
+/// How the tracked left and right blinks drive a combined blink morph.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Reflect)]
+pub enum BlinkCombine {
+    /// Close both eyes when either closes, so winks still read.
+    #[default]
+    Max,
+    Average,
+}
+
+/// Drive the VRM blink expressions from the tracked ARKit blinks.
+///
+/// Which of them moves a mesh depends on the morphs it has: see
+/// [`MeshBlinks`].
+#[derive(Debug, Clone, Default, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct EyeBlinks {
+    pub combine: BlinkCombine,
+}
+
+/// One of the VRM blinks which follow the tracked ARKit blinks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DerivedBlink {
+    Left,
+    Right,
+    Both,
+}
+
+/// The VRM blink weights derived from the tracked ARKit blinks.
+///
+/// These are kept apart from [`ExpressionWeights`], so that each mesh only
+/// takes them for the blink morphs [`MeshBlinks`] picks, while the VRM blink
+/// expressions set by anything else, such as an expression pose, still drive
+/// every mesh.
+#[derive(Debug, Clone, Default, Resource)]
+pub struct DerivedBlinks {
+    pub left: f32,
+    pub right: f32,
+    pub both: f32,
+}
+
+impl DerivedBlinks {
+    pub fn get(&self, blink: DerivedBlink) -> f32 {
+        match blink {
+            DerivedBlink::Left => self.left,
+            DerivedBlink::Right => self.right,
+            DerivedBlink::Both => self.both,
+        }
+    }
+}
+
+/// The derived blinks which follow a tracked blend shape.
+pub fn derived_blinks(name: &str) -> &'static [DerivedBlink] {
+    match name {
+        EYE_BLINK_LEFT => &[DerivedBlink::Left, DerivedBlink::Both],
+        EYE_BLINK_RIGHT => &[DerivedBlink::Right, DerivedBlink::Both],
+        _ => &[],
+    }
+}
+
+pub fn apply_eye_blinks(
+    settings: Res<EyeBlinks>,
+    expressions: Res<ExpressionWeights>,
+    mut derived: ResMut<DerivedBlinks>,
+) {
+    let left = expressions.get(EYE_BLINK_LEFT);
+    let right = expressions.get(EYE_BLINK_RIGHT);
+    *derived = DerivedBlinks {
+        left,
+        right,
+        both: match settings.combine {
+            BlinkCombine::Max => left.max(right),
+            BlinkCombine::Average => (left + right) / 2.,
+        },
+    };
+}
+
+/// The blink morphs a mesh has, by canonical name.
+///
+/// Only the most specific blink morphs follow the tracked blinks, so that
+/// eyes don't close twice on meshes with several: ARKit's per-eye blinks
+/// first, then VRM's per-eye blinks, and the combined blink only when there
+/// are neither.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MeshBlinks {
+    arkit: bool,
+    per_eye: bool,
+}
+
+impl MeshBlinks {
+    pub fn new<'a>(names: impl IntoIterator<Item = &'a str>) -> MeshBlinks {
+        let mut blinks = MeshBlinks::default();
+        for name in names {
+            match name {
+                EYE_BLINK_LEFT | EYE_BLINK_RIGHT => blinks.arkit = true,
+                BLINK_LEFT | BLINK_RIGHT => blinks.per_eye = true,
+                _ => {}
+            }
+        }
+        blinks
+    }
+
+    /// The derived blink which the morph with this canonical name follows,
+    /// if any.
+    pub fn derived(&self, name: &str) -> Option<DerivedBlink> {
+        match name {
+            BLINK_LEFT if !self.arkit => Some(DerivedBlink::Left),
+            BLINK_RIGHT if !self.arkit => Some(DerivedBlink::Right),
+            BLINK if !self.arkit && !self.per_eye => Some(DerivedBlink::Both),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn derived(names: &[&str]) -> Vec<Option<DerivedBlink>> {
+        let blinks = MeshBlinks::new(names.iter().copied());
+        names.iter().map(|name| blinks.derived(name)).collect()
+    }
+
+    #[test]
+    fn arkit_blinks_are_tracked_directly() {
+        assert_eq!(
+            derived(&[EYE_BLINK_LEFT, EYE_BLINK_RIGHT, BLINK_LEFT, BLINK_RIGHT, BLINK]),
+            vec![None; 5],
+        );
+    }
+
+    #[test]
+    fn per_eye_blinks_follow_their_eye() {
+        assert_eq!(
+            derived(&[BLINK_LEFT, BLINK_RIGHT, BLINK]),
+            vec![Some(DerivedBlink::Left), Some(DerivedBlink::Right), None],
+        );
+    }
+
+    #[test]
+    fn combined_blink_is_the_fallback() {
+        assert_eq!(derived(&[BLINK, "aa"]), vec![Some(DerivedBlink::Both), None]);
+    }
+}
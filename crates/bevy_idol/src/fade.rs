@@ -0,0 +1,221 @@
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+
+use bevy_vrm::extensions::mtoon::{MToonMaterial, MToonOutlineMaterial};
+use bevy_vrm::Vrm;
+
+use crate::crossfade::AvatarOpacity;
+
+const FADE_KEY: KeyCode = KeyCode::F12;
+
+/// Fade the whole avatar in and out, by scaling the alpha of its materials,
+/// including its MToon outlines.
+#[derive(Debug, Clone, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct AvatarFade {
+    /// Current opacity, from 0 to 1.
+    pub opacity: f32,
+    /// Opacity being faded towards.
+    pub target: f32,
+    /// Time taken to fade fully in or out, in seconds.
+    pub duration: f32,
+}
+
+impl AvatarFade {
+    pub fn new(duration: f32) -> AvatarFade {
+        AvatarFade {
+            opacity: 1.,
+            target: 1.,
+            duration,
+        }
+    }
+
+    pub fn fade_to(&mut self, target: f32, duration: Option<f32>) {
+        self.target = target.clamp(0., 1.);
+        if let Some(duration) = duration {
+            self.duration = duration;
+        }
+    }
+}
+
+/// A material's alpha mode, alpha and depth writes from before it was
+/// faded, and the opacity it was last faded to.
+struct FadedMaterial {
+    alpha_mode: AlphaMode,
+    alpha: f32,
+    z_write: bool,
+    opacity: f32,
+}
+
+/// The original state of every faded material.
+#[derive(Default, Resource)]
+pub struct FadeOriginals {
+    mtoon: HashMap<AssetId<MToonMaterial>, FadedMaterial>,
+    outline: HashMap<AssetId<MToonOutlineMaterial>, FadedMaterial>,
+    standard: HashMap<AssetId<StandardMaterial>, FadedMaterial>,
+}
+
+pub fn toggle_fade_hotkey(keys: Res<ButtonInput<KeyCode>>, mut fade: ResMut<AvatarFade>) {
+    if keys.just_pressed(FADE_KEY) {
+        let target = if fade.target > 0.5 { 0. } else { 1. };
+        fade.fade_to(target, None);
+    }
+}
+
+/// The opacity of the avatar `entity` belongs to, if it belongs to one.
+fn avatar_opacity(
+    entity: Entity,
+    parents: &Query<&Parent>,
+    avatars: &Query<Option<&AvatarOpacity>, With<Handle<Vrm>>>,
+) -> Option<f32> {
+    std::iter::once(entity)
+        .chain(parents.iter_ancestors(entity))
+        .find_map(|e| avatars.get(e).ok())
+        .map(|opacity| opacity.map_or(1., |opacity| opacity.0))
+}
+
+fn blend_mode(mode: AlphaMode) -> AlphaMode {
+    match mode {
+        AlphaMode::Opaque | AlphaMode::Mask(_) => AlphaMode::Blend,
+        mode => mode,
+    }
+}
+
+/// Fade the MToon materials used by `entities`, where `mtoon` finds the
+/// MToon material within each asset, and restore those which are no longer
+/// faded.
+fn fade_mtoon_materials<M: Asset>(
+    opacity_of: impl Fn(Entity) -> Option<f32>,
+    entities: &Query<(Entity, &Handle<M>)>,
+    materials: &mut Assets<M>,
+    originals: &mut HashMap<AssetId<M>, FadedMaterial>,
+    mtoon: impl Fn(&mut M) -> &mut MToonMaterial,
+) {
+    // Only touch the materials when they would change, as every change
+    // rebuilds their bind groups.
+    let mut seen = HashSet::default();
+    for (entity, handle) in entities {
+        let Some(opacity) = opacity_of(entity) else {
+            continue;
+        };
+        let id = handle.id();
+        if opacity >= 1. || !seen.insert(id) {
+            continue;
+        }
+        if originals.get(&id).is_some_and(|faded| faded.opacity == opacity) {
+            continue;
+        }
+        let Some(material) = materials.get_mut(id) else {
+            continue;
+        };
+        let material = mtoon(material);
+        let faded = originals.entry(id).or_insert_with(|| FadedMaterial {
+            alpha_mode: material.alpha_mode,
+            alpha: material.base_color.alpha,
+            z_write: material.transparent_with_z_write,
+            opacity,
+        });
+        faded.opacity = opacity;
+        // Opaque materials keep writing depth while faded, so the avatar
+        // doesn't show through itself.
+        if matches!(material.alpha_mode, AlphaMode::Opaque | AlphaMode::Mask(_)) {
+            material.transparent_with_z_write = true;
+        }
+        material.alpha_mode = blend_mode(material.alpha_mode);
+        material.base_color.alpha = faded.alpha * opacity;
+    }
+
+    // Restore the original alpha modes of materials which are opaque again
+    // (or no longer in use), whose pipelines are still cached, so they are
+    // opaque again without a gap.
+    originals.retain(|id, faded| {
+        if seen.contains(id) {
+            return true;
+        }
+        if let Some(material) = materials.get_mut(*id) {
+            let material = mtoon(material);
+            material.alpha_mode = faded.alpha_mode;
+            material.base_color.alpha = faded.alpha;
+            material.transparent_with_z_write = faded.z_write;
+        }
+        false
+    });
+}
+
+pub fn update_fade(
+    time: Res<Time>,
+    mut fade: ResMut<AvatarFade>,
+    mut originals: ResMut<FadeOriginals>,
+    parents: Query<&Parent>,
+    avatars: Query<Option<&AvatarOpacity>, With<Handle<Vrm>>>,
+    mtoon_entities: Query<(Entity, &Handle<MToonMaterial>)>,
+    outline_entities: Query<(Entity, &Handle<MToonOutlineMaterial>)>,
+    standard_entities: Query<(Entity, &Handle<StandardMaterial>)>,
+    mut mtoon_materials: ResMut<Assets<MToonMaterial>>,
+    mut outline_materials: ResMut<Assets<MToonOutlineMaterial>>,
+    mut standard_materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if fade.opacity != fade.target {
+        let step = if fade.duration > 0. {
+            time.delta_seconds() / fade.duration
+        } else {
+            1.
+        };
+        let delta = (fade.target - fade.opacity).clamp(-step, step);
+        fade.opacity += delta;
+    }
+
+    let faded = fade.opacity < 1. || avatars.iter().flatten().any(|opacity| opacity.0 < 1.);
+    if !faded && originals.mtoon.is_empty() && originals.outline.is_empty() && originals.standard.is_empty() {
+        return;
+    }
+    let originals = &mut *originals;
+
+    let opacity_of = |entity: Entity| avatar_opacity(entity, &parents, &avatars)
+        .map(|opacity| opacity * fade.opacity);
+    fade_mtoon_materials(opacity_of, &mtoon_entities, &mut mtoon_materials, &mut originals.mtoon, |material| material);
+    fade_mtoon_materials(
+        opacity_of,
+        &outline_entities,
+        &mut outline_materials,
+        &mut originals.outline,
+        |material| &mut material.base,
+    );
+
+    let mut seen = HashSet::default();
+    for (entity, handle) in &standard_entities {
+        let Some(opacity) = opacity_of(entity) else {
+            continue;
+        };
+        let id = handle.id();
+        if opacity >= 1. || !seen.insert(id) {
+            continue;
+        }
+        if originals.standard.get(&id).is_some_and(|faded| faded.opacity == opacity) {
+            continue;
+        }
+        let Some(material) = standard_materials.get_mut(id) else {
+            continue;
+        };
+        let faded = originals.standard.entry(id).or_insert_with(|| FadedMaterial {
+            alpha_mode: material.alpha_mode,
+            alpha: material.base_color.alpha(),
+            z_write: false,
+            opacity,
+        });
+        faded.opacity = opacity;
+        material.alpha_mode = blend_mode(material.alpha_mode);
+        material.base_color.set_alpha(faded.alpha * opacity);
+    }
+
+    originals.standard.retain(|id, faded| {
+        if seen.contains(id) {
+            return true;
+        }
+        if let Some(material) = standard_materials.get_mut(*id) {
+            material.alpha_mode = faded.alpha_mode;
+            material.base_color.set_alpha(faded.alpha);
+        }
+        false
+    });
+}
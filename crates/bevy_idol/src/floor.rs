@@ -0,0 +1,136 @@
+use bevy::color::palettes::css::GRAY;
+use bevy::pbr::NotShadowCaster;
+use bevy::prelude::*;
+use bevy::render::mesh::PrimitiveTopology;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{AsBindGroup, ShaderRef};
+use bevy::render::view::RenderLayers;
+use clap::ValueEnum;
+
+use crate::ToggleVisibilityKey;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum FloorStyle {
+    /// An opaque floor which receives shadows.
+    Solid,
+    /// A transparent floor which only draws the shadows falling on it.
+    ShadowCatcher,
+}
+
+/// A material which is transparent except where shadows fall on it.
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+pub struct ShadowCatcherMaterial {
+    /// The colour of the shadow, where alpha is the opacity of a full shadow.
+    #[uniform(0)]
+    pub color: LinearRgba,
+}
+
+impl Material for ShadowCatcherMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/shadow_catcher.wgsl".into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        AlphaMode::Blend
+    }
+}
+
+#[derive(Component)]
+pub struct Floor;
+
+#[derive(Component)]
+pub struct Grid;
+
+/// Build a square grid of lines on the XZ plane.
+fn grid_mesh(half_size: f32, spacing: f32) -> Mesh {
+    let count = (half_size / spacing).floor() as i32;
+    let mut positions = Vec::new();
+    for i in -count..=count {
+        let offset = i as f32 * spacing;
+        positions.push([offset, 0., -half_size]);
+        positions.push([offset, 0., half_size]);
+        positions.push([-half_size, 0., offset]);
+        positions.push([half_size, 0., offset]);
+    }
+
+    Mesh::new(PrimitiveTopology::LineList, RenderAssetUsages::RENDER_WORLD)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+}
+
+pub fn spawn_floor(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    shadow_catchers: &mut Assets<ShadowCatcherMaterial>,
+    style: FloorStyle,
+    height: f32,
+    in_output: bool,
+) {
+    let mesh = meshes.add(Mesh::from(Plane3d::new(Vec3::Y, Vec2::splat(5.))));
+    let transform = Transform::from_xyz(0., height, 0.);
+    let render_layers = if in_output {
+        RenderLayers::layer(0)
+    } else {
+        RenderLayers::layer(1)
+    };
+
+    let mut floor = commands.spawn((
+        Name::from("Floor"),
+        Floor,
+        NotShadowCaster,
+        render_layers,
+        ToggleVisibilityKey(KeyCode::F6),
+    ));
+    match style {
+        FloorStyle::Solid => {
+            floor.insert(PbrBundle {
+                mesh,
+                material: materials.add(StandardMaterial {
+                    base_color: GRAY.into(),
+                    perceptual_roughness: 1.,
+                    ..default()
+                }),
+                transform,
+                ..default()
+            });
+        }
+        FloorStyle::ShadowCatcher => {
+            floor.insert(MaterialMeshBundle {
+                mesh,
+                material: shadow_catchers.add(ShadowCatcherMaterial {
+                    color: LinearRgba::new(0., 0., 0., 0.5),
+                }),
+                transform,
+                ..default()
+            });
+        }
+    }
+}
+
+pub fn spawn_grid(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    height: f32,
+    visible: bool,
+) {
+    commands.spawn((
+        Name::from("Grid"),
+        PbrBundle {
+            mesh: meshes.add(grid_mesh(5., 0.25)),
+            material: materials.add(StandardMaterial {
+                base_color: Color::WHITE.with_alpha(0.25),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..default()
+            }),
+            // Lift the grid slightly so it doesn't z-fight with the floor.
+            transform: Transform::from_xyz(0., height + 0.001, 0.),
+            visibility: if visible { Visibility::Inherited } else { Visibility::Hidden },
+            ..default()
+        },
+        Grid,
+        RenderLayers::layer(1),
+        ToggleVisibilityKey(KeyCode::F4),
+    ));
+}
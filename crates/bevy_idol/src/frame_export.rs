@@ -0,0 +1,109 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy::render::camera::RenderTarget;
+use bevy::render::view::screenshot::ScreenshotManager;
+use bevy::time::TimeUpdateStrategy;
+use bevy::window::WindowRef;
+
+use crate::cameras::OutputCamera;
+use crate::supersampling::OutputDownsample;
+
+/// Frames which may be waiting to be written before the export holds back.
+const MAX_PENDING_FRAMES: usize = 8;
+
+/// Write each output frame to a numbered PNG, with alpha, for offline
+/// compositing.
+///
+/// Time advances by exactly one frame per captured frame, so the sequence
+/// plays back at `fps` however long each frame takes to render and write.
+/// When too many frames are waiting to be written, time stands still and
+/// nothing is captured until they catch up.
+#[derive(Resource)]
+pub struct FrameExport {
+    pub dir: PathBuf,
+    pub fps: u32,
+    /// Stop after this many frames. Escape stops early.
+    pub frame_count: Option<u32>,
+    next_frame: u32,
+    stopping: bool,
+    pending: Arc<AtomicUsize>,
+}
+
+impl FrameExport {
+    pub fn new(dir: PathBuf, fps: u32, frame_count: Option<u32>) -> FrameExport {
+        FrameExport {
+            dir,
+            fps,
+            frame_count,
+            next_frame: 0,
+            stopping: false,
+            pending: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+}
+
+pub fn export_frames(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut export: ResMut<FrameExport>,
+    mut screenshots: ResMut<ScreenshotManager>,
+    mut time_strategy: ResMut<TimeUpdateStrategy>,
+    cameras: Query<&Camera, Or<(With<OutputCamera>, With<OutputDownsample>)>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let frame_time = Duration::from_secs_f64(1. / export.fps.max(1) as f64);
+    *time_strategy = TimeUpdateStrategy::ManualDuration(Duration::ZERO);
+
+    if keys.just_pressed(KeyCode::Escape)
+        || export.frame_count.map_or(false, |count| export.next_frame >= count) {
+        export.stopping = true;
+    }
+    if export.stopping {
+        if export.pending.load(Ordering::Acquire) == 0 {
+            info!("exported {} frames to {}", export.next_frame, export.dir.display());
+            exit.send(AppExit::Success);
+        }
+        return;
+    }
+
+    if export.pending.load(Ordering::Acquire) >= MAX_PENDING_FRAMES {
+        return;
+    }
+
+    let Some(window) = cameras.iter().find_map(|camera| match camera.target {
+        RenderTarget::Window(WindowRef::Entity(window)) => Some(window),
+        _ => None,
+    }) else {
+        return;
+    };
+
+    let path = export.dir.join(format!("{:06}.png", export.next_frame));
+    let pending = export.pending.clone();
+    pending.fetch_add(1, Ordering::AcqRel);
+    let requested = screenshots.take_screenshot(window, move |image| {
+        match image.try_into_dynamic() {
+            Ok(image) => {
+                if let Err(err) = image.to_rgba8().save(&path) {
+                    error!("failed to write {}: {}", path.display(), err);
+                }
+            }
+            Err(err) => error!("failed to convert {}: {}", path.display(), err),
+        }
+        pending.fetch_sub(1, Ordering::AcqRel);
+    });
+
+    match requested {
+        Ok(()) => {
+            export.next_frame += 1;
+            *time_strategy = TimeUpdateStrategy::ManualDuration(frame_time);
+        }
+        Err(err) => {
+            export.pending.fetch_sub(1, Ordering::AcqRel);
+            warn!("failed to capture frame {}: {}", export.next_frame, err);
+        }
+    }
+}
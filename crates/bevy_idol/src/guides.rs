@@ -0,0 +1,143 @@
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+use crate::ToggleVisibilityKey;
+
+const GUIDES_KEY: KeyCode = KeyCode::F2;
+
+/// The box in the preview window with the output's aspect ratio, which the
+/// framing guides are drawn within.
+#[derive(Debug, Clone, Copy, Component)]
+pub struct OutputGuides {
+    pub aspect_ratio: f32,
+}
+
+fn guide(parent: &mut ChildBuilder, style: Style, color: Color, outline: bool) {
+    let mut node = NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            ..style
+        },
+        ..default()
+    };
+    if outline {
+        node.style.border = UiRect::all(Val::Px(1.));
+        node.border_color = color.into();
+    } else {
+        node.background_color = color.into();
+    }
+    parent.spawn(node);
+}
+
+fn line(parent: &mut ChildBuilder, vertical: bool, at: f32, from: f32, length: f32, color: Color) {
+    let style = if vertical {
+        Style {
+            left: Val::Percent(at),
+            top: Val::Percent(from),
+            width: Val::Px(1.),
+            height: Val::Percent(length),
+            ..default()
+        }
+    } else {
+        Style {
+            top: Val::Percent(at),
+            left: Val::Percent(from),
+            height: Val::Px(1.),
+            width: Val::Percent(length),
+            ..default()
+        }
+    };
+    guide(parent, style, color, false);
+}
+
+fn inset(parent: &mut ChildBuilder, percent: f32, color: Color) {
+    let style = Style {
+        left: Val::Percent(percent),
+        right: Val::Percent(percent),
+        top: Val::Percent(percent),
+        bottom: Val::Percent(percent),
+        ..default()
+    };
+    guide(parent, style, color, true);
+}
+
+/// Spawn framing guides for the output (rule of thirds, centre and safe
+/// areas) over the preview window, hidden until toggled with F2.
+///
+/// They are UI on the preview window, so they never appear in the output.
+pub fn spawn_output_guides(commands: &mut Commands, width: u32, height: u32) {
+    let border = Color::srgba(1., 1., 1., 0.8);
+    let thirds = Color::srgba(1., 1., 1., 0.4);
+    let safe = Color::srgba(1., 0.8, 0., 0.6);
+
+    commands
+        .spawn((
+            Name::from("Output Guides"),
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            ToggleVisibilityKey(GUIDES_KEY),
+        ))
+        .with_children(|root| {
+            root
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            border: UiRect::all(Val::Px(1.)),
+                            ..default()
+                        },
+                        border_color: border.into(),
+                        ..default()
+                    },
+                    OutputGuides {
+                        aspect_ratio: width as f32 / height.max(1) as f32,
+                    },
+                ))
+                .with_children(|frame| {
+                    for at in [100. / 3., 200. / 3.] {
+                        line(frame, true, at, 0., 100., thirds);
+                        line(frame, false, at, 0., 100., thirds);
+                    }
+                    line(frame, true, 50., 47., 6., border);
+                    line(frame, false, 50., 47., 6., border);
+                    // Action safe (90%) and title safe (80%).
+                    inset(frame, 5., safe);
+                    inset(frame, 10., safe);
+                });
+        });
+}
+
+/// Fit the guides' frame to the preview window, keeping the output's aspect
+/// ratio.
+pub fn update_output_guides(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut guides: Query<(&OutputGuides, &mut Style)>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+
+    let width = window.width();
+    let height = window.height();
+    for (guides, mut style) in &mut guides {
+        let (w, h) = if width / height.max(1.) > guides.aspect_ratio {
+            (height * guides.aspect_ratio, height)
+        } else {
+            (width, width / guides.aspect_ratio)
+        };
+        let (w, h) = (Val::Px(w), Val::Px(h));
+        if style.width != w || style.height != h {
+            style.width = w;
+            style.height = h;
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use std::path::Path;
+
+use bevy::hierarchy::HierarchyQueryExt;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
+
+use bevy_vrm::extensions::vrm::{Humanoid, HumanoidBone};
+
+/// Limits on a bone's rotation away from its rest pose.
+///
+/// Angles are in degrees, as Euler angles applied yaw (Y), then pitch (X),
+/// then roll (Z), in the bone's rest space.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Reflect)]
+#[serde(rename_all = "camelCase")]
+pub struct RotationLimit {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl RotationLimit {
+    pub const fn new(min: Vec3, max: Vec3) -> RotationLimit {
+        RotationLimit { min, max }
+    }
+
+    pub const fn symmetric(range: Vec3) -> RotationLimit {
+        RotationLimit {
+            min: Vec3::new(-range.x, -range.y, -range.z),
+            max: range,
+        }
+    }
+
+    /// Clamp a rotation relative to the rest pose.
+    pub fn clamp(&self, rotation: Quat) -> Quat {
+        let (y, x, z) = rotation.to_euler(EulerRot::YXZ);
+        let angles = Vec3::new(x, y, z).clamp(
+            self.min * std::f32::consts::PI / 180.,
+            self.max * std::f32::consts::PI / 180.,
+        );
+        Quat::from_euler(EulerRot::YXZ, angles.y, angles.x, angles.z)
+    }
+}
+
+/// Per-bone rotation limits for an avatar, applied after everything else has
+/// posed the bones.
+#[derive(Debug, Clone, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component)]
+pub struct BoneLimits {
+    pub limits: HashMap<HumanoidBone, RotationLimit>,
+}
+
+impl Default for BoneLimits {
+    fn default() -> Self {
+        use HumanoidBone::*;
+
+        let spine = RotationLimit::symmetric(Vec3::new(30., 40., 30.));
+        let hand = RotationLimit::symmetric(Vec3::new(80., 80., 60.));
+        BoneLimits {
+            limits: [
+                (Spine, spine),
+                (Chest, spine),
+                (UpperChest, spine),
+                (Neck, RotationLimit::symmetric(Vec3::new(30., 50., 30.))),
+                (Head, RotationLimit::symmetric(Vec3::new(50., 80., 40.))),
+                // Elbows bend forwards, which is a rotation about Y in
+                // opposite directions for each arm.
+                (LeftLowerArm, RotationLimit::new(Vec3::new(-90., -160., -10.), Vec3::new(90., 5., 10.))),
+                (RightLowerArm, RotationLimit::new(Vec3::new(-90., -5., -10.), Vec3::new(90., 160., 10.))),
+                (LeftHand, hand),
+                (RightHand, hand),
+                // Knees only bend backwards.
+                (LeftLowerLeg, RotationLimit::new(Vec3::new(-5., -10., -5.), Vec3::new(160., 10., 5.))),
+                (RightLowerLeg, RotationLimit::new(Vec3::new(-5., -10., -5.), Vec3::new(160., 10., 5.))),
+            ].into_iter().collect(),
+        }
+    }
+}
+
+impl BoneLimits {
+    /// Load limits from a JSON file of the form
+    /// `{"limits": {"head": {"min": [x, y, z], "max": [x, y, z]}}}`.
+    pub fn from_file(path: &Path) -> anyhow::Result<BoneLimits> {
+        let contents = std::fs::read(path)?;
+        Ok(serde_json::from_slice(&contents)?)
+    }
+}
+
+/// The local rotation of a humanoid bone in the avatar's rest pose.
+#[derive(Debug, Clone, Copy, Component, Reflect)]
+#[reflect(Component)]
+pub struct RestRotation(pub Quat);
+
+pub fn add_rest_rotations(
+    mut commands: Commands,
+    humanoids: Query<&Humanoid, Added<Humanoid>>,
+    transforms: Query<&Transform>,
+) {
+    for humanoid in &humanoids {
+        for entity in humanoid.bones.values() {
+            if let Ok(transform) = transforms.get(*entity) {
+                commands.entity(*entity).insert(RestRotation(transform.rotation));
+            }
+        }
+    }
+}
+
+/// Clamp each humanoid's bones to the limits on its avatar.
+pub fn apply_bone_limits(
+    humanoids: Query<(Entity, &Humanoid)>,
+    limits: Query<&BoneLimits>,
+    parents: Query<&Parent>,
+    mut bones: Query<(&mut Transform, &RestRotation)>,
+) {
+    for (entity, humanoid) in &humanoids {
+        let Some(limits) = std::iter::once(entity)
+            .chain(parents.iter_ancestors(entity))
+            .find_map(|e| limits.get(e).ok()) else {
+            continue;
+        };
+
+        for (bone, limit) in &limits.limits {
+            let Some((mut transform, rest)) = humanoid.bones.get(bone)
+                .and_then(|e| bones.get_mut(*e).ok()) else {
+                continue;
+            };
+
+            let relative = rest.0.inverse() * transform.rotation;
+            let clamped = limit.clamp(relative);
+            if !clamped.abs_diff_eq(relative, 1e-5) {
+                transform.rotation = rest.0 * clamped;
+            }
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use bevy::hierarchy::HierarchyQueryExt;
+use bevy::prelude::*;
+
+use bevy_vrm::extensions::vrm::LookAtTargetRoot;
+use idol_api::SetLookAtRequest;
+
+/// Where the avatar's eyes should be aimed.
+#[derive(Resource)]
+pub struct LookAt {
+    pub target: SetLookAtRequest,
+}
+
+impl Default for LookAt {
+    fn default() -> Self {
+        LookAt {
+            target: SetLookAtRequest::Default,
+        }
+    }
+}
+
+/// A world-space anchor for looking at a fixed point.
+#[derive(Component)]
+pub struct LookAtPoint;
+
+fn is_descendant_of(entity: Entity, ancestor: Entity, parents: &Query<&Parent>) -> bool {
+    std::iter::once(entity)
+        .chain(parents.iter_ancestors(entity))
+        .any(|e| e == ancestor)
+}
+
+/// Reparent each avatar's look target to match `LookAt`.
+pub fn update_look_at_target(
+    mut commands: Commands,
+    mut look_at: ResMut<LookAt>,
+    mut roots: Query<(Entity, Ref<LookAtTargetRoot>, &mut Transform), Without<LookAtPoint>>,
+    mut points: Query<(Entity, &mut Transform), With<LookAtPoint>>,
+    names: Query<(Entity, &Name)>,
+    parents: Query<&Parent>,
+) {
+    let changed = look_at.is_changed();
+    for (root, target_root, mut transform) in &mut roots {
+        // The default transform can also be edited in the inspector.
+        if !changed && !target_root.is_changed() {
+            continue;
+        }
+
+        let (parent, new_transform) = match &look_at.target {
+            SetLookAtRequest::Default => (None, target_root.default_transform),
+            SetLookAtRequest::Point { position } => {
+                let point = match points.get_single_mut() {
+                    Ok((point, mut point_transform)) => {
+                        point_transform.translation = *position;
+                        point
+                    }
+                    Err(_) => commands
+                        .spawn((
+                            Name::from("Look At Point"),
+                            SpatialBundle::from_transform(Transform::from_translation(*position)),
+                            LookAtPoint,
+                        ))
+                        .id(),
+                };
+                (Some(point), Transform::IDENTITY)
+            }
+            SetLookAtRequest::Entity { name } => {
+                let entity = names.iter()
+                    .find(|(_, n)| n.as_str() == name)
+                    .map(|(e, _)| e);
+                match entity {
+                    Some(entity) if !is_descendant_of(entity, root, &parents) =>
+                        (Some(entity), Transform::IDENTITY),
+                    Some(_) => {
+                        warn!("cannot look at {name}, it is part of the look target");
+                        (None, target_root.default_transform)
+                    }
+                    None => {
+                        warn!("no entity named {name} to look at");
+                        (None, target_root.default_transform)
+                    }
+                }
+            }
+        };
+
+        let parent = parent.unwrap_or(target_root.default_parent);
+        commands.entity(root).set_parent(parent);
+        *transform = new_transform;
+    }
+
+    // Don't keep retrying an invalid target every time an avatar spawns.
+    if changed {
+        let valid = match &look_at.target {
+            SetLookAtRequest::Entity { name } => names.iter().any(|(_, n)| n.as_str() == name),
+            _ => true,
+        };
+        if !valid {
+            look_at.bypass_change_detection().target = SetLookAtRequest::Default;
+        }
+    }
+}
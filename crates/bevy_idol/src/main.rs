@@ -1,52 +1,525 @@
 use std::fmt::Write;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
+use bevy::asset::io::AssetSourceBuilder;
 use bevy::color::palettes::css::{BEIGE, BLUE, MAROON, RED};
-use bevy::core_pipeline::tonemapping::Tonemapping;
+use bevy::core_pipeline::dof::{DepthOfFieldMode, DepthOfFieldSettings};
+use bevy::core_pipeline::experimental::taa::TemporalAntiAliasPlugin;
 use bevy::input::mouse::MouseMotion;
 use bevy::prelude::*;
 use bevy::render::camera::{CameraOutputMode, RenderTarget};
 use bevy::render::mesh::morph::MeshMorphWeights;
 use bevy::render::mesh::VertexAttributeValues;
-use bevy::render::render_resource::Face;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, Face, TextureDimension, TextureFormat};
+use bevy::render::renderer::RenderDevice;
 use bevy::render::view::RenderLayers;
-use bevy::window::{WindowRef, WindowResolution};
+use bevy::sprite::Material2dPlugin;
+use bevy::transform::TransformSystem;
+use bevy::utils::{HashMap, HashSet};
+use bevy::window::{WindowLevel, WindowPosition, WindowRef, WindowResolution};
 use bevy_inspector_egui::inspector_egui_impls::InspectorEguiImpl;
 use clap::Parser;
 
+use bevy_vrm::extensions::mtoon::MaterialCullMode;
 use bevy_vrm::extensions::vrm::LookAtTarget;
-use bevy_vrm::VrmBundle;
+use bevy_vrm::morph_aliases::MorphAliases;
+use bevy_vrm::{MissingLookAt, MissingNormals, RootCorrection, Vrm, VrmBundle, VrmLoaderSettings};
+use idol_api::{SetSmoothingRequest, SmoothingGroup, SmoothingPreset};
 
+use crate::accessories::{Accessories, attach_accessories};
 use crate::add_blend_shapes::{AddBlendShapes, apply_blend_shapes, BlendShapeLibrary};
-use crate::cameras::{OutputCamera, PreviewCamera};
-use crate::tracking::Faces;
-use crate::webcam::WebcamTexture;
+use crate::avatar_config::{apply_avatar_smoothing, AvatarConfig, AvatarConfigs};
+use crate::avatars::{AvatarSet, report_preload_progress, switch_avatar_hotkeys};
+use crate::background::{BackgroundKind, parse_color, prepare_skybox_images, PreviewBackground, update_preview_background};
+use crate::blend_shape_matches::{BlendShapeMatches, record_tracked_blend_shapes, report_unmatched_blend_shapes};
+use crate::blink_links::{apply_blink_links, BlinkLinks};
+use crate::body::{ArmTracking, FootGrounding, update_arm_ik, update_foot_ik};
+use crate::brows::{BrowLandmarks, combine_brow_expressions, derive_brow_blend_shapes};
+use crate::calibration::{Calibration, CalibrationProfiles, DEFAULT_PROFILE, load_active_profile};
+use crate::cameras::{DollyZoom, fall_back_from_taa, msaa_from_samples, CameraLens, output_color_grading, OutputCamera, PostAntiAliasing, PreviewCamera, ProjectionKind, TaaFallback, TonemappingKind, update_depth_of_field, update_dolly_zoom};
+use crate::expression_poses::{apply_expression_poses, apply_idle_pose, ExpressionPoses, IdlePose, toggle_expression_poses};
+use crate::expressions::{collect_tracked_expressions, ExpressionSystems, ExpressionWeights, MorphTargetIndices, TrackingSides};
+use crate::eye_blinks::{apply_eye_blinks, BlinkCombine, DerivedBlinks, EyeBlinks};
+use crate::crossfade::{AvatarCrossfade, AvatarOpacity, start_crossfades, update_crossfades};
+use crate::fade::{AvatarFade, FadeOriginals, toggle_fade_hotkey, update_fade};
+use crate::floor::{FloorStyle, ShadowCatcherMaterial, spawn_floor, spawn_grid};
+use crate::frame_export::{export_frames, FrameExport};
+use crate::guides::{spawn_output_guides, update_output_guides};
+use crate::limits::{add_rest_rotations, apply_bone_limits, BoneLimits, RestRotation};
+use crate::look_at::{LookAt, update_look_at_target};
+use crate::morph_audit::{apply_morph_audit, morph_audit_hotkeys, MorphAudit};
+use crate::output_alpha::{OutputAlpha, OutputAlphaPlugin};
+use crate::output_background::{fit_output_background, OutputBackground, spawn_output_background};
+use crate::parts::{hide_new_parts, HiddenParts};
+use crate::phonemes::{apply_phoneme_timeline, PhonemePlayback, PhonemeTimeline, PhonemeTimelineLoader};
+use crate::procedural::{add_procedural_poses, apply_breath_morph, apply_breathing, apply_idle_sway, apply_procedural_poses, Breathing, BreathingState, IdleSway, IdleSwayState, ProceduralPose, ProceduralSeed, restore_procedural_poses, SaccadeState, Saccades, update_breathing, update_idle_sway, update_saccades};
+use crate::response_curves::{apply_response_curves, ResponseCurves};
+use crate::shaders::{log_shader_reloads, reload_shaders_hotkey};
+use crate::smoothing::{smooth_face_transforms, Smoothing, SmoothingParams, smooth_tracked_expressions};
+use crate::smoothing_plot::{draw_smoothing_plot, sample_applied, sample_incoming, SmoothingPlot, toggle_smoothing_plot};
+use crate::supersampling::{DownsampleMaterial, fit_output_size, spawn_supersampled_output, SupersampledOutput, supersampling_scale};
+use crate::tongue::{add_tongue_rest, apply_tongue_bone, apply_tongue_morph, parse_vec3, Tongue};
+use crate::tracking::{AxisConvention, Faces, parse_axes, Poses, reset_translation_lock, TrackedTranslation, TrackingStatus, update_tracking_status};
+use crate::tracking_indicator::{IndicatorPlacement, spawn_tracking_indicator, update_tracking_indicators};
+use crate::tracking_noise::TrackingNoise;
+use crate::webcam::{CameraPlaneSettings, WebcamTexture};
 
 mod api;
 mod tracking;
+mod tracking_indicator;
+mod tracking_noise;
 mod webcam;
+#[cfg(all(feature = "webcam-capture", target_os = "linux"))]
+mod webcam_capture;
 mod cameras;
 mod debug_mesh;
+mod error;
+mod accessories;
 mod add_blend_shapes;
+mod avatar_config;
+mod avatars;
+mod background;
+mod blend_shape_matches;
+mod blink_links;
+mod body;
+mod brows;
+mod calibration;
+mod crossfade;
+mod expression_poses;
+mod expressions;
+mod eye_blinks;
+mod fade;
+mod floor;
+mod frame_export;
+mod guides;
+mod limits;
+mod look_at;
+mod morph_audit;
+mod output_alpha;
+mod output_background;
+mod parts;
+mod phonemes;
+mod procedural;
+mod remote_avatar;
+mod response_curves;
+mod rig;
+mod shaders;
+mod smoothing;
+mod smoothing_plot;
+mod supersampling;
+mod tongue;
 
 #[derive(Parser, Resource)]
 struct Options {
     #[arg(long, default_value = "127.0.0.1:8888")]
     pub api_bind: String,
+    #[cfg(all(feature = "virtual-camera", target_os = "linux"))]
     #[arg(long, short = 'c')]
     pub virtual_camera_index: Option<usize>,
+    /// Capture the camera plane from a local webcam, given its index or
+    /// device path, instead of frames sent to `PUT /v1/camera`.
+    #[cfg(all(feature = "webcam-capture", target_os = "linux"))]
+    #[arg(long)]
+    pub webcam_device: Option<String>,
     #[arg(long, short = 'f')]
     pub output_fps: Option<u32>,
+    /// Write each output frame to a numbered PNG in this directory, with
+    /// time stepped at `--output-fps` (30 by default). Escape stops.
+    #[arg(long)]
+    pub export_frames: Option<PathBuf>,
+    /// Stop exporting frames after this many.
+    #[arg(long)]
+    pub export_frame_count: Option<u32>,
     #[arg(long, short = 'W', default_value = "1920")]
     pub output_width: u32,
     #[arg(long, short = 'H', default_value = "1080")]
     pub output_height: u32,
+    /// Render the output at this multiple of its resolution and downsample
+    /// it, for smoother edges. Capped by the GPU's largest texture size.
+    #[arg(long, default_value = "1")]
+    pub output_supersampling: u32,
+    /// Keep the output window above other windows.
+    #[arg(long)]
+    pub output_always_on_top: bool,
+    /// Draw the output window without a title bar or border.
+    #[arg(long)]
+    pub output_borderless: bool,
+    /// Position of the output window's top-left corner, in physical pixels,
+    /// as `x,y`.
+    #[arg(long, value_parser = parse_window_position)]
+    pub output_position: Option<IVec2>,
+    /// HANATool blend shape files, or directories of them. Repeat to layer
+    /// several; later files replace shapes of the same name.
+    #[arg(long)]
+    pub extra_blend_shapes: Vec<PathBuf>,
+    /// Blend the webcam passthrough with its alpha channel, for frames which
+    /// have already had their background removed.
     #[arg(long)]
-    pub extra_blend_shapes: Option<PathBuf>,
+    pub webcam_alpha: bool,
+    /// Distance of the webcam's camera plane behind the origin, in metres.
+    #[arg(long, default_value = "5")]
+    pub webcam_plane_distance: f32,
+    /// Length of the camera plane's shorter side, in metres.
+    #[arg(long, default_value = "10")]
+    pub webcam_plane_size: f32,
     #[arg(long, default_value = "150")]
     pub hot_reload_delay: u64,
+    /// Path to the avatar within the assets directory, or an HTTP(S) URL.
     #[arg(long, default_value = "avatars/demo.vrm")]
     pub avatar: String,
+    /// Additional avatars to load at startup, which can be switched to with
+    /// PageUp/PageDown or the API.
+    #[arg(long = "preload-avatar")]
+    pub preload_avatars: Vec<String>,
+    /// Names of avatar nodes (such as glasses) to hide at startup. They can
+    /// be toggled with `PUT /v1/parts/<name>`.
+    #[arg(long = "hide-part")]
+    pub hide_parts: Vec<String>,
+    /// Generate smooth rather than flat normals for meshes which have none.
+    #[arg(long)]
+    pub smooth_normals: bool,
+    /// Rotate avatars which aren't Y-up and facing the camera: `none`, `auto`
+    /// (from the humanoid's rest pose), or YXZ Euler angles in degrees as
+    /// `x,y,z`.
+    #[arg(long, value_parser = parse_root_correction, default_value = "none")]
+    pub root_correction: RootCorrection,
+    /// How far in front of the head the avatar focuses by default, in
+    /// metres. Nearer targets make the eyes converge more.
+    #[arg(long, default_value = "10")]
+    pub look_distance: f32,
+    /// Leave the eyes still on avatars without look-at metadata, rather than
+    /// looking with the eye bones using the VRM defaults.
+    #[arg(long)]
+    pub ignore_missing_look_at: bool,
+    /// Override the alpha cutoff of a masked material, as `<material>=<cutoff>`
+    /// where the material is its name or index. Repeatable.
+    #[arg(long = "alpha-cutoff", value_parser = parse_alpha_cutoff)]
+    pub alpha_cutoffs: Vec<(String, f32)>,
+    /// Override which faces a material culls, as `<material>=<back|front|none>`
+    /// where the material is its name or index. Repeatable.
+    #[arg(long = "cull-mode", value_parser = parse_cull_mode)]
+    pub cull_modes: Vec<(String, MaterialCullMode)>,
+    /// JSON file mapping morph target names to the expression names which
+    /// drive them, replacing the built-in VRoid aliases.
+    #[arg(long)]
+    pub morph_aliases: Option<PathBuf>,
+    #[arg(long, default_value = "avatar_cache")]
+    pub avatar_cache_dir: PathBuf,
+    #[arg(long)]
+    pub refresh_avatar: bool,
+    /// Add a floor which receives shadows. Toggle it with F6.
+    #[arg(long, value_enum)]
+    pub floor: Option<FloorStyle>,
+    #[arg(long, default_value = "0")]
+    pub floor_height: f32,
+    /// Also draw the floor in the output window.
+    #[arg(long)]
+    pub floor_in_output: bool,
+    /// Show the reference grid in the preview at startup. Toggle it with F4.
+    #[arg(long)]
+    pub grid: bool,
+    /// Add small random eye movements when gaze isn't being tracked.
+    #[arg(long)]
+    pub saccades: bool,
+    /// Maximum saccade angle, in degrees.
+    #[arg(long, default_value = "1.5")]
+    pub saccade_amplitude: f32,
+    /// Average number of saccades per second.
+    #[arg(long, default_value = "0.5")]
+    pub saccade_frequency: f32,
+    /// Add procedural breathing, which fades out when the spine is tracked.
+    #[arg(long)]
+    pub breathing: bool,
+    /// Breaths per minute.
+    #[arg(long, default_value = "14")]
+    pub breathing_rate: f32,
+    /// Spine rotation at the peak of a breath, in degrees.
+    #[arg(long, default_value = "1")]
+    pub breathing_depth: f32,
+    /// Morph target to drive with the breath.
+    #[arg(long, default_value = "")]
+    pub breathing_morph: String,
+    /// Morph target to drive from the tracked tongueOut, for avatars whose
+    /// tongue morph has another name.
+    #[arg(long, default_value = "")]
+    pub tongue_morph: String,
+    /// Bone to move forward with the tracked tongueOut.
+    #[arg(long, default_value = "")]
+    pub tongue_bone: String,
+    /// Offset of the tongue bone when the tongue is fully out, as x,y,z in
+    /// its parent's space.
+    #[arg(long, value_parser = parse_vec3, default_value = "0,0,0.02")]
+    pub tongue_bone_offset: Vec3,
+    /// Add a slow idle sway of the hips and spine, which fades out when they
+    /// are tracked.
+    #[arg(long)]
+    pub idle_sway: bool,
+    /// Maximum hip rotation of the idle sway, in degrees.
+    #[arg(long, default_value = "2")]
+    pub idle_sway_amplitude: f32,
+    #[arg(long, default_value = "0.1")]
+    pub idle_sway_speed: f32,
+    /// Seed for saccades, idle sway and any other procedural randomness, to
+    /// make it reproducible. By default a new seed is picked each run.
+    #[arg(long)]
+    pub procedural_seed: Option<u32>,
+    /// Drive the avatar's arms from tracked poses.
+    #[arg(long)]
+    pub arm_tracking: bool,
+    /// Keep the avatar's feet on the floor with IK.
+    #[arg(long)]
+    pub foot_ik: bool,
+    /// Extra pitch of the feet when grounded, in degrees.
+    #[arg(long, default_value = "0")]
+    pub foot_roll: f32,
+    /// JSON file of accessories to attach to the avatar's bones at startup.
+    #[arg(long)]
+    pub accessories: Option<PathBuf>,
+    /// JSON file of per-bone rotation limits, replacing the built-in ones.
+    #[arg(long)]
+    pub bone_limits: Option<PathBuf>,
+    /// JSON file of expression poses, replacing the built-in ones.
+    #[arg(long)]
+    pub expression_poses: Option<PathBuf>,
+    /// Swap the tracked left and right blend shapes, for trackers which name
+    /// them from the camera's perspective. Hold F10 to force a tracked left
+    /// blink and check.
+    #[arg(long)]
+    pub swap_tracked_sides: bool,
+    /// How the tracked left and right blinks drive avatars which only have a
+    /// combined blink morph.
+    #[arg(long, value_enum, default_value = "max")]
+    pub blink_combine: BlinkCombine,
+    /// Axis convention of the tracked face transforms: `mediapipe`, `arkit`,
+    /// `unity`, or our axes as tracker axes, like `-x,y,z`.
+    #[arg(long, value_parser = AxisConvention::parse, default_value = "mediapipe")]
+    pub face_axes: AxisConvention,
+    /// Axes along which tracked head translation is ignored, such as `z` to
+    /// stop leaning in from zooming, or `xyz` to apply rotation only.
+    #[arg(long, value_parser = parse_axes, default_value = "none")]
+    pub lock_translation: BVec3,
+    /// Directory of saved calibration profiles.
+    #[arg(long, default_value = "calibration")]
+    pub calibration_dir: PathBuf,
+    /// Calibration profile to use and save to, such as a name for the
+    /// camera setup. Defaults to one for `--webcam-device` when capturing,
+    /// or else `default`.
+    #[arg(long)]
+    pub calibration_profile: Option<String>,
+    /// Smoothing of all tracking: `off`, `responsive` (~35ms of added
+    /// latency), `balanced` (~105ms) or `smooth` (~320ms). Each group
+    /// defaults to its own preset.
+    #[arg(long, value_parser = parse_smoothing_preset)]
+    pub smoothing: Option<SmoothingPreset>,
+    /// Smoothing of the tracked blend shapes, other than gaze. Defaults to
+    /// `responsive`.
+    #[arg(long, value_parser = parse_smoothing_preset)]
+    pub expression_smoothing: Option<SmoothingPreset>,
+    /// Smoothing of the tracked face transform. Defaults to `balanced`.
+    #[arg(long, value_parser = parse_smoothing_preset)]
+    pub head_smoothing: Option<SmoothingPreset>,
+    /// Smoothing of the `eyeLook*` gaze blend shapes. Defaults to
+    /// `responsive`.
+    #[arg(long, value_parser = parse_smoothing_preset)]
+    pub look_at_smoothing: Option<SmoothingPreset>,
+    /// Override the presets' cutoff frequency when still, in Hz.
+    #[arg(long)]
+    pub smoothing_min_cutoff: Option<f32>,
+    /// Override how much the presets' cutoff rises with speed.
+    #[arg(long)]
+    pub smoothing_beta: Option<f32>,
+    /// Override how far ahead the presets extrapolate, in seconds.
+    #[arg(long)]
+    pub smoothing_extrapolation: Option<f32>,
+    /// Open the plot of incoming against applied tracking values at
+    /// startup. End toggles it.
+    #[arg(long)]
+    pub smoothing_plot: bool,
+    /// Seconds taken to fade the avatar fully in or out, with F12 or
+    /// `PUT /v1/fade`.
+    #[arg(long, default_value = "1")]
+    pub fade_duration: f32,
+    /// Seconds taken to cross-fade between avatars when switching. Zero
+    /// switches instantly.
+    #[arg(long, default_value = "0")]
+    pub avatar_crossfade: f32,
+    /// Seconds without a face frame before tracking is considered lost.
+    #[arg(long, default_value = "1")]
+    pub tracking_timeout: f32,
+    /// Consecutive face frames needed before tracking is considered active.
+    #[arg(long, default_value = "5")]
+    pub tracking_min_frames: u32,
+    /// Where to show a "tracking lost" notice in the preview while no face
+    /// is tracked.
+    #[arg(long, value_enum, default_value_t = IndicatorPlacement::TopRight)]
+    pub tracking_indicator: IndicatorPlacement,
+    /// Show the tracking lost notice in the output window too.
+    #[arg(long)]
+    pub tracking_indicator_in_output: bool,
+    /// Add seeded Gaussian noise to incoming face frames, to test smoothing.
+    /// The `--noise-*` options set its standard deviation per channel.
+    #[arg(long)]
+    pub debug_tracking_noise: bool,
+    #[arg(long, default_value = "1")]
+    pub noise_seed: u32,
+    /// In metres.
+    #[arg(long, default_value = "0.002")]
+    pub noise_translation: f32,
+    /// In degrees.
+    #[arg(long, default_value = "0.5")]
+    pub noise_rotation: f32,
+    #[arg(long, default_value = "0.02")]
+    pub noise_blend_shapes: f32,
+    /// JSON file of morphs driven along with blinks, replacing the built-in
+    /// squints. An empty list disables them.
+    #[arg(long)]
+    pub blink_links: Option<PathBuf>,
+    /// JSON file of response curves for tracked expressions, by name, such
+    /// as `{"curves": {"mouthSmileLeft": {"gamma": 0.7}}}`.
+    #[arg(long)]
+    pub expression_curves: Option<PathBuf>,
+    /// Expression pose to hold until tracking first arrives, such as the
+    /// built-in `idle`.
+    #[arg(long, default_value = "")]
+    pub idle_pose: String,
+    /// How long the idle pose takes to fade out once tracking arrives, in
+    /// seconds.
+    #[arg(long, default_value = "1")]
+    pub idle_pose_fade: f32,
+    /// A phoneme timeline to play at startup, which overrides the tracked mouth.
+    #[arg(long)]
+    pub phonemes: Option<String>,
+    /// Crossfade between phonemes, in seconds.
+    #[arg(long, default_value = "0.08")]
+    pub phoneme_fade: f32,
+    #[arg(long, value_enum, default_value = "perspective")]
+    pub output_projection: ProjectionKind,
+    #[arg(long, default_value = "0.6")]
+    pub output_ortho_height: f32,
+    /// Vertical field of view of the preview camera, in degrees.
+    #[arg(long, value_parser = parse_fov, default_value = "45")]
+    pub preview_fov: f32,
+    /// Distance to the preview camera's near clipping plane, in metres.
+    /// Defaults to 0.1.
+    #[arg(long, value_parser = parse_clip_distance)]
+    pub preview_near: Option<f32>,
+    /// Distance to the preview camera's far clipping plane, in metres.
+    /// Defaults to 1000.
+    #[arg(long, value_parser = parse_clip_distance)]
+    pub preview_far: Option<f32>,
+    /// Vertical field of view of the output camera, in degrees, when it uses
+    /// a perspective projection.
+    #[arg(long, value_parser = parse_fov, default_value = "45")]
+    pub output_fov: f32,
+    /// Distance to the output camera's near clipping plane, in metres.
+    /// Defaults to 0.1, or 0 for orthographic projections.
+    #[arg(long, value_parser = parse_clip_distance)]
+    pub output_near: Option<f32>,
+    /// Distance to the output camera's far clipping plane, in metres.
+    /// Defaults to 1000.
+    #[arg(long, value_parser = parse_clip_distance)]
+    pub output_far: Option<f32>,
+    #[arg(long)]
+    pub output_depth_of_field: bool,
+    /// Convey leaning towards the webcam with a dolly zoom of the output
+    /// camera. 0 disables it, 1 moves the camera in proportion to the head.
+    #[arg(long, default_value = "0")]
+    pub dolly_zoom: f32,
+    /// Tracked head distance at which the dolly zoom is neutral, in the
+    /// tracker's units. Defaults to the first tracked distance.
+    #[arg(long)]
+    pub dolly_zoom_reference: Option<f32>,
+    #[arg(long, default_value = "2.8")]
+    pub depth_of_field_aperture: f32,
+    #[arg(long, default_value = "2")]
+    pub msaa_samples: u32,
+    #[arg(long, value_enum, default_value = "none")]
+    pub preview_anti_aliasing: PostAntiAliasing,
+    #[arg(long, value_enum, default_value = "none")]
+    pub output_anti_aliasing: PostAntiAliasing,
+    /// Tonemapping of the preview camera. None suits toon avatars, while
+    /// PBR avatars may look better with aces, agx or tony-mc-mapface.
+    #[arg(long, value_enum, default_value = "none")]
+    pub preview_tonemapping: TonemappingKind,
+    /// Tonemapping of the output camera.
+    #[arg(long, value_enum, default_value = "none")]
+    pub output_tonemapping: TonemappingKind,
+    /// Whether the output window's colour is premultiplied by its alpha.
+    /// Straight alpha avoids dark fringes in consumers which expect it.
+    #[arg(long, value_enum, default_value = "premultiplied")]
+    pub output_alpha: OutputAlpha,
+    /// Exposure of the output camera, in stops. This never affects the
+    /// preview.
+    #[arg(long, default_value = "0")]
+    pub output_exposure: f32,
+    /// Gamma of the output camera. This never affects the preview.
+    #[arg(long, default_value = "1")]
+    pub output_gamma: f32,
+    /// Image within the assets directory to show behind the avatar in the
+    /// output, cropped to fill it. Insert hides it, for transparent capture.
+    #[arg(long)]
+    pub output_background: Option<String>,
+    /// Rate, in Hz, of the fixed timestep which steps expressions and
+    /// procedural animation, so they behave the same at any frame rate.
+    #[arg(long, default_value = "60")]
+    pub fixed_update_rate: f64,
+    /// Background of the preview window. This never affects the output.
+    #[arg(long, value_enum, default_value = "color")]
+    pub preview_background: BackgroundKind,
+    /// Preview clear colour, as hex.
+    #[arg(long, value_parser = parse_color)]
+    pub preview_color: Option<Color>,
+    /// Top colour of the preview gradient, as hex.
+    #[arg(long, value_parser = parse_color)]
+    pub preview_gradient_top: Option<Color>,
+    /// Bottom colour of the preview gradient, as hex.
+    #[arg(long, value_parser = parse_color)]
+    pub preview_gradient_bottom: Option<Color>,
+    /// Skybox image within the assets directory, as six square faces
+    /// stacked vertically (+X, -X, +Y, -Y, +Z, -Z).
+    #[arg(long)]
+    pub preview_skybox: Option<String>,
+    /// Return the preview camera to its framing after this many seconds
+    /// without input. Press Home to save the current framing.
+    #[arg(long)]
+    pub camera_return_timeout: Option<f32>,
+    /// How quickly the camera returns to its framing.
+    #[arg(long, default_value = "2")]
+    pub camera_return_speed: f32,
+}
+
+impl Options {
+    /// The calibration profile named on the command line, or else the one
+    /// keyed on the captured webcam.
+    fn calibration_profile(&self) -> &str {
+        if let Some(name) = &self.calibration_profile {
+            return name;
+        }
+        #[cfg(all(feature = "webcam-capture", target_os = "linux"))]
+        if let Some(device) = &self.webcam_device {
+            return device;
+        }
+        DEFAULT_PROFILE
+    }
+
+    fn preview_lens(&self) -> CameraLens {
+        CameraLens {
+            fov: self.preview_fov,
+            near: self.preview_near,
+            far: self.preview_far,
+        }
+    }
+
+    fn output_lens(&self) -> CameraLens {
+        CameraLens {
+            fov: self.output_fov,
+            near: self.output_near,
+            far: self.output_far,
+        }
+    }
 }
 
 struct InspectorExtrasPlugin;
@@ -60,8 +533,30 @@ impl Plugin for InspectorExtrasPlugin {
 }
 
 fn main() -> anyhow::Result<()> {
-    let options = Options::parse();
+    let mut options = Options::parse();
     let mut app = App::new();
+
+    let mut any_remote = false;
+    for avatar in std::iter::once(&mut options.avatar).chain(options.preload_avatars.iter_mut()) {
+        if remote_avatar::is_url(avatar) {
+            let file_name = remote_avatar::fetch_avatar(
+                avatar, &options.avatar_cache_dir, options.refresh_avatar)?;
+            *avatar = format!("{}://{}", remote_avatar::AVATAR_CACHE_SOURCE, file_name);
+            any_remote = true;
+        }
+    }
+
+    if any_remote {
+        app.register_asset_source(
+            remote_avatar::AVATAR_CACHE_SOURCE,
+            AssetSourceBuilder::platform_default(
+                &options.avatar_cache_dir.to_string_lossy(), None),
+        );
+    }
+
+    let calibration_profiles = CalibrationProfiles::new(
+        options.calibration_dir.clone(), options.calibration_profile());
+
     app
         .add_plugins((
             DefaultPlugins
@@ -77,39 +572,349 @@ fn main() -> anyhow::Result<()> {
             InspectorExtrasPlugin,
             bevy_obj::ObjPlugin,
             bevy_vrm::VrmPlugin,
+            TemporalAntiAliasPlugin,
+            OutputAlphaPlugin,
+            MaterialPlugin::<ShadowCatcherMaterial>::default(),
+            Material2dPlugin::<DownsampleMaterial>::default(),
         ))
         .init_asset_loader::<debug_mesh::DebugMeshLoader>()
+        .init_asset::<PhonemeTimeline>()
+        .init_asset_loader::<PhonemeTimelineLoader>()
         .init_resource::<Faces>()
-        .insert_resource(Msaa::Sample2)
+        .init_resource::<Poses>()
+        .insert_resource(TrackingStatus::new(
+            Duration::from_secs_f32(options.tracking_timeout), options.tracking_min_frames))
+        .init_resource::<LookAt>()
+        .init_resource::<ExpressionWeights>()
+        .register_type::<BrowLandmarks>()
+        .init_resource::<BrowLandmarks>()
+        .init_resource::<BlendShapeMatches>()
+        .register_type::<TrackingSides>()
+        .register_type::<EyeBlinks>()
+        .register_type::<MorphAudit>()
+        .init_resource::<MorphAudit>()
+        .insert_resource(EyeBlinks {
+            combine: options.blink_combine,
+        })
+        .init_resource::<DerivedBlinks>()
+        .register_type::<Smoothing>()
+        .register_type::<SmoothingParams>()
+        .register_type::<AvatarFade>()
+        .insert_resource(AvatarFade::new(options.fade_duration))
+        .init_resource::<FadeOriginals>()
+        .register_type::<AvatarCrossfade>()
+        .register_type::<AvatarOpacity>()
+        .insert_resource(AvatarCrossfade {
+            duration: options.avatar_crossfade,
+        })
+        .register_type::<HiddenParts>()
+        .register_type::<TrackingNoise>()
+        .insert_resource(TrackingNoise::new(
+            options.debug_tracking_noise,
+            options.noise_seed,
+            options.noise_translation,
+            options.noise_rotation.to_radians(),
+            options.noise_blend_shapes,
+        ))
+        .register_type::<TrackedTranslation>()
+        .register_type::<CameraPlaneSettings>()
+        .insert_resource(CameraPlaneSettings {
+            distance: options.webcam_plane_distance,
+            size: options.webcam_plane_size,
+        })
+        .insert_resource(TrackedTranslation::new(!options.lock_translation))
+        .register_type::<Calibration>()
+        .insert_resource(load_active_profile(&calibration_profiles))
+        .insert_resource(calibration_profiles)
+        .insert_resource(HiddenParts {
+            names: options.hide_parts.iter().cloned().collect(),
+        })
+        .insert_resource(TrackingSides {
+            swap: options.swap_tracked_sides,
+        })
+        .insert_resource(PhonemePlayback {
+            fade: options.phoneme_fade,
+            ..default()
+        })
+        .insert_resource(options.procedural_seed.map_or_else(ProceduralSeed::from_time, ProceduralSeed))
+        .init_resource::<SaccadeState>()
+        .init_resource::<BreathingState>()
+        .init_resource::<IdleSwayState>()
+        .register_type::<ProceduralPose>()
+        .register_type::<ProceduralSeed>()
+        .register_type::<Breathing>()
+        .register_type::<IdleSway>()
+        .register_type::<ArmTracking>()
+        .register_type::<FootGrounding>()
+        .register_type::<BoneLimits>()
+        .register_type::<RestRotation>()
+        .register_type::<PreviewBackground>()
+        .register_type::<OutputBackground>()
+        .register_type::<Tongue>()
+        .register_type::<DollyZoom>()
+        .insert_resource(Breathing {
+            enabled: options.breathing,
+            rate: options.breathing_rate,
+            depth: options.breathing_depth,
+            morph: options.breathing_morph.clone(),
+            ..default()
+        })
+        .register_type::<Saccades>()
+        .insert_resource(Saccades {
+            enabled: options.saccades,
+            amplitude: options.saccade_amplitude,
+            frequency: options.saccade_frequency,
+        })
+        .insert_resource(IdleSway {
+            enabled: options.idle_sway,
+            amplitude: options.idle_sway_amplitude,
+            speed: options.idle_sway_speed,
+            ..default()
+        })
+        .insert_resource(Tongue {
+            morph: options.tongue_morph.clone(),
+            bone: options.tongue_bone.clone(),
+            offset: options.tongue_bone_offset,
+        })
+        .insert_resource(ArmTracking {
+            enabled: options.arm_tracking,
+            ..default()
+        })
+        .insert_resource(FootGrounding {
+            enabled: options.foot_ik,
+            ground_height: options.floor_height,
+            foot_roll: options.foot_roll,
+        })
         .add_systems(Update, (
-            api::update_api,
-            update_face_mesh,
-            update_face_transforms,
+            (api::update_api, smooth_face_transforms).chain(),
+            update_face_mesh.after(smooth_face_transforms),
+            update_face_transforms.after(smooth_face_transforms),
             update_free_look,
             toggle_visibility,
             update_debug_text,
             update_camera_plane,
             apply_blend_shapes,
-            update_morph_targets,
+            update_morph_targets.in_set(ExpressionSystems::Apply),
+            update_depth_of_field,
+            fall_back_from_taa,
+            update_dolly_zoom.after(smooth_face_transforms),
+            fit_output_background.after(update_dolly_zoom),
             dump_state,
+            switch_avatar_hotkeys,
+            report_preload_progress,
         ))
+        .add_systems(Update, (
+            add_procedural_poses,
+            apply_breathing,
+            apply_idle_sway,
+            apply_breath_morph.after(update_morph_targets),
+            apply_tongue_morph.after(update_morph_targets),
+            (add_tongue_rest, apply_tongue_bone).chain(),
+            (
+                update_tracking_status,
+                api::publish_tracking_status,
+            ).chain().after(api::update_api),
+            reset_translation_lock.after(api::update_api),
+            update_arm_ik.after(api::update_api),
+            update_look_at_target.after(api::update_api),
+            add_rest_rotations,
+            attach_accessories.after(api::update_api),
+            (record_tracked_blend_shapes, report_unmatched_blend_shapes).chain().after(derive_brow_blend_shapes),
+            derive_brow_blend_shapes.after(api::update_api).before(smooth_face_transforms),
+            update_preview_background,
+            prepare_skybox_images,
+        ))
+        .insert_resource(Time::<Fixed>::from_hz(options.fixed_update_rate))
+        .configure_sets(FixedUpdate, (
+            ExpressionSystems::Collect,
+            ExpressionSystems::Layers,
+        ).chain())
+        .add_systems(FixedUpdate, (
+            (
+                collect_tracked_expressions,
+                combine_brow_expressions,
+                smooth_tracked_expressions,
+                apply_response_curves,
+            ).chain().in_set(ExpressionSystems::Collect),
+            (
+                apply_phoneme_timeline,
+                apply_idle_pose,
+                apply_expression_poses,
+                apply_blink_links,
+                apply_eye_blinks,
+            ).chain().in_set(ExpressionSystems::Layers),
+            update_saccades,
+            update_breathing,
+            update_idle_sway,
+        ))
+        .add_systems(Update, (
+            toggle_expression_poses,
+            reload_shaders_hotkey,
+            log_shader_reloads,
+            (toggle_fade_hotkey, update_fade).chain().after(api::update_api),
+            hide_new_parts,
+            update_output_guides,
+            update_tracking_indicators,
+            (
+                toggle_smoothing_plot,
+                sample_incoming.after(api::update_api).before(smooth_face_transforms),
+                sample_applied.after(smooth_face_transforms),
+                draw_smoothing_plot,
+            ).chain(),
+            (start_crossfades, update_crossfades)
+                .chain()
+                .after(switch_avatar_hotkeys)
+                .after(api::update_api)
+                .before(bevy_vrm::spawn_vrms)
+                .before(update_fade),
+            apply_avatar_smoothing
+                .after(switch_avatar_hotkeys)
+                .after(api::update_api)
+                .before(smooth_face_transforms),
+            (morph_audit_hotkeys, apply_morph_audit)
+                .chain()
+                .after(apply_breath_morph)
+                .after(apply_tongue_morph),
+        ))
+        .add_systems(PreUpdate, restore_procedural_poses)
+        .add_systems(PostUpdate, (
+            apply_procedural_poses,
+            update_foot_ik,
+            apply_bone_limits,
+        ).chain().before(TransformSystem::TransformPropagate))
         .add_systems(Startup, init);
     let runtime = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
         .unwrap();
 
-    if let Some(path) = options.extra_blend_shapes.as_ref() {
-        let contents = std::fs::read(path)?;
-        let library = BlendShapeLibrary::from_slice(&contents)?;
+    options.preview_lens().validate(ProjectionKind::Perspective)
+        .map_err(|err| err.context("invalid preview camera"))?;
+    options.output_lens().validate(options.output_projection)
+        .map_err(|err| err.context("invalid output camera"))?;
+
+    let mut msaa = msaa_from_samples(options.msaa_samples)?;
+    app.insert_resource(TaaFallback { msaa });
+    if msaa != Msaa::Off
+        && (options.preview_anti_aliasing == PostAntiAliasing::Taa
+        || options.output_anti_aliasing == PostAntiAliasing::Taa) {
+        warn!("TAA is incompatible with MSAA, disabling MSAA");
+        msaa = Msaa::Off;
+    }
+
+    app.insert_resource(msaa);
+
+    let expression_poses = match options.expression_poses.as_ref() {
+        Some(path) => ExpressionPoses::from_file(path)?,
+        None => ExpressionPoses::default(),
+    };
+    if !options.idle_pose.is_empty() && !expression_poses.poses.iter().any(|p| p.name == options.idle_pose) {
+        warn!("no expression pose named {}", options.idle_pose);
+    }
+    app
+        .register_type::<ExpressionPoses>()
+        .register_type::<IdlePose>()
+        .insert_resource(expression_poses)
+        .insert_resource(IdlePose::new(options.idle_pose.clone(), options.idle_pose_fade));
+
+    let mut smoothing = Smoothing::default();
+    for (group, preset) in [
+        (SmoothingGroup::Expressions, options.expression_smoothing),
+        (SmoothingGroup::Head, options.head_smoothing),
+        (SmoothingGroup::LookAt, options.look_at_smoothing),
+    ] {
+        smoothing.apply(&SetSmoothingRequest {
+            group: Some(group),
+            preset: Some(preset.or(options.smoothing).unwrap_or(Smoothing::default_preset(group))),
+            min_cutoff: options.smoothing_min_cutoff,
+            beta: options.smoothing_beta,
+            extrapolation: options.smoothing_extrapolation,
+        });
+    }
+    app.insert_resource(smoothing.clone());
+    app.insert_resource(SmoothingPlot::new(options.smoothing_plot));
+
+    let blink_links = match options.blink_links.as_ref() {
+        Some(path) => BlinkLinks::from_file(path)?,
+        None => BlinkLinks::default(),
+    };
+    app
+        .register_type::<BlinkLinks>()
+        .insert_resource(blink_links);
+
+    let response_curves = match options.expression_curves.as_ref() {
+        Some(path) => ResponseCurves::from_file(path)?,
+        None => ResponseCurves::default(),
+    };
+    app
+        .register_type::<ResponseCurves>()
+        .insert_resource(response_curves);
+
+    let bone_limits = match options.bone_limits.as_ref() {
+        Some(path) => BoneLimits::from_file(path)?,
+        None => BoneLimits::default(),
+    };
+    app.insert_resource(AvatarBoneLimits {
+        limits: bone_limits,
+    });
+
+    let morph_aliases = match options.morph_aliases.as_ref() {
+        Some(path) => serde_json::from_slice(&std::fs::read(path)?)?,
+        None => MorphAliases::default(),
+    };
+    app.insert_resource(AvatarMorphAliases {
+        aliases: morph_aliases,
+    });
+
+    let accessories = match options.accessories.as_ref() {
+        Some(path) => Accessories::from_file(path)?,
+        None => Accessories::default(),
+    };
+    app.insert_resource(accessories);
+
+    if !options.extra_blend_shapes.is_empty() {
+        let library = BlendShapeLibrary::from_paths(&options.extra_blend_shapes)?;
         info!("loaded {} extra blend shapes", library.blend_shapes.len());
         app.insert_resource(ExtraBlendShapesLibrary {
             library,
         });
     }
 
+    let mut avatar_configs = AvatarConfigs::new(smoothing);
+    for avatar in std::iter::once(&options.avatar).chain(&options.preload_avatars) {
+        let file = avatar_config::avatar_file(avatar, &options.avatar_cache_dir);
+        let Some(config) = AvatarConfig::discover(&file)? else {
+            continue;
+        };
+        info!("loaded avatar config {}", AvatarConfig::sidecar_path(&file).display());
+        if !config.extra_blend_shapes.is_empty() {
+            let paths = [options.extra_blend_shapes.as_slice(), &config.extra_blend_shapes].concat();
+            let library = BlendShapeLibrary::from_paths(&paths)?;
+            avatar_configs.blend_shapes.insert(avatar.clone(), library.blend_shapes);
+        }
+        avatar_configs.configs.insert(avatar.clone(), config);
+    }
+    app.insert_resource(avatar_configs);
+
+    #[cfg(all(feature = "webcam-capture", target_os = "linux"))]
+    if let Some(device) = options.webcam_device.as_ref() {
+        app
+            .insert_resource(webcam_capture::WebcamCapture::open(device)?)
+            .add_systems(Update, webcam_capture::apply_webcam_capture
+                .after(api::update_api)
+                .before(update_camera_plane));
+    }
+
+    if let Some(dir) = options.export_frames.as_ref() {
+        std::fs::create_dir_all(dir).map_err(|err| error::IdolError::write(dir, err))?;
+        let fps = options.output_fps.unwrap_or(30);
+        app
+            .insert_resource(FrameExport::new(dir.clone(), fps, options.export_frame_count))
+            .add_systems(Update, export_frames);
+    }
+
     let api_addr = options.api_bind.parse()?;
-    let (api_state, api_resource) = api::ApiState::new();
+    let (api_state, api_resource) = api::ApiState::new(options.face_axes);
     runtime.spawn(async move {
         if let Err(err) = axum_server::Server::bind(api_addr)
             .serve(api::new_api().with_state(api_state).into_make_service()).await {
@@ -140,6 +945,11 @@ struct FaceBlendShapes;
 struct FreeLook {
     pub move_speed: f32,
     pub look_speed: f32,
+    /// Seconds without input before returning to `home`, if set.
+    pub return_timeout: Option<f32>,
+    pub return_speed: f32,
+    pub home: Transform,
+    pub idle: f32,
 }
 
 #[derive(Component)]
@@ -156,28 +966,174 @@ struct ExtraBlendShapesLibrary {
     library: BlendShapeLibrary,
 }
 
+fn load_avatar(
+    assets: &AssetServer,
+    path: &str,
+    options: &Options,
+    morph_aliases: &MorphAliases,
+) -> Handle<Vrm> {
+    let missing_normals = if options.smooth_normals {
+        MissingNormals::Smooth
+    } else {
+        MissingNormals::Flat
+    };
+    let morph_aliases = morph_aliases.clone();
+    let root_correction = options.root_correction;
+    let look_distance = options.look_distance;
+    let alpha_cutoffs: HashMap<String, f32> = options.alpha_cutoffs.iter().cloned().collect();
+    let cull_modes: HashMap<String, MaterialCullMode> = options.cull_modes.iter().cloned().collect();
+    let missing_look_at = if options.ignore_missing_look_at {
+        MissingLookAt::Ignore
+    } else {
+        MissingLookAt::Default
+    };
+    assets.load_with_settings(path.to_string(), move |settings: &mut VrmLoaderSettings| {
+        settings.missing_normals = missing_normals;
+        settings.morph_aliases = morph_aliases.clone();
+        settings.root_correction = root_correction;
+        settings.look_distance = look_distance;
+        settings.missing_look_at = missing_look_at;
+        settings.alpha_cutoffs = alpha_cutoffs.clone();
+        settings.cull_modes = cull_modes.clone();
+    })
+}
+
+fn parse_smoothing_preset(s: &str) -> Result<SmoothingPreset, String> {
+    serde_json::from_value(serde_json::Value::String(s.to_string()))
+        .map_err(|_| format!("unknown smoothing preset {s:?}, expected off, responsive, balanced or smooth"))
+}
+
+fn parse_fov(s: &str) -> Result<f32, String> {
+    let fov: f32 = s.parse().map_err(|err| format!("invalid field of view {s:?}: {err}"))?;
+    if fov > 0. && fov < 180. {
+        Ok(fov)
+    } else {
+        Err(format!("field of view must be between 0 and 180 degrees, got {fov}"))
+    }
+}
+
+fn parse_clip_distance(s: &str) -> Result<f32, String> {
+    let distance: f32 = s.parse().map_err(|err| format!("invalid distance {s:?}: {err}"))?;
+    if distance.is_finite() && distance >= 0. {
+        Ok(distance)
+    } else {
+        Err(format!("clipping plane distance must be a non-negative number, got {distance}"))
+    }
+}
+
+fn parse_window_position(s: &str) -> Result<IVec2, String> {
+    match s.split_once(',') {
+        Some((x, y)) => Ok(IVec2::new(
+            x.trim().parse().map_err(|err| format!("invalid x {x:?}: {err}"))?,
+            y.trim().parse().map_err(|err| format!("invalid y {y:?}: {err}"))?,
+        )),
+        None => Err(format!("invalid position {s:?}: expected x,y")),
+    }
+}
+
+fn parse_alpha_cutoff(s: &str) -> Result<(String, f32), String> {
+    match s.rsplit_once('=') {
+        Some((material, cutoff)) => Ok((
+            material.to_string(),
+            cutoff.trim().parse().map_err(|err| format!("invalid cutoff {cutoff:?}: {err}"))?,
+        )),
+        None => Err(format!("invalid alpha cutoff {s:?}: expected <material>=<cutoff>")),
+    }
+}
+
+fn parse_cull_mode(s: &str) -> Result<(String, MaterialCullMode), String> {
+    let Some((material, cull_mode)) = s.rsplit_once('=') else {
+        return Err(format!("invalid cull mode {s:?}: expected <material>=<back|front|none>"));
+    };
+    let cull_mode = serde_json::from_value(serde_json::Value::String(cull_mode.trim().to_string()))
+        .map_err(|_| format!("unknown cull mode {cull_mode:?}, expected back, front or none"))?;
+    Ok((material.to_string(), cull_mode))
+}
+
+fn parse_root_correction(s: &str) -> Result<RootCorrection, String> {
+    match s {
+        "none" => Ok(RootCorrection::None),
+        "auto" => Ok(RootCorrection::Auto),
+        _ => parse_vec3(s).map(|euler| RootCorrection::Euler(euler.to_array())),
+    }
+}
+
+fn preview_background(options: &Options, assets: &AssetServer) -> PreviewBackground {
+    let defaults = PreviewBackground::default();
+    PreviewBackground {
+        kind: options.preview_background,
+        color: options.preview_color.unwrap_or(defaults.color),
+        top: options.preview_gradient_top.unwrap_or(defaults.top),
+        bottom: options.preview_gradient_bottom.unwrap_or(defaults.bottom),
+        skybox: options.preview_skybox.as_ref().map(|path| assets.load(path)),
+        ..defaults
+    }
+}
+
+#[derive(Resource)]
+struct AvatarBoneLimits {
+    limits: BoneLimits,
+}
+
+#[derive(Resource)]
+struct AvatarMorphAliases {
+    aliases: MorphAliases,
+}
+
 fn init(
     assets: Res<AssetServer>,
     extra_blend_shapes: Option<Res<ExtraBlendShapesLibrary>>,
+    bone_limits: Res<AvatarBoneLimits>,
+    morph_aliases: Res<AvatarMorphAliases>,
+    mut avatar_configs: ResMut<AvatarConfigs>,
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut images: ResMut<Assets<Image>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    mut shadow_catchers: ResMut<Assets<ShadowCatcherMaterial>>,
+    mut downsample_materials: ResMut<Assets<DownsampleMaterial>>,
+    mut phoneme_playback: ResMut<PhonemePlayback>,
+    render_device: Res<RenderDevice>,
     options: Res<Options>,
 ) {
+    let max_texture_size = render_device.limits().max_texture_dimension_2d;
+    let (output_width, output_height) = fit_output_size(
+        options.output_width, options.output_height, max_texture_size);
+    if (output_width, output_height) != (options.output_width, options.output_height) {
+        warn!(
+            "output resolution {}x{} exceeds the GPU's largest texture size of {}, using {}x{}",
+            options.output_width, options.output_height, max_texture_size, output_width, output_height,
+        );
+    }
+
     commands.spawn(DirectionalLightBundle {
+        directional_light: DirectionalLight {
+            // Shadows change the look of the avatar, so only enable them when
+            // there is a floor to catch them.
+            shadows_enabled: options.floor.is_some(),
+            ..default()
+        },
         transform: Transform::from_xyz(1., 10., 10.)
             .looking_at(Vec3::ZERO, Vec3::Y),
         ..default()
     });
 
+    if let Some(style) = options.floor {
+        spawn_floor(
+            &mut commands, &mut meshes, &mut materials, &mut shadow_catchers,
+            style, options.floor_height, options.floor_in_output);
+    }
+    spawn_grid(&mut commands, &mut meshes, &mut materials, options.floor_height, options.grid);
+
     // Preview Camera
-    commands.spawn((
+    let preview_transform = Transform::from_xyz(0., 1., 5.)
+        .looking_at(Vec3::new(0., 1., 0.), Vec3::Y);
+    let mut preview_camera = commands.spawn((
         Name::from("Preview Camera"),
         Camera3dBundle {
-            transform: Transform::from_xyz(0., 1., 5.)
-                .looking_at(Vec3::new(0., 1., 0.), Vec3::Y),
-            tonemapping: Tonemapping::None,
+            transform: preview_transform,
+            projection: options.preview_lens().projection(ProjectionKind::Perspective, 1.),
+            tonemapping: options.preview_tonemapping.tonemapping(),
             ..default()
         },
         RenderLayers::from_layers(&[0, 1]),
@@ -185,8 +1141,15 @@ fn init(
         FreeLook {
             move_speed: 10.,
             look_speed: 0.001,
+            return_timeout: options.camera_return_timeout,
+            return_speed: options.camera_return_speed,
+            home: preview_transform,
+            idle: 0.,
         },
+        preview_background(&options, &assets),
     ));
+    options.preview_anti_aliasing.insert(&mut preview_camera);
+    spawn_output_guides(&mut commands, output_width, output_height);
 
     // Debug Face
     commands.spawn((
@@ -216,32 +1179,94 @@ fn init(
             Window {
                 title: "Bevy Idol [Output]".into(),
                 transparent: true,
+                composite_alpha_mode: options.output_alpha.composite_alpha_mode(),
                 resizable: false,
-                resolution: WindowResolution::new(options.output_width as f32, options.output_height as f32)
+                decorations: !options.output_borderless,
+                window_level: if options.output_always_on_top {
+                    WindowLevel::AlwaysOnTop
+                } else {
+                    WindowLevel::Normal
+                },
+                position: options.output_position
+                    .map_or(WindowPosition::Automatic, WindowPosition::At),
+                resolution: WindowResolution::new(output_width as f32, output_height as f32)
                     .with_scale_factor_override(1.),
                 ..default()
             },
         ))
         .id();
-    commands.spawn((
+    let supersampling = supersampling_scale(
+        options.output_supersampling,
+        output_width,
+        output_height,
+        max_texture_size,
+    );
+    if supersampling < options.output_supersampling {
+        warn!(
+            "output supersampling limited to {}x ({}x{}) by the GPU's largest texture size of {}",
+            supersampling, output_width * supersampling, output_height * supersampling, max_texture_size,
+        );
+    }
+    let output_target = if supersampling > 1 {
+        spawn_supersampled_output(
+            &mut commands,
+            &mut images,
+            &mut meshes,
+            &mut downsample_materials,
+            output_window,
+            SupersampledOutput {
+                width: output_width,
+                height: output_height,
+                scale: supersampling,
+                straight_alpha: options.output_alpha == OutputAlpha::Straight,
+            },
+        )
+    } else {
+        RenderTarget::Window(WindowRef::Entity(output_window))
+    };
+    let mut output_camera = commands.spawn((
         Name::from("Output Camera"),
         Camera3dBundle {
             transform: Transform::from_xyz(0., 1.5, 1.)
                 .looking_at(Vec3::new(0., 1.5, 0.), Vec3::Y),
             camera: Camera {
-                target: RenderTarget::Window(WindowRef::Entity(output_window)),
+                target: output_target,
                 output_mode: CameraOutputMode::Write {
                     blend_state: None,
                     clear_color: Color::NONE.into(),
                 },
                 ..default()
             },
-            tonemapping: Tonemapping::None,
+            projection: options.output_lens().projection(options.output_projection, options.output_ortho_height),
+            tonemapping: options.output_tonemapping.tonemapping(),
+            color_grading: output_color_grading(options.output_exposure, options.output_gamma),
             ..default()
         },
         RenderLayers::from_layers(&[0, 2]),
         OutputCamera,
+        DollyZoom::new(options.dolly_zoom, options.dolly_zoom_reference),
     ));
+    options.output_anti_aliasing.insert(&mut output_camera);
+    if supersampling == 1 {
+        // Otherwise the downsample converts to straight alpha, after
+        // averaging.
+        options.output_alpha.insert(&mut output_camera);
+    }
+
+    if options.output_depth_of_field {
+        // Gaussian blur keeps the alpha channel smooth, whereas the bokeh
+        // mode tends to bleed the transparent clear colour into the avatar.
+        output_camera.insert(DepthOfFieldSettings {
+            mode: DepthOfFieldMode::Gaussian,
+            aperture_f_stops: options.depth_of_field_aperture,
+            ..default()
+        });
+    }
+    let output_camera = output_camera.id();
+
+    if let Some(path) = &options.output_background {
+        spawn_output_background(&mut commands, &mut meshes, &mut materials, output_camera, assets.load(path));
+    }
 
     // Debug Marker
     commands
@@ -265,14 +1290,29 @@ fn init(
         ));
 
     // Camera plane
-    let mut camera_image = Image::default();
-    camera_image.data.copy_from_slice(&[0xff, 0, 0, 0xff]);
+    // A 1x1 opaque red placeholder, until the first webcam frame replaces it.
+    let camera_image = Image::new(
+        Extent3d {
+            width: 1,
+            height: 1,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        vec![0xff, 0, 0, 0xff],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
     let camera_image = images.add(camera_image);
     let camera_material = materials.add(StandardMaterial {
         base_color_texture: Some(camera_image.clone()),
         perceptual_roughness: 1.,
         unlit: true,
         cull_mode: Some(Face::Front),
+        alpha_mode: if options.webcam_alpha {
+            AlphaMode::Blend
+        } else {
+            AlphaMode::Opaque
+        },
         ..default()
     });
     commands.insert_resource(WebcamTexture {
@@ -282,12 +1322,13 @@ fn init(
     commands.spawn((
         Name::from("Camera Plane"),
         PbrBundle {
+            // A unit plane, sized and placed by `update_camera_plane`.
             mesh: meshes.add(Mesh::from(Plane3d {
                 normal: Dir3::NEG_Y,
-                half_size: Vec2::splat(5.),
+                half_size: Vec2::splat(0.5),
             })),
             material: camera_material,
-            transform: Transform::from_xyz(0., 1., -5.)
+            transform: Transform::from_xyz(0., 1., -options.webcam_plane_distance)
                 .with_rotation(Quat::from_rotation_z(std::f32::consts::PI)
                     * Quat::from_rotation_x(std::f32::consts::PI * 0.5)),
             visibility: Visibility::Hidden,
@@ -298,6 +1339,13 @@ fn init(
         ToggleVisibilityKey(KeyCode::F7),
     ));
 
+    // Tracking Lost Indicators
+    let indicator_font = assets.load("fonts/Chewy-Regular.ttf");
+    spawn_tracking_indicator(&mut commands, options.tracking_indicator, None, indicator_font.clone());
+    if options.tracking_indicator_in_output {
+        spawn_tracking_indicator(&mut commands, options.tracking_indicator, Some(output_camera), indicator_font);
+    }
+
     // Debug Text
     let debug_text_style = TextStyle {
         font: assets.load("fonts/Chewy-Regular.ttf"),
@@ -319,20 +1367,45 @@ fn init(
         DebugText,
     ));
 
+    if let Some(path) = options.phonemes.as_ref() {
+        phoneme_playback.play(assets.load(path), 0.);
+    }
+
     // Avatar
+    let avatars = std::iter::once(&options.avatar)
+        .chain(&options.preload_avatars)
+        .map(|path| {
+            let aliases = avatar_configs.configs.get(path)
+                .and_then(|config| config.morph_aliases.as_ref())
+                .unwrap_or(&morph_aliases.aliases);
+            let vrm = load_avatar(&assets, path, &options, aliases);
+            avatar_configs.register(path, &vrm);
+            (path, vrm)
+        })
+        .collect::<Vec<_>>();
     let mut avatar = commands.spawn((
         Name::from("Avatar"),
         VrmBundle {
-            vrm: assets.load(&options.avatar),
+            vrm: avatars[0].1.clone(),
             ..default()
         },
+        bone_limits.limits.clone(),
     ));
 
-    if let Some(extra_blend_shapes) = extra_blend_shapes.as_ref() {
-        avatar
-            .insert(AddBlendShapes {
-                blend_shapes: extra_blend_shapes.library.blend_shapes.clone(),
-            });
+    if !options.preload_avatars.is_empty() {
+        avatar.insert(AvatarSet::new(avatars.iter().map(|(_, vrm)| vrm.clone()).collect()));
+    }
+
+    let global_blend_shapes = extra_blend_shapes.as_ref()
+        .map(|extra_blend_shapes| extra_blend_shapes.library.blend_shapes.clone());
+    if global_blend_shapes.is_some() || !avatar_configs.blend_shapes.is_empty() {
+        let mut add_blend_shapes = AddBlendShapes::new(global_blend_shapes.unwrap_or_default());
+        for (path, vrm) in &avatars {
+            if let Some(blend_shapes) = avatar_configs.blend_shapes.get(*path) {
+                add_blend_shapes.avatar_blend_shapes.insert(vrm.id(), Arc::new(blend_shapes.clone()));
+            }
+        }
+        avatar.insert(add_blend_shapes);
     }
 }
 
@@ -402,7 +1475,7 @@ fn update_free_look(
     keys: Res<ButtonInput<KeyCode>>,
     mouse_buttons: Res<ButtonInput<MouseButton>>,
     mut mouse_motion: EventReader<MouseMotion>,
-    mut entities: Query<(&mut Transform, &FreeLook)>,
+    mut entities: Query<(&mut Transform, &mut FreeLook)>,
 ) {
     let mut translate = Vec3::ZERO;
     if keys.pressed(KeyCode::KeyW) {
@@ -433,11 +1506,31 @@ fn update_free_look(
         }
     }
 
-    for (mut transform, look) in &mut entities {
+    let interacting = keys.get_pressed().next().is_some()
+        || mouse_buttons.get_pressed().next().is_some();
+    let save_home = keys.just_pressed(KeyCode::Home);
+
+    for (mut transform, mut look) in &mut entities {
         transform.rotate_local_y(rotate.x * look.look_speed);
         transform.rotate_local_x(rotate.y * look.look_speed);
         let delta_translation = transform.rotation * translate * look.move_speed;
         transform.translation += delta_translation;
+
+        if save_home {
+            look.home = *transform;
+        }
+
+        if interacting {
+            look.idle = 0.;
+            continue;
+        }
+
+        look.idle += time.delta_seconds();
+        if look.return_timeout.is_some_and(|timeout| look.idle >= timeout) {
+            let t = 1. - (-look.return_speed * time.delta_seconds()).exp();
+            transform.translation = transform.translation.lerp(look.home.translation, t);
+            transform.rotation = transform.rotation.slerp(look.home.rotation, t);
+        }
     }
 }
 
@@ -457,6 +1550,7 @@ fn toggle_visibility(
 
 fn update_debug_text(
     faces: Res<Faces>,
+    tracking: Res<TrackingStatus>,
     mut debug_text: Query<&mut Text, With<DebugText>>,
     preview_camera: Query<&Transform, With<PreviewCamera>>,
 ) {
@@ -475,6 +1569,8 @@ fn update_debug_text(
         } else {
             text.sections[0].value = "No face\n".into();
         }
+        let status = if tracking.active { "active" } else { "inactive" };
+        writeln!(text.sections[0].value, "tracking {status}").ok();
 
         if let Some(transform) = preview_camera.iter().next() {
             text.sections[1].value = format!(
@@ -486,6 +1582,7 @@ fn update_debug_text(
 
 fn update_camera_plane(
     webcam: Res<WebcamTexture>,
+    settings: Res<CameraPlaneSettings>,
     images: Res<Assets<Image>>,
     mut query: Query<&mut Transform, With<CameraPlane>>,
 ) {
@@ -502,51 +1599,64 @@ fn update_camera_plane(
     };
 
     for mut transform in &mut query {
-        transform.scale.x = x;
-        transform.scale.z = -y;
+        transform.translation.z = -settings.distance;
+        transform.scale.x = x * settings.size;
+        transform.scale.z = -y * settings.size;
     }
 }
 
 fn update_morph_targets(
+    mut commands: Commands,
     mut gizmos: Gizmos,
     faces: Res<Faces>,
+    expressions: Res<ExpressionWeights>,
+    blinks: Res<DerivedBlinks>,
     meshes: Res<Assets<Mesh>>,
-    mut entities: Query<(&Handle<Mesh>, &mut MeshMorphWeights)>,
+    vrms: Res<Assets<Vrm>>,
+    mut blend_shape_matches: ResMut<BlendShapeMatches>,
+    mut mesh_events: EventReader<AssetEvent<Mesh>>,
+    mut entities: Query<(Entity, &Handle<Mesh>, &mut MeshMorphWeights, Option<&mut MorphTargetIndices>)>,
     // humanoids: Query<&Eyes>,
     mut look_targets: Query<&mut Transform, With<LookAtTarget>>,
 ) {
-    let Some(face) = faces.faces.get(0) else {
-        return;
-    };
-    let blend_shapes = &face.blend_shapes;
-
-    let p = face.transform.translation + Vec3::Y;
-    let d = face.transform.translation.normalize();
-    let l = Vec3::Y + d * -5. / d.z;
-
-    let u = p + face.transform.up() * 0.1;
-    let f = p + face.transform.forward() * 0.1;
-    gizmos.line(p, u, MAROON);
-    gizmos.line(p, f, BEIGE);
-    gizmos.line(l, Vec3::Y, BLUE);
-    // gizmos.line(p, l, Color::CYAN);
-
-    let mut color = RED;
-    for landmarks in face.landmarks[468..].windows(2) {
-        let a = &landmarks[0];
-        let b = &landmarks[1];
-
-        // let p0 = q.transform_point(a.position);
-        // let p1 = q.transform_point(b.position);
-
-        // log::info!("axax {} / {} - {} {}", a.position, b.position, p0, p1);
-
-        // gizmos.line(p0, p1, color);
-        // color.set_r(color.r() - 0.1);
+    if let Some(face) = faces.faces.get(0) {
+        let p = face.transform.translation + Vec3::Y;
+        let d = face.transform.translation.normalize();
+        let l = Vec3::Y + d * -5. / d.z;
+
+        let u = p + face.transform.up() * 0.1;
+        let f = p + face.transform.forward() * 0.1;
+        gizmos.line(p, u, MAROON);
+        gizmos.line(p, f, BEIGE);
+        gizmos.line(l, Vec3::Y, BLUE);
+        // gizmos.line(p, l, Color::CYAN);
+
+        let mut color = RED;
+        for landmarks in face.landmarks[468..].windows(2) {
+            let a = &landmarks[0];
+            let b = &landmarks[1];
+
+            // let p0 = q.transform_point(a.position);
+            // let p1 = q.transform_point(b.position);
+
+            // log::info!("axax {} / {} - {} {}", a.position, b.position, p0, p1);
+
+            // gizmos.line(p0, p1, color);
+            // color.set_r(color.r() - 0.1);
+        }
     }
 
-    for (mesh, mut weights) in &mut entities {
-        let Some(mesh) = meshes.get(mesh) else {
+    // Adding blend shapes modifies the mesh in place.
+    let modified: HashSet<AssetId<Mesh>> = mesh_events.read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+
+    for (entity, handle, mut weights, cached_indices) in &mut entities {
+        let mesh_id = handle.id();
+        let Some(mesh) = meshes.get(handle) else {
             continue;
         };
 
@@ -554,9 +1664,41 @@ fn update_morph_targets(
             continue;
         };
 
+        // Adding blend shapes replaces the mesh's morph targets, so the
+        // weights spawned with the scene may no longer line up with them.
+        if names.len() != weights.weights().len() {
+            warn!("mesh has {} morph targets but {} weights, resizing weights",
+                names.len(), weights.weights().len());
+            match MeshMorphWeights::new(vec![0.; names.len()]) {
+                Ok(new_weights) => *weights = new_weights,
+                Err(err) => {
+                    warn!("failed to resize morph weights: {err}");
+                    continue;
+                }
+            }
+        }
+
+        // Only look up the VRM's aliases when the indices need rebuilding.
+        let aliases = || vrms.iter()
+            .find(|(_, vrm)| vrm.meshes.iter().any(|m| m.id() == mesh_id))
+            .map(|(_, vrm)| &vrm.morph_aliases);
         let weights = weights.weights_mut();
-        for (name, weight) in names.iter().zip(weights.iter_mut()) {
-            *weight = blend_shapes.get(name.as_str()).copied().unwrap_or(0.);
+        match cached_indices {
+            Some(mut indices) => {
+                if !indices.is_valid(mesh_id, &expressions)
+                    || indices.indices.len() != names.len()
+                    || modified.contains(&mesh_id) {
+                    *indices = MorphTargetIndices::new(mesh_id, names, aliases(), &expressions);
+                    blend_shape_matches.add_indices(&indices);
+                }
+                indices.apply(&expressions, &blinks, weights);
+            }
+            None => {
+                let indices = MorphTargetIndices::new(mesh_id, names, aliases(), &expressions);
+                blend_shape_matches.add_indices(&indices);
+                indices.apply(&expressions, &blinks, weights);
+                commands.entity(entity).insert(indices);
+            }
         }
     }
 
@@ -579,6 +1721,8 @@ fn update_morph_targets(
 fn dump_state(
     keys: Res<ButtonInput<KeyCode>>,
     faces: Res<Faces>,
+    meshes: Res<Assets<Mesh>>,
+    face_meshes: Query<&Handle<Mesh>, With<FaceMesh>>,
 ) {
     if !keys.just_pressed(KeyCode::F11) {
         return;
@@ -599,5 +1743,19 @@ fn dump_state(
         }
     }
 
-    std::fs::write("out.txt", out).unwrap();
+    match error::write_file("out.txt", out) {
+        Ok(()) => info!("wrote tracking state to out.txt"),
+        Err(err) => error!("{err}"),
+    }
+
+    // The face mesh is a copy of the canonical model, so it has the
+    // landmark connectivity.
+    if let Some(face) = faces.faces.first() {
+        let mesh = face_meshes.iter().find_map(|handle| meshes.get(handle));
+        let obj = debug_mesh::landmarks_to_obj(&face.landmarks, mesh);
+        match error::write_file("landmarks.obj", obj) {
+            Ok(()) => info!("wrote {} landmarks to landmarks.obj", face.landmarks.len()),
+            Err(err) => error!("{err}"),
+        }
+    }
 }
@@ -0,0 +1,146 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use bevy::render::mesh::morph::MeshMorphWeights;
+
+use crate::DebugText;
+
+const AUDIT_KEY: KeyCode = KeyCode::F1;
+const NEXT_KEY: KeyCode = KeyCode::ArrowRight;
+const PREVIOUS_KEY: KeyCode = KeyCode::ArrowLeft;
+const PAUSE_KEY: KeyCode = KeyCode::Space;
+
+/// Cycle through every morph target on the avatar, one at a time, ramping
+/// each up and back down, to check what an avatar supports.
+///
+/// While active this overrides all other expressions.
+#[derive(Debug, Clone, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct MorphAudit {
+    pub active: bool,
+    pub paused: bool,
+    /// Seconds spent on each morph target.
+    pub period: f32,
+    pub names: Vec<String>,
+    pub index: usize,
+    pub time: f32,
+}
+
+impl Default for MorphAudit {
+    fn default() -> Self {
+        MorphAudit {
+            active: false,
+            paused: false,
+            period: 1.5,
+            names: Vec::new(),
+            index: 0,
+            time: 0.,
+        }
+    }
+}
+
+impl MorphAudit {
+    fn step(&mut self, offset: isize) {
+        let len = self.names.len().max(1) as isize;
+        self.index = (self.index as isize + offset).rem_euclid(len) as usize;
+        self.time = 0.;
+    }
+
+    fn weight(&self) -> f32 {
+        (PI * (self.time / self.period).clamp(0., 1.)).sin()
+    }
+}
+
+/// F1 starts or stops the audit, the arrow keys step through the morph
+/// targets and space pauses on the current one.
+pub fn morph_audit_hotkeys(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut audit: ResMut<MorphAudit>,
+    meshes: Res<Assets<Mesh>>,
+    entities: Query<&Handle<Mesh>, With<MeshMorphWeights>>,
+    mut debug_text: Query<&mut Visibility, With<DebugText>>,
+) {
+    if keys.just_pressed(AUDIT_KEY) {
+        audit.active = !audit.active;
+        if audit.active {
+            let mut names = Vec::new();
+            for mesh in entities.iter().filter_map(|handle| meshes.get(handle)) {
+                for name in mesh.morph_target_names().unwrap_or_default() {
+                    if !names.contains(name) {
+                        names.push(name.clone());
+                    }
+                }
+            }
+            info!("auditing {} morph targets", names.len());
+            *audit = MorphAudit {
+                active: true,
+                period: audit.period,
+                names,
+                ..default()
+            };
+            for mut visibility in &mut debug_text {
+                *visibility = Visibility::Visible;
+            }
+        }
+    }
+
+    if !audit.active {
+        return;
+    }
+    if keys.just_pressed(NEXT_KEY) {
+        audit.step(1);
+    }
+    if keys.just_pressed(PREVIOUS_KEY) {
+        audit.step(-1);
+    }
+    if keys.just_pressed(PAUSE_KEY) {
+        audit.paused = !audit.paused;
+    }
+}
+
+/// Replace the avatar's morph weights with the audited morph target.
+pub fn apply_morph_audit(
+    time: Res<Time>,
+    mut audit: ResMut<MorphAudit>,
+    meshes: Res<Assets<Mesh>>,
+    mut entities: Query<(&Handle<Mesh>, &mut MeshMorphWeights)>,
+    mut debug_text: Query<&mut Text, With<DebugText>>,
+) {
+    if !audit.active {
+        if audit.is_changed() {
+            for mut text in &mut debug_text {
+                text.sections[2].value.clear();
+            }
+        }
+        return;
+    }
+
+    if audit.paused {
+        // Hold the morph fully on.
+        audit.time = audit.period / 2.;
+    } else {
+        audit.time += time.delta_seconds();
+        if audit.time >= audit.period {
+            audit.step(1);
+        }
+    }
+
+    let Some(name) = audit.names.get(audit.index) else {
+        return;
+    };
+    let weight = audit.weight();
+    for (mesh, mut weights) in &mut entities {
+        let names = meshes.get(mesh).and_then(|mesh| mesh.morph_target_names());
+        for (i, w) in weights.weights_mut().iter_mut().enumerate() {
+            let matches = names.and_then(|names| names.get(i)).is_some_and(|n| n == name);
+            *w = if matches { weight } else { 0. };
+        }
+    }
+
+    for mut text in &mut debug_text {
+        text.sections[2].value = format!(
+            "morph {}/{} {name} = {weight:.2}{}\n",
+            audit.index + 1, audit.names.len(),
+            if audit.paused { " (paused)" } else { "" });
+    }
+}
@@ -0,0 +1,253 @@
+use bevy::core_pipeline::core_3d::graph::{Core3d, Node3d};
+use bevy::core_pipeline::fullscreen_vertex_shader::fullscreen_shader_vertex_state;
+use bevy::ecs::query::QueryItem;
+use bevy::ecs::system::EntityCommands;
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use bevy::render::extract_component::{ExtractComponent, ExtractComponentPlugin};
+use bevy::render::render_graph::{NodeRunError, RenderGraphApp, RenderGraphContext, RenderLabel, ViewNode, ViewNodeRunner};
+use bevy::render::render_resource::binding_types::{sampler, texture_2d};
+use bevy::render::render_resource::{BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries, CachedRenderPipelineId, ColorTargetState, ColorWrites, FragmentState, MultisampleState, Operations, PipelineCache, PrimitiveState, RenderPassColorAttachment, RenderPassDescriptor, RenderPipelineDescriptor, Sampler, SamplerBindingType, SamplerDescriptor, ShaderStages, TextureFormat, TextureSampleType};
+use bevy::render::renderer::{RenderAdapter, RenderContext, RenderDevice, RenderInstance};
+use bevy::render::texture::BevyDefault;
+use bevy::render::view::window::{create_surfaces, ExtractedWindows};
+use bevy::render::view::ViewTarget;
+use bevy::render::{Render, RenderApp};
+use bevy::window::CompositeAlphaMode;
+use clap::ValueEnum;
+
+/// How the output window's alpha channel relates to its colour.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputAlpha {
+    /// Colour is already multiplied by alpha, which is how the avatar is
+    /// rendered over the transparent clear colour.
+    #[default]
+    Premultiplied,
+    /// Colour is divided back out by alpha, for consumers which composite
+    /// straight alpha and would otherwise darken semi-transparent edges. The
+    /// window asks to be composited as straight alpha too, where the surface
+    /// supports it.
+    Straight,
+}
+
+impl OutputAlpha {
+    pub fn composite_alpha_mode(self) -> CompositeAlphaMode {
+        match self {
+            // Leave the platform's choice alone, as not every surface
+            // supports picking the mode explicitly.
+            OutputAlpha::Premultiplied => CompositeAlphaMode::Auto,
+            OutputAlpha::Straight => CompositeAlphaMode::PostMultiplied,
+        }
+    }
+
+    pub fn insert(self, entity: &mut EntityCommands) {
+        if self == OutputAlpha::Straight {
+            entity.insert(StraightAlpha);
+        }
+    }
+}
+
+/// Convert a camera's output from premultiplied to straight alpha.
+#[derive(Debug, Clone, Copy, Component, ExtractComponent)]
+pub struct StraightAlpha;
+
+pub struct OutputAlphaPlugin;
+
+impl Plugin for OutputAlphaPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(ExtractComponentPlugin::<StraightAlpha>::default());
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        // Run after FXAA, which expects premultiplied input, and before
+        // upscaling to the window.
+        render_app
+            .add_systems(Render, check_composite_alpha_modes.before(create_surfaces))
+            .add_render_graph_node::<ViewNodeRunner<StraightAlphaNode>>(Core3d, StraightAlphaLabel)
+            .add_render_graph_edges(
+                Core3d,
+                (Node3d::Fxaa, StraightAlphaLabel, Node3d::EndMainPassPostProcessing),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app.init_resource::<StraightAlphaPipeline>();
+    }
+}
+
+fn wgpu_alpha_mode(mode: CompositeAlphaMode) -> wgpu::CompositeAlphaMode {
+    match mode {
+        CompositeAlphaMode::Auto => wgpu::CompositeAlphaMode::Auto,
+        CompositeAlphaMode::Opaque => wgpu::CompositeAlphaMode::Opaque,
+        CompositeAlphaMode::PreMultiplied => wgpu::CompositeAlphaMode::PreMultiplied,
+        CompositeAlphaMode::PostMultiplied => wgpu::CompositeAlphaMode::PostMultiplied,
+        CompositeAlphaMode::Inherit => wgpu::CompositeAlphaMode::Inherit,
+    }
+}
+
+/// Fall back from composite alpha modes which a window's surface doesn't
+/// support, rather than failing to configure it. Many surfaces, such as
+/// those on Windows and some Wayland compositors, can't composite
+/// post-multiplied alpha.
+///
+/// This runs before Bevy creates each window's surface, so it checks the
+/// capabilities on a surface of its own, which is dropped straight away.
+fn check_composite_alpha_modes(
+    // Surfaces must be created on the main thread on some platforms.
+    #[cfg(any(target_os = "macos", target_os = "ios"))] _marker: Option<NonSend<bevy::core::NonSendMarker>>,
+    mut windows: ResMut<ExtractedWindows>,
+    render_instance: Res<RenderInstance>,
+    render_adapter: Res<RenderAdapter>,
+    mut checked: Local<HashSet<Entity>>,
+) {
+    for window in windows.windows.values_mut() {
+        if window.alpha_mode == CompositeAlphaMode::Auto || !checked.insert(window.entity) {
+            continue;
+        }
+
+        let target = wgpu::SurfaceTargetUnsafe::RawHandle {
+            raw_display_handle: window.handle.display_handle,
+            raw_window_handle: window.handle.window_handle,
+        };
+        // SAFETY: extracted window handles are valid for as long as the
+        // window exists, and the surface is dropped before returning.
+        let surface = match unsafe { render_instance.create_surface_unsafe(target) } {
+            Ok(surface) => surface,
+            Err(err) => {
+                warn!("failed to check the window's supported alpha modes: {err}");
+                continue;
+            }
+        };
+        let supported = surface.get_capabilities(&render_adapter).alpha_modes;
+        if supported.contains(&wgpu_alpha_mode(window.alpha_mode)) {
+            continue;
+        }
+
+        let fallback = if supported.contains(&wgpu::CompositeAlphaMode::PreMultiplied) {
+            CompositeAlphaMode::PreMultiplied
+        } else {
+            CompositeAlphaMode::Auto
+        };
+        warn!(
+            "the window surface doesn't support {:?} alpha (only {:?}), using {:?}; \
+            captured frames are unaffected, but the window may look wrong on screen",
+            window.alpha_mode, supported, fallback,
+        );
+        window.alpha_mode = fallback;
+    }
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+struct StraightAlphaLabel;
+
+#[derive(Default)]
+struct StraightAlphaNode;
+
+impl ViewNode for StraightAlphaNode {
+    type ViewQuery = (&'static ViewTarget, &'static StraightAlpha);
+
+    fn run<'w>(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        (view_target, _): QueryItem<'w, Self::ViewQuery>,
+        world: &'w World,
+    ) -> Result<(), NodeRunError> {
+        let pipeline = world.resource::<StraightAlphaPipeline>();
+        let pipeline_id = if view_target.is_hdr() {
+            pipeline.hdr
+        } else {
+            pipeline.ldr
+        };
+        let Some(render_pipeline) = world.resource::<PipelineCache>().get_render_pipeline(pipeline_id) else {
+            return Ok(());
+        };
+
+        let post_process = view_target.post_process_write();
+        let bind_group = render_context.render_device().create_bind_group(
+            "straight_alpha_bind_group",
+            &pipeline.layout,
+            &BindGroupEntries::sequential((post_process.source, &pipeline.sampler)),
+        );
+
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("straight_alpha_pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: post_process.destination,
+                resolve_target: None,
+                ops: Operations::default(),
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_render_pipeline(render_pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+        Ok(())
+    }
+}
+
+#[derive(Resource)]
+struct StraightAlphaPipeline {
+    layout: BindGroupLayout,
+    sampler: Sampler,
+    ldr: CachedRenderPipelineId,
+    hdr: CachedRenderPipelineId,
+}
+
+impl FromWorld for StraightAlphaPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let layout = render_device.create_bind_group_layout(
+            "straight_alpha_bind_group_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::FRAGMENT,
+                (
+                    texture_2d(TextureSampleType::Float { filterable: true }),
+                    sampler(SamplerBindingType::Filtering),
+                ),
+            ),
+        );
+        let sampler = render_device.create_sampler(&SamplerDescriptor::default());
+        let shader = world.resource::<AssetServer>().load("shaders/straight_alpha.wgsl");
+
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let queue = |format: TextureFormat| {
+            pipeline_cache.queue_render_pipeline(RenderPipelineDescriptor {
+                label: Some("straight_alpha_pipeline".into()),
+                layout: vec![layout.clone()],
+                vertex: fullscreen_shader_vertex_state(),
+                fragment: Some(FragmentState {
+                    shader: shader.clone(),
+                    shader_defs: vec![],
+                    entry_point: "fragment".into(),
+                    targets: vec![Some(ColorTargetState {
+                        format,
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    })],
+                }),
+                primitive: PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: MultisampleState::default(),
+                push_constant_ranges: vec![],
+            })
+        };
+        let ldr = queue(TextureFormat::bevy_default());
+        let hdr = queue(ViewTarget::TEXTURE_FORMAT_HDR);
+
+        Self {
+            layout,
+            sampler,
+            ldr,
+            hdr,
+        }
+    }
+}
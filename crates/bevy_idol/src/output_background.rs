@@ -0,0 +1,84 @@
+use bevy::pbr::{NotShadowCaster, NotShadowReceiver};
+use bevy::prelude::*;
+use bevy::render::view::RenderLayers;
+
+use crate::cameras::OutputCamera;
+use crate::ToggleVisibilityKey;
+
+/// How far towards the output camera's far plane the background sits, so it
+/// is behind everything else without being clipped.
+const BACKGROUND_DEPTH: f32 = 0.99;
+
+/// A static image behind the avatar in the output, on a quad parented to the
+/// output camera which is sized to cover its view. Toggled with Insert, to
+/// capture with transparency instead.
+#[derive(Debug, Clone, Component, Reflect)]
+#[reflect(Component)]
+pub struct OutputBackground {
+    pub image: Handle<Image>,
+}
+
+pub fn spawn_output_background(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    camera: Entity,
+    image: Handle<Image>,
+) {
+    // The camera has no visibility of its own, which the quad needs to
+    // inherit.
+    commands.entity(camera).insert(VisibilityBundle::default());
+    commands.spawn((
+        Name::from("Output Background"),
+        PbrBundle {
+            mesh: meshes.add(Rectangle::new(1., 1.)),
+            material: materials.add(StandardMaterial {
+                base_color_texture: Some(image.clone()),
+                unlit: true,
+                fog_enabled: false,
+                ..default()
+            }),
+            ..default()
+        },
+        OutputBackground { image },
+        NotShadowCaster,
+        NotShadowReceiver,
+        RenderLayers::layer(2),
+        ToggleVisibilityKey(KeyCode::Insert),
+    )).set_parent(camera);
+}
+
+/// Keep the background covering the output camera's view, cropping the
+/// image rather than stretching it.
+pub fn fit_output_background(
+    images: Res<Assets<Image>>,
+    cameras: Query<&Projection, With<OutputCamera>>,
+    mut backgrounds: Query<(&Parent, &OutputBackground, &mut Transform)>,
+) {
+    for (parent, background, mut transform) in &mut backgrounds {
+        let Ok(projection) = cameras.get(parent.get()) else {
+            continue;
+        };
+
+        let (distance, view_size) = match projection {
+            Projection::Perspective(perspective) => {
+                let distance = perspective.far * BACKGROUND_DEPTH;
+                let height = 2. * distance * (perspective.fov / 2.).tan();
+                (distance, Vec2::new(height * perspective.aspect_ratio, height))
+            }
+            Projection::Orthographic(orthographic) => {
+                let distance = orthographic.near
+                    + (orthographic.far - orthographic.near) * BACKGROUND_DEPTH;
+                (distance, orthographic.area.size())
+            }
+        };
+        let image_size = images.get(&background.image)
+            .map(|image| image.size_f32())
+            .filter(|size| size.min_element() > 0.)
+            .unwrap_or(view_size);
+        let scale = (view_size / image_size).max_element();
+
+        transform.translation = Vec3::new(0., 0., -distance);
+        transform.scale = (image_size * scale).extend(1.);
+    }
+}
@@ -0,0 +1,78 @@
+use bevy::ecs::system::SystemParam;
+use bevy::hierarchy::HierarchyQueryExt;
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+
+use bevy_vrm::Vrm;
+use idol_api::ApiError;
+
+/// Names of avatar nodes (such as glasses or outfit parts) which are hidden.
+///
+/// This persists across avatar switches, so a part stays hidden in any
+/// avatar which has a node of the same name.
+#[derive(Debug, Clone, Default, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct HiddenParts {
+    pub names: HashSet<String>,
+}
+
+fn visibility(hidden: bool) -> Visibility {
+    if hidden {
+        Visibility::Hidden
+    } else {
+        Visibility::Inherited
+    }
+}
+
+#[derive(SystemParam)]
+pub struct PartParams<'w, 's> {
+    hidden: ResMut<'w, HiddenParts>,
+    avatars: Query<'w, 's, Entity, With<Handle<Vrm>>>,
+    children: Query<'w, 's, &'static Children>,
+    nodes: Query<'w, 's, (&'static Name, &'static mut Visibility)>,
+}
+
+impl<'w, 's> PartParams<'w, 's> {
+    /// Show or hide every node with the given name in the loaded avatars.
+    pub fn set_visible(&mut self, name: &str, visible: bool) -> Result<(), ApiError> {
+        let mut found = false;
+        for avatar in &self.avatars {
+            for entity in self.children.iter_descendants(avatar) {
+                let Ok((node_name, mut node_visibility)) = self.nodes.get_mut(entity) else {
+                    continue;
+                };
+                if node_name.as_str() == name {
+                    *node_visibility = visibility(!visible);
+                    found = true;
+                }
+            }
+        }
+
+        if !found {
+            return Err(ApiError::not_found(format!("no avatar node named {name}")));
+        }
+
+        if visible {
+            self.hidden.names.remove(name);
+        } else {
+            self.hidden.names.insert(name.to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Hide parts of newly spawned avatars.
+pub fn hide_new_parts(
+    hidden: Res<HiddenParts>,
+    mut nodes: Query<(&Name, &mut Visibility), Added<Name>>,
+) {
+    if hidden.names.is_empty() {
+        return;
+    }
+
+    for (name, mut node_visibility) in &mut nodes {
+        if hidden.names.contains(name.as_str()) {
+            *node_visibility = Visibility::Hidden;
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use anyhow::anyhow;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use bevy::asset::io::Reader;
+use bevy::prelude::*;
+use bevy::utils::ConditionalSendFuture;
+
+use crate::expressions::ExpressionWeights;
+
+/// The mouth shapes driven by phoneme timelines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum Viseme {
+    Aa,
+    Ih,
+    Ou,
+    Ee,
+    Oh,
+}
+
+impl Viseme {
+    pub const ALL: [Viseme; 5] = [Viseme::Aa, Viseme::Ih, Viseme::Ou, Viseme::Ee, Viseme::Oh];
+
+    /// The VRM expression preset name for this viseme.
+    pub fn name(self) -> &'static str {
+        match self {
+            Viseme::Aa => "aa",
+            Viseme::Ih => "ih",
+            Viseme::Ou => "ou",
+            Viseme::Ee => "ee",
+            Viseme::Oh => "oh",
+        }
+    }
+
+    /// Parse either a viseme name or an ARPAbet phoneme (as produced by most
+    /// forced aligners). Phonemes with no open mouth shape return `None`.
+    pub fn from_phoneme(phoneme: &str) -> Option<Viseme> {
+        let phoneme = phoneme.trim_end_matches(|c: char| c.is_ascii_digit());
+        match phoneme.to_ascii_lowercase().as_str() {
+            "aa" | "ae" | "ah" | "ay" | "aw" => Some(Viseme::Aa),
+            "ih" | "iy" | "y" => Some(Viseme::Ih),
+            "ou" | "uw" | "uh" | "w" => Some(Viseme::Ou),
+            "ee" | "eh" | "ey" | "er" => Some(Viseme::Ee),
+            "oh" | "ao" | "ow" | "oy" => Some(Viseme::Oh),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Reflect)]
+pub struct PhonemeSegment {
+    pub start: f32,
+    pub end: f32,
+    /// The viseme to show, or `None` for a closed mouth.
+    pub viseme: Option<Viseme>,
+}
+
+/// A timed sequence of mouth shapes.
+///
+/// The text format has one segment per line, `<start> <end> <phoneme>`, with
+/// times in seconds. Blank lines and lines starting with `#` are ignored.
+#[derive(Debug, Clone, Asset, Reflect)]
+pub struct PhonemeTimeline {
+    pub segments: Vec<PhonemeSegment>,
+}
+
+impl PhonemeTimeline {
+    pub fn parse(text: &str) -> anyhow::Result<PhonemeTimeline> {
+        let mut segments = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = line.split_whitespace();
+            let mut next = || parts.next()
+                .ok_or_else(|| anyhow!("line {}: expected <start> <end> <phoneme>", index + 1));
+            let start = next()?.parse::<f32>()?;
+            let end = next()?.parse::<f32>()?;
+            let phoneme = next()?;
+            if end < start {
+                return Err(anyhow!("line {}: segment ends before it starts", index + 1));
+            }
+
+            segments.push(PhonemeSegment {
+                start,
+                end,
+                viseme: Viseme::from_phoneme(phoneme),
+            });
+        }
+
+        Ok(PhonemeTimeline {
+            segments,
+        })
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.segments.iter().map(|s| s.end).fold(0., f32::max)
+    }
+
+    /// The weight of `viseme` at `time`, crossfading over `fade` seconds at
+    /// each segment boundary.
+    pub fn evaluate(&self, viseme: Viseme, time: f32, fade: f32) -> f32 {
+        let fade = fade.max(1e-3);
+        self.segments.iter()
+            .filter(|s| s.viseme == Some(viseme))
+            .map(|s| {
+                let fade_in = ((time - s.start) / fade + 0.5).clamp(0., 1.);
+                let fade_out = ((s.end - time) / fade + 0.5).clamp(0., 1.);
+                fade_in * fade_out
+            })
+            .sum::<f32>()
+            .min(1.)
+    }
+}
+
+#[derive(Default)]
+pub struct PhonemeTimelineLoader;
+
+impl AssetLoader for PhonemeTimelineLoader {
+    type Asset = PhonemeTimeline;
+    type Settings = ();
+    type Error = anyhow::Error;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a Self::Settings,
+        _load_context: &'a mut LoadContext,
+    ) -> impl ConditionalSendFuture<Output=Result<Self::Asset, Self::Error>> {
+        async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            PhonemeTimeline::parse(std::str::from_utf8(&bytes)?)
+        }
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["phonemes"]
+    }
+}
+
+/// The phoneme timeline which is currently playing, if any.
+#[derive(Debug, Default, Resource)]
+pub struct PhonemePlayback {
+    pub timeline: Option<Handle<PhonemeTimeline>>,
+    /// Position in the timeline, in seconds.
+    pub time: f32,
+    /// Crossfade duration between segments, in seconds.
+    pub fade: f32,
+}
+
+impl PhonemePlayback {
+    pub fn play(&mut self, timeline: Handle<PhonemeTimeline>, offset: f32) {
+        self.timeline = Some(timeline);
+        self.time = offset;
+    }
+
+    pub fn stop(&mut self) {
+        self.timeline = None;
+    }
+}
+
+fn is_mouth_shape(name: &str) -> bool {
+    name.starts_with("mouth") || name.starts_with("jaw")
+        || Viseme::ALL.iter().any(|v| v.name() == name)
+}
+
+/// While a timeline is playing, replace the tracked mouth with its visemes.
+pub fn apply_phoneme_timeline(
+    time: Res<Time>,
+    timelines: Res<Assets<PhonemeTimeline>>,
+    mut playback: ResMut<PhonemePlayback>,
+    mut expressions: ResMut<ExpressionWeights>,
+) {
+    let Some(handle) = playback.timeline.clone() else {
+        return;
+    };
+    // Hold at the start until the timeline has loaded.
+    let Some(timeline) = timelines.get(&handle) else {
+        return;
+    };
+
+    let t = playback.time;
+    if t > timeline.duration() + playback.fade {
+        playback.stop();
+        return;
+    }
+    playback.time += time.delta_seconds();
+
+    expressions.clear_matching(is_mouth_shape);
+    for viseme in Viseme::ALL {
+        expressions.set(viseme.name(), timeline.evaluate(viseme, t, playback.fade));
+    }
+}
+
@@ -0,0 +1,478 @@
+use bevy::prelude::*;
+use bevy::render::mesh::morph::MeshMorphWeights;
+
+use bevy_vrm::extensions::vrm::{Eye, Humanoid, HumanoidBone, LookAtTarget};
+
+use crate::tracking::Faces;
+
+/// A tiny xorshift PRNG, good enough for procedural animation.
+#[derive(Debug, Clone)]
+pub struct Rng(u32);
+
+impl Rng {
+    pub fn new(seed: u32) -> Rng {
+        // Xorshift gets stuck at zero.
+        Rng(seed.max(1))
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.0 = x;
+        x
+    }
+
+    /// A random value in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Rng::new(ProceduralSeed::from_time().0)
+    }
+}
+
+/// The seed behind all procedural randomness.
+///
+/// Procedural state is stepped in `FixedUpdate`, so with a fixed seed the
+/// same input (such as a replayed recording) gives identical motion.
+#[derive(Debug, Clone, Copy, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct ProceduralSeed(pub u32);
+
+impl ProceduralSeed {
+    pub fn from_time() -> ProceduralSeed {
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(1, |d| d.subsec_nanos());
+        ProceduralSeed(seed)
+    }
+
+    /// An independent seed for each source of randomness, so that no two
+    /// draw from the same sequence.
+    pub fn stream(&self, stream: u32) -> u32 {
+        let mut x = self.0 ^ stream.wrapping_mul(0x9e3779b9);
+        x = (x ^ (x >> 16)).wrapping_mul(0x85ebca6b);
+        x = (x ^ (x >> 13)).wrapping_mul(0xc2b2ae35);
+        x ^ (x >> 16)
+    }
+}
+
+const SACCADE_STREAM: u32 = 0;
+const IDLE_SWAY_STREAM: u32 = 1;
+
+impl Default for ProceduralSeed {
+    fn default() -> Self {
+        ProceduralSeed::from_time()
+    }
+}
+
+/// Smooth 1D value noise in `[-1, 1]`.
+pub fn noise(x: f32, seed: u32) -> f32 {
+    fn lattice(i: i32, seed: u32) -> f32 {
+        let mut h = (i as u32).wrapping_mul(0x27d4eb2d) ^ seed.wrapping_mul(0x165667b1);
+        h ^= h >> 15;
+        h = h.wrapping_mul(0x85ebca6b);
+        h ^= h >> 13;
+        (h >> 8) as f32 / (1u32 << 23) as f32 - 1.
+    }
+
+    let i = x.floor();
+    let t = x - i;
+    let t = t * t * (3. - 2. * t);
+    let a = lattice(i as i32, seed);
+    let b = lattice(i as i32 + 1, seed);
+    a + (b - a) * t
+}
+
+/// Procedural animation layered on top of a humanoid bone's pose.
+///
+/// Procedural systems accumulate rotations into `pending` during `Update`.
+/// They are applied in `PostUpdate` and removed again in the next `PreUpdate`,
+/// so anything else driving the bone always sees the un-animated pose.
+/// Removing them restores the rotation from before they were applied, which
+/// also drops anything layered after them, such as bone limits, so that it
+/// doesn't build up over frames.
+///
+/// The state behind each layer is stepped in `FixedUpdate`, which may run any
+/// number of times per frame, so only the `Update` systems touch `pending`.
+#[derive(Debug, Clone, Component, Reflect)]
+#[reflect(Component)]
+pub struct ProceduralPose {
+    /// The rotation to apply this frame, in the parent's space.
+    pub pending: Quat,
+    /// Smoothed angular speed of the underlying pose, in radians per second.
+    pub motion: f32,
+    base: Option<Quat>,
+    last_base: Option<Quat>,
+}
+
+impl Default for ProceduralPose {
+    fn default() -> Self {
+        ProceduralPose {
+            pending: Quat::IDENTITY,
+            motion: 0.,
+            base: None,
+            last_base: None,
+        }
+    }
+}
+
+// How quickly `ProceduralPose::motion` follows the underlying pose, per second.
+const MOTION_SMOOTHING: f32 = 5.;
+
+// How quickly procedural layers fade in and out, per second.
+const FADE_SPEED: f32 = 2.;
+
+/// The fastest tracked motion of any of `bones` on any humanoid, in radians
+/// per second.
+fn tracked_motion(
+    humanoids: &Query<&Humanoid>,
+    bones: &[HumanoidBone],
+    poses: &Query<&ProceduralPose>,
+) -> f32 {
+    humanoids.iter()
+        .flat_map(|humanoid| bones.iter().filter_map(|bone| humanoid.bones.get(bone)))
+        .filter_map(|entity| poses.get(*entity).ok())
+        .map(|pose| pose.motion)
+        .fold(0., f32::max)
+}
+
+/// The weight a procedural layer should fade towards, given how much the
+/// bones it drives are being moved by tracking.
+fn fade_target(enabled: bool, motion: f32, fade_motion_degrees: f32) -> f32 {
+    if enabled && fade_motion_degrees > 0. {
+        1. - (motion / fade_motion_degrees.to_radians()).clamp(0., 1.)
+    } else {
+        0.
+    }
+}
+
+fn add_rotation(humanoid: &Humanoid, bone: HumanoidBone, poses: &mut Query<&mut ProceduralPose>, rotation: Quat) {
+    if let Some(mut pose) = humanoid.bones.get(&bone)
+        .and_then(|entity| poses.get_mut(*entity).ok()) {
+        pose.pending = rotation * pose.pending;
+    }
+}
+
+pub fn add_procedural_poses(
+    mut commands: Commands,
+    humanoids: Query<&Humanoid, Added<Humanoid>>,
+) {
+    for humanoid in &humanoids {
+        for entity in humanoid.bones.values() {
+            commands.entity(*entity).insert(ProceduralPose::default());
+        }
+    }
+}
+
+pub fn restore_procedural_poses(
+    time: Res<Time>,
+    mut bones: Query<(&mut Transform, &mut ProceduralPose)>,
+) {
+    let dt = time.delta_seconds();
+    for (mut transform, mut pose) in &mut bones {
+        if let Some(base) = pose.base.take() {
+            transform.rotation = base;
+        }
+        pose.pending = Quat::IDENTITY;
+
+        let base = transform.rotation;
+        if let (Some(last_base), true) = (pose.last_base, dt > 0.) {
+            let speed = last_base.angle_between(base) / dt;
+            pose.motion += (speed - pose.motion) * (1. - (-MOTION_SMOOTHING * dt).exp());
+        }
+        pose.last_base = Some(base);
+    }
+}
+
+pub fn apply_procedural_poses(
+    mut bones: Query<(&mut Transform, &mut ProceduralPose)>,
+) {
+    for (mut transform, mut pose) in &mut bones {
+        if pose.pending == Quat::IDENTITY {
+            continue;
+        }
+
+        pose.base = Some(transform.rotation);
+        transform.rotation = pose.pending * transform.rotation;
+    }
+}
+
+/// Small, quick, random eye movements layered under the look-at target.
+#[derive(Debug, Clone, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct Saccades {
+    pub enabled: bool,
+    /// Maximum angle of a saccade, in degrees.
+    pub amplitude: f32,
+    /// Average number of saccades per second.
+    pub frequency: f32,
+}
+
+impl Default for Saccades {
+    fn default() -> Self {
+        Saccades {
+            enabled: false,
+            amplitude: 1.5,
+            frequency: 0.5,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct SaccadeState {
+    rng: Rng,
+    next_saccade: f32,
+    target: Vec2,
+    current: Vec2,
+}
+
+impl FromWorld for SaccadeState {
+    fn from_world(world: &mut World) -> Self {
+        let seed = world.get_resource_or_insert_with(ProceduralSeed::default).stream(SACCADE_STREAM);
+        SaccadeState {
+            rng: Rng::new(seed),
+            next_saccade: 0.,
+            target: Vec2::ZERO,
+            current: Vec2::ZERO,
+        }
+    }
+}
+
+// Weight above which an eyeLook* blend shape counts as tracked gaze.
+const GAZE_THRESHOLD: f32 = 0.1;
+// How quickly the eyes move towards the next fixation, per second.
+const SACCADE_SPEED: f32 = 40.;
+
+fn gaze_tracked(faces: &Faces) -> bool {
+    faces.faces.first().map_or(false, |face| face.blend_shapes.iter()
+        .any(|(name, weight)| name.starts_with("eyeLook") && *weight > GAZE_THRESHOLD))
+}
+
+pub fn update_saccades(
+    time: Res<Time>,
+    settings: Res<Saccades>,
+    faces: Res<Faces>,
+    mut state: ResMut<SaccadeState>,
+    eyes: Query<(&GlobalTransform, &LookAtTarget), With<Eye>>,
+    mut targets: Query<(&mut Transform, &Parent)>,
+    parents: Query<&GlobalTransform>,
+) {
+    let state = &mut *state;
+    let now = time.elapsed_seconds();
+    if !settings.enabled || settings.frequency <= 0. || gaze_tracked(&faces) {
+        // Ease back to the look target, so disabling never snaps the eyes.
+        state.target = Vec2::ZERO;
+    } else if now >= state.next_saccade {
+        let angle = state.rng.range(0., std::f32::consts::TAU);
+        let magnitude = settings.amplitude.to_radians() * state.rng.next_f32().sqrt();
+        state.target = Vec2::from_angle(angle) * magnitude;
+        state.next_saccade = now + state.rng.range(0.5, 1.5) / settings.frequency;
+    }
+
+    let t = 1. - (-SACCADE_SPEED * time.delta_seconds()).exp();
+    state.current = state.current.lerp(state.target, t);
+
+    for (eye_transform, look_at_target) in &eyes {
+        let Ok((mut transform, parent)) = targets.get_mut(look_at_target.0) else {
+            continue;
+        };
+        let Ok(parent_transform) = parents.get(parent.get()) else {
+            continue;
+        };
+
+        let distance = eye_transform.translation().distance(parent_transform.translation());
+        let offset = Vec2::new(state.current.x.tan(), state.current.y.tan()) * distance;
+        transform.translation = offset.extend(0.);
+    }
+}
+
+/// Gentle procedural breathing, rotating the spine and optionally driving a
+/// breath morph target.
+#[derive(Debug, Clone, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct Breathing {
+    pub enabled: bool,
+    /// Breaths per minute.
+    pub rate: f32,
+    /// Total rotation of the spine at the peak of a breath, in degrees.
+    pub depth: f32,
+    /// Name of a morph target to drive with the breath, if not empty.
+    pub morph: String,
+    /// Tracked spine motion, in degrees per second, at which breathing has
+    /// completely faded out.
+    pub fade_motion: f32,
+}
+
+impl Default for Breathing {
+    fn default() -> Self {
+        Breathing {
+            enabled: false,
+            rate: 14.,
+            depth: 1.,
+            morph: String::new(),
+            fade_motion: 30.,
+        }
+    }
+}
+
+#[derive(Default, Resource)]
+pub struct BreathingState {
+    phase: f32,
+    weight: f32,
+    /// The current breath, from 0 (exhaled) to 1 (inhaled), after fading.
+    pub breath: f32,
+}
+
+// How the breath is shared out along the spine.
+const BREATHING_BONES: [(HumanoidBone, f32); 3] = [
+    (HumanoidBone::Spine, 0.2),
+    (HumanoidBone::Chest, 0.35),
+    (HumanoidBone::UpperChest, 0.45),
+];
+
+/// Step the breathing cycle. This runs in `FixedUpdate`.
+pub fn update_breathing(
+    time: Res<Time>,
+    settings: Res<Breathing>,
+    mut state: ResMut<BreathingState>,
+    humanoids: Query<&Humanoid>,
+    poses: Query<&ProceduralPose>,
+) {
+    let dt = time.delta_seconds();
+    state.phase = (state.phase + dt * settings.rate / 60.).fract();
+
+    let motion = tracked_motion(&humanoids, &BREATHING_BONES.map(|(bone, _)| bone), &poses);
+    let target_weight = fade_target(settings.enabled, motion, settings.fade_motion);
+    state.weight += (target_weight - state.weight) * (1. - (-FADE_SPEED * dt).exp());
+    state.breath = (1. - (std::f32::consts::TAU * state.phase).cos()) * 0.5 * state.weight;
+}
+
+pub fn apply_breathing(
+    settings: Res<Breathing>,
+    state: Res<BreathingState>,
+    humanoids: Query<&Humanoid>,
+    mut poses: Query<&mut ProceduralPose>,
+) {
+    let angle = state.breath * settings.depth.to_radians();
+    for humanoid in &humanoids {
+        for (bone, share) in BREATHING_BONES {
+            // Lean back slightly when breathing in.
+            add_rotation(humanoid, bone, &mut poses, Quat::from_rotation_x(-angle * share));
+        }
+    }
+}
+
+pub fn apply_breath_morph(
+    settings: Res<Breathing>,
+    state: Res<BreathingState>,
+    meshes: Res<Assets<Mesh>>,
+    mut entities: Query<(&Handle<Mesh>, &mut MeshMorphWeights)>,
+) {
+    if settings.morph.is_empty() {
+        return;
+    }
+
+    for (mesh, mut weights) in &mut entities {
+        let Some(index) = meshes.get(mesh)
+            .and_then(|mesh| mesh.morph_target_names())
+            .and_then(|names| names.iter().position(|name| *name == settings.morph)) else {
+            continue;
+        };
+
+        if let Some(weight) = weights.weights_mut().get_mut(index) {
+            *weight = (*weight + state.breath).min(1.);
+        }
+    }
+}
+
+/// A slow, noise-driven idle sway of the hips and spine.
+#[derive(Debug, Clone, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct IdleSway {
+    pub enabled: bool,
+    /// Maximum hip rotation, in degrees.
+    pub amplitude: f32,
+    /// Speed of the sway, roughly in cycles per second.
+    pub speed: f32,
+    /// Tracked hip and spine motion, in degrees per second, at which the sway
+    /// has completely faded out.
+    pub fade_motion: f32,
+}
+
+impl Default for IdleSway {
+    fn default() -> Self {
+        IdleSway {
+            enabled: false,
+            amplitude: 2.,
+            speed: 0.1,
+            fade_motion: 30.,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct IdleSwayState {
+    seed: u32,
+    time: f32,
+    weight: f32,
+}
+
+impl FromWorld for IdleSwayState {
+    fn from_world(world: &mut World) -> Self {
+        let seed = world.get_resource_or_insert_with(ProceduralSeed::default).stream(IDLE_SWAY_STREAM);
+        IdleSwayState {
+            seed,
+            time: 0.,
+            weight: 0.,
+        }
+    }
+}
+
+const IDLE_SWAY_BONES: [HumanoidBone; 2] = [HumanoidBone::Hips, HumanoidBone::Spine];
+
+/// Step the idle sway. This runs in `FixedUpdate`.
+pub fn update_idle_sway(
+    time: Res<Time>,
+    settings: Res<IdleSway>,
+    mut state: ResMut<IdleSwayState>,
+    humanoids: Query<&Humanoid>,
+    poses: Query<&ProceduralPose>,
+) {
+    let dt = time.delta_seconds();
+    state.time += dt * settings.speed;
+
+    let motion = tracked_motion(&humanoids, &IDLE_SWAY_BONES, &poses);
+    let target_weight = fade_target(settings.enabled, motion, settings.fade_motion);
+    state.weight += (target_weight - state.weight) * (1. - (-FADE_SPEED * dt).exp());
+}
+
+pub fn apply_idle_sway(
+    settings: Res<IdleSway>,
+    state: Res<IdleSwayState>,
+    humanoids: Query<&Humanoid>,
+    mut poses: Query<&mut ProceduralPose>,
+) {
+    for humanoid in &humanoids {
+        let amplitude = settings.amplitude.to_radians() * state.weight;
+        let shift = noise(state.time, state.seed) * amplitude;
+        let turn = noise(state.time, state.seed.wrapping_add(1)) * amplitude * 0.5;
+        let lean = noise(state.time, state.seed.wrapping_add(2)) * amplitude * 0.3;
+
+        // Shift weight by rolling the hips, and counter-roll the spine so
+        // the head stays roughly upright.
+        add_rotation(humanoid, HumanoidBone::Hips, &mut poses,
+            Quat::from_rotation_z(shift) * Quat::from_rotation_y(turn));
+        add_rotation(humanoid, HumanoidBone::Spine, &mut poses,
+            Quat::from_rotation_z(-shift * 0.6) * Quat::from_rotation_x(lean));
+    }
+}
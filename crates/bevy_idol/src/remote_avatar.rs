@@ -0,0 +1,83 @@
+use std::io::Read;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+
+/// The asset source which downloaded avatars are loaded from.
+pub const AVATAR_CACHE_SOURCE: &str = "avatar-cache";
+
+/// How long to wait for the server to accept the connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long the response may stall between reads before giving up.
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The largest avatar which will be downloaded. Even detailed VRMs are well
+/// under this.
+const MAX_AVATAR_SIZE: u64 = 256 * 1024 * 1024;
+
+pub fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+/// The name of a URL's file in the cache. This must be stable across Rust
+/// versions and runs, so it uses BLAKE3 rather than the std hasher.
+fn cache_file_name(url: &str) -> String {
+    format!("{}.vrm", blake3::hash(url.as_bytes()).to_hex())
+}
+
+fn download(url: &str) -> anyhow::Result<Vec<u8>> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout_connect(CONNECT_TIMEOUT)
+        .timeout_read(READ_TIMEOUT)
+        .build();
+
+    // ureq follows redirects by default.
+    let response = match agent.get(url).call() {
+        Ok(response) => response,
+        Err(ureq::Error::Status(code, response)) =>
+            return Err(anyhow!("server returned {} {}", code, response.status_text())),
+        Err(err) => return Err(err.into()),
+    };
+
+    let too_large = || anyhow!("avatar is larger than {} MiB", MAX_AVATAR_SIZE / (1024 * 1024));
+    let length = response.header("Content-Length").and_then(|length| length.parse::<u64>().ok());
+    if length.is_some_and(|length| length > MAX_AVATAR_SIZE) {
+        return Err(too_large());
+    }
+
+    // The length may be missing or wrong, so stop reading just past the limit
+    // either way.
+    let mut bytes = Vec::new();
+    response.into_reader().take(MAX_AVATAR_SIZE + 1).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 > MAX_AVATAR_SIZE {
+        return Err(too_large());
+    }
+    Ok(bytes)
+}
+
+/// Download the avatar at `url` into `cache_dir`, returning the file name it
+/// was stored under.
+///
+/// Downloads are keyed on the URL, so a cached copy is reused unless `refresh`
+/// is set.
+pub fn fetch_avatar(url: &str, cache_dir: &Path, refresh: bool) -> anyhow::Result<String> {
+    let file_name = cache_file_name(url);
+    let path = cache_dir.join(&file_name);
+    if path.exists() && !refresh {
+        return Ok(file_name);
+    }
+
+    std::fs::create_dir_all(cache_dir)
+        .with_context(|| format!("failed to create avatar cache {}", cache_dir.display()))?;
+    let bytes = download(url)
+        .with_context(|| format!("failed to download avatar from {url}"))?;
+
+    // Write to a temporary file first so that an interrupted download never
+    // leaves a truncated avatar in the cache.
+    let temp_path = path.with_extension("vrm.part");
+    std::fs::write(&temp_path, bytes)?;
+    std::fs::rename(&temp_path, &path)?;
+    Ok(file_name)
+}
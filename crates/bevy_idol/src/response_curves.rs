@@ -0,0 +1,89 @@
+use std::path::Path;
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::expressions::ExpressionWeights;
+use crate::tracking::Faces;
+
+/// How a tracked weight maps to the weight applied to the avatar.
+#[derive(Debug, Clone, Serialize, Deserialize, Reflect)]
+#[serde(rename_all = "camelCase")]
+pub enum ResponseCurve {
+    /// `weight^gamma`: below 1 makes the expression more pronounced, above 1
+    /// makes it subtler.
+    Gamma(f32),
+    /// Linear interpolation between `[input, output]` points, sorted by
+    /// input. Inputs outside the points are clamped to the end points.
+    Points(Vec<[f32; 2]>),
+}
+
+impl ResponseCurve {
+    pub fn apply(&self, weight: f32) -> f32 {
+        match self {
+            ResponseCurve::Gamma(gamma) => weight.max(0.).powf(*gamma),
+            ResponseCurve::Points(points) => {
+                let Some((first, last)) = points.first().zip(points.last()) else {
+                    return weight;
+                };
+                if weight <= first[0] {
+                    return first[1];
+                }
+                for pair in points.windows(2) {
+                    let ([x0, y0], [x1, y1]) = (pair[0], pair[1]);
+                    if weight <= x1 {
+                        let t = if x1 > x0 { (weight - x0) / (x1 - x0) } else { 1. };
+                        return y0 + (y1 - y0) * t;
+                    }
+                }
+                last[1]
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ResponseCurvesConfig {
+    curves: HashMap<String, ResponseCurve>,
+}
+
+/// Response curves for tracked expressions, by name. Expressions without a
+/// curve are linear.
+#[derive(Debug, Clone, Default, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct ResponseCurves {
+    pub curves: HashMap<String, ResponseCurve>,
+}
+
+impl ResponseCurves {
+    /// Load curves from a JSON file of the form
+    /// `{"curves": {"mouthSmileLeft": {"gamma": 0.7}}}`.
+    pub fn from_file(path: &Path) -> anyhow::Result<ResponseCurves> {
+        let contents = std::fs::read(path)?;
+        let config: ResponseCurvesConfig = serde_json::from_slice(&contents)?;
+        Ok(ResponseCurves {
+            curves: config.curves,
+        })
+    }
+}
+
+/// Apply the response curves to the tracked (and smoothed) weights, before
+/// any other layers.
+pub fn apply_response_curves(
+    faces: Res<Faces>,
+    curves: Res<ResponseCurves>,
+    mut expressions: ResMut<ExpressionWeights>,
+) {
+    if faces.faces.is_empty() {
+        return;
+    }
+
+    for (name, curve) in &curves.curves {
+        if expressions.index_of(name).is_some() {
+            let weight = expressions.get(name);
+            expressions.set(name, curve.apply(weight).clamp(0., 1.));
+        }
+    }
+}
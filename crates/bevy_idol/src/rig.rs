@@ -0,0 +1,88 @@
+use bevy::ecs::system::SystemParam;
+use bevy::hierarchy::HierarchyQueryExt;
+use bevy::prelude::*;
+
+use bevy_vrm::extensions::vrm::{Humanoid, HumanoidBone};
+use idol_api::{ApiError, GetRigResponse, RigAvatar, RigBone, RigTransform};
+
+fn rig_transform(transform: &Transform) -> RigTransform {
+    RigTransform {
+        translation: transform.translation,
+        rotation: transform.rotation,
+        scale: transform.scale,
+    }
+}
+
+fn bone_name(bone: HumanoidBone) -> String {
+    match serde_json::to_value(bone) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => format!("{bone:?}"),
+    }
+}
+
+/// Read-only access to the humanoid rigs, for tooling.
+#[derive(SystemParam)]
+pub struct RigQuery<'w, 's> {
+    humanoids: Query<'w, 's, (Entity, &'static Humanoid, Option<&'static Name>)>,
+    bones: Query<'w, 's, (&'static mut Transform, &'static GlobalTransform, Option<&'static Name>)>,
+    parents: Query<'w, 's, &'static Parent>,
+}
+
+impl<'w, 's> RigQuery<'w, 's> {
+    pub fn snapshot(&self) -> GetRigResponse {
+        let avatars = self.humanoids.iter()
+            .map(|(entity, humanoid, name)| {
+                let mut bones: Vec<_> = humanoid.bones.iter()
+                    .filter_map(|(bone, bone_entity)| {
+                        let (local, global, name) = self.bones.get(*bone_entity).ok()?;
+                        let parent = self.parents.iter_ancestors(*bone_entity)
+                            .find_map(|ancestor| humanoid.bones.iter()
+                                .find(|(_, e)| **e == ancestor)
+                                .map(|(parent, _)| bone_name(*parent)));
+                        Some((*bone, RigBone {
+                            bone: bone_name(*bone),
+                            entity: bone_entity.to_bits(),
+                            name: name.map(|n| n.to_string()),
+                            parent,
+                            local: rig_transform(local),
+                            global: rig_transform(&global.compute_transform()),
+                        }))
+                    })
+                    .collect();
+                bones.sort_by_key(|(bone, _)| *bone);
+
+                RigAvatar {
+                    entity: entity.to_bits(),
+                    name: name.map(|n| n.to_string()),
+                    bones: bones.into_iter().map(|(_, bone)| bone).collect(),
+                }
+            })
+            .collect();
+
+        GetRigResponse {
+            avatars,
+        }
+    }
+
+    /// Set the local rotation of a bone in every avatar which has it.
+    ///
+    /// The rotation is clamped by the avatar's bone limits along with the
+    /// rest of the pose.
+    pub fn set_rotation(&mut self, bone: HumanoidBone, rotation: Quat) -> Result<(), ApiError> {
+        let mut found = false;
+        for (_, humanoid, _) in &self.humanoids {
+            let Some((mut transform, _, _)) = humanoid.bones.get(&bone)
+                .and_then(|e| self.bones.get_mut(*e).ok()) else {
+                continue;
+            };
+            transform.rotation = rotation.normalize();
+            found = true;
+        }
+
+        if found {
+            Ok(())
+        } else {
+            Err(ApiError::not_found(format!("no avatar has a {} bone", bone_name(bone))))
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use bevy::prelude::*;
+
+// Reloads every shader which was loaded from the assets directory.
+const RELOAD_SHADERS_KEY: KeyCode = KeyCode::F5;
+
+/// Reload shaders (such as MToon) from disk, without restarting.
+///
+/// Pipelines using a reloaded shader, or a shader which imports it, are
+/// rebuilt by Bevy's pipeline cache.
+pub fn reload_shaders_hotkey(
+    keys: Res<ButtonInput<KeyCode>>,
+    assets: Res<AssetServer>,
+    shaders: Res<Assets<Shader>>,
+) {
+    if !keys.just_pressed(RELOAD_SHADERS_KEY) {
+        return;
+    }
+
+    // Shaders built into Bevy have no path, so only ours are reloaded.
+    let paths: Vec<_> = shaders.ids()
+        .filter_map(|id| assets.get_path(id))
+        .map(|path| path.into_owned())
+        .collect();
+    info!("reloading {} shaders", paths.len());
+    for path in paths {
+        assets.reload(path);
+    }
+}
+
+pub fn log_shader_reloads(
+    mut events: EventReader<AssetEvent<Shader>>,
+    assets: Res<AssetServer>,
+) {
+    for event in events.read() {
+        if let AssetEvent::Modified { id } = event {
+            if let Some(path) = assets.get_path(*id) {
+                info!("reloaded shader {path}");
+            }
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use idol_api::{SetSmoothingRequest, SmoothingGroup, SmoothingPreset};
+
+use crate::expressions::ExpressionWeights;
+use crate::tracking::Faces;
+
+/// One Euro filter parameters for a group of tracked channels.
+///
+/// The filter is a low pass whose cutoff rises with speed, so slow movements
+/// are smoothed heavily while fast ones stay responsive. The output can then
+/// be extrapolated along the filtered velocity to claw back some latency, at
+/// the cost of overshoot.
+#[derive(Debug, Clone, Reflect)]
+pub struct SmoothingParams {
+    pub enabled: bool,
+    /// Cutoff frequency when still, in Hz.
+    pub min_cutoff: f32,
+    /// How much the cutoff rises with speed.
+    pub beta: f32,
+    /// Cutoff frequency of the velocity estimate, in Hz.
+    pub derivative_cutoff: f32,
+    /// How far ahead to extrapolate, in seconds.
+    pub extrapolation: f32,
+}
+
+impl SmoothingParams {
+    /// The parameters of a preset. The added latency when still is roughly
+    /// `1 / (2π min_cutoff)`, minus the extrapolation:
+    /// - `responsive`: ~35ms, for reaction streams.
+    /// - `balanced`: ~105ms.
+    /// - `smooth`: ~320ms, for cinematic shots.
+    pub fn from_preset(preset: SmoothingPreset) -> SmoothingParams {
+        let (enabled, min_cutoff, beta, extrapolation) = match preset {
+            SmoothingPreset::Off => (false, 1., 0., 0.),
+            SmoothingPreset::Responsive => (true, 3., 1., 0.02),
+            SmoothingPreset::Balanced => (true, 1.5, 0.5, 0.),
+            SmoothingPreset::Smooth => (true, 0.5, 0.1, 0.),
+        };
+        SmoothingParams {
+            enabled,
+            min_cutoff,
+            beta,
+            derivative_cutoff: 1.,
+            extrapolation,
+        }
+    }
+
+    /// Apply a preset, if any, and then any individual overrides.
+    pub fn apply(&mut self, request: &SetSmoothingRequest) {
+        if let Some(preset) = request.preset {
+            *self = SmoothingParams::from_preset(preset);
+        }
+        if let Some(min_cutoff) = request.min_cutoff {
+            self.min_cutoff = min_cutoff;
+        }
+        if let Some(beta) = request.beta {
+            self.beta = beta;
+        }
+        if let Some(extrapolation) = request.extrapolation {
+            self.extrapolation = extrapolation;
+        }
+    }
+}
+
+/// Smoothing of tracking, configured separately for each
+/// [`SmoothingGroup`], so speech can stay snappy while head motion is
+/// steadied.
+#[derive(Debug, Clone, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct Smoothing {
+    pub expressions: SmoothingParams,
+    pub head: SmoothingParams,
+    pub look_at: SmoothingParams,
+}
+
+impl Smoothing {
+    /// The default preset of each group: light on expressions and gaze,
+    /// heavier on the head.
+    pub fn default_preset(group: SmoothingGroup) -> SmoothingPreset {
+        match group {
+            SmoothingGroup::Expressions => SmoothingPreset::Responsive,
+            SmoothingGroup::Head => SmoothingPreset::Balanced,
+            SmoothingGroup::LookAt => SmoothingPreset::Responsive,
+        }
+    }
+
+    pub fn group_mut(&mut self, group: SmoothingGroup) -> &mut SmoothingParams {
+        match group {
+            SmoothingGroup::Expressions => &mut self.expressions,
+            SmoothingGroup::Head => &mut self.head,
+            SmoothingGroup::LookAt => &mut self.look_at,
+        }
+    }
+
+    /// Apply a request to its group, or to every group if it has none.
+    pub fn apply(&mut self, request: &SetSmoothingRequest) {
+        match request.group {
+            Some(group) => self.group_mut(group).apply(request),
+            None => {
+                for group in [SmoothingGroup::Expressions, SmoothingGroup::Head, SmoothingGroup::LookAt] {
+                    self.group_mut(group).apply(request);
+                }
+            }
+        }
+    }
+
+    fn for_blend_shape(&self, name: &str) -> &SmoothingParams {
+        if name.starts_with("eyeLook") {
+            &self.look_at
+        } else {
+            &self.expressions
+        }
+    }
+}
+
+impl Default for Smoothing {
+    fn default() -> Self {
+        Smoothing {
+            expressions: SmoothingParams::from_preset(Smoothing::default_preset(SmoothingGroup::Expressions)),
+            head: SmoothingParams::from_preset(Smoothing::default_preset(SmoothingGroup::Head)),
+            look_at: SmoothingParams::from_preset(Smoothing::default_preset(SmoothingGroup::LookAt)),
+        }
+    }
+}
+
+fn smoothing_factor(cutoff: f32, dt: f32) -> f32 {
+    let tau = 1. / (TAU * cutoff.max(f32::EPSILON));
+    1. / (1. + tau / dt)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct OneEuroFilter {
+    value: f32,
+    velocity: f32,
+}
+
+impl OneEuroFilter {
+    fn new(value: f32) -> OneEuroFilter {
+        OneEuroFilter {
+            value,
+            velocity: 0.,
+        }
+    }
+
+    fn filter(&mut self, value: f32, dt: f32, settings: &SmoothingParams) -> f32 {
+        let velocity = (value - self.value) / dt;
+        self.velocity += (velocity - self.velocity) * smoothing_factor(settings.derivative_cutoff, dt);
+        let cutoff = settings.min_cutoff + settings.beta * self.velocity.abs();
+        self.value += (value - self.value) * smoothing_factor(cutoff, dt);
+        self.value + self.velocity * settings.extrapolation
+    }
+}
+
+/// The filters for one face transform. Rotation is filtered per quaternion
+/// component and renormalised, which is close enough for the small steps
+/// between frames.
+#[derive(Debug, Clone, Copy)]
+struct TransformFilter {
+    translation: [OneEuroFilter; 3],
+    rotation: [OneEuroFilter; 4],
+}
+
+impl TransformFilter {
+    fn new(transform: &Transform) -> TransformFilter {
+        TransformFilter {
+            translation: transform.translation.to_array().map(OneEuroFilter::new),
+            rotation: transform.rotation.to_array().map(OneEuroFilter::new),
+        }
+    }
+
+    fn filter(&mut self, transform: &mut Transform, dt: f32, settings: &SmoothingParams) {
+        let translation = transform.translation.to_array();
+        transform.translation = Vec3::from_array(std::array::from_fn(|i|
+            self.translation[i].filter(translation[i], dt, settings)));
+
+        // Stay in the same hemisphere as the filtered rotation, so it doesn't
+        // swing through the long way round when the tracker flips sign.
+        let previous = Quat::from_array(self.rotation.map(|filter| filter.value));
+        let mut rotation = transform.rotation;
+        if previous.dot(rotation) < 0. {
+            rotation = -rotation;
+        }
+        let rotation = rotation.to_array();
+        transform.rotation = Quat::from_array(std::array::from_fn(|i|
+            self.rotation[i].filter(rotation[i], dt, settings))).normalize();
+    }
+}
+
+/// Smooth the tracked face transform in place, as each face frame arrives.
+pub fn smooth_face_transforms(
+    time: Res<Time>,
+    settings: Res<Smoothing>,
+    mut faces: ResMut<Faces>,
+    mut last_frame: Local<f32>,
+    mut filter: Local<Option<TransformFilter>>,
+) {
+    if !faces.is_changed() {
+        return;
+    }
+
+    let now = time.elapsed_seconds();
+    let dt = now - std::mem::replace(&mut *last_frame, now);
+    let Some(face) = faces.faces.first_mut().filter(|_| settings.head.enabled && dt > 0.) else {
+        *filter = None;
+        return;
+    };
+
+    match filter.as_mut() {
+        Some(filter) => filter.filter(&mut face.transform, dt, &settings.head),
+        None => *filter = Some(TransformFilter::new(&face.transform)),
+    }
+}
+
+/// Smooth the tracked weights in place. This runs in `FixedUpdate`, after
+/// the tracked weights are collected.
+pub fn smooth_tracked_expressions(
+    time: Res<Time>,
+    settings: Res<Smoothing>,
+    faces: Res<Faces>,
+    mut filters: Local<HashMap<String, OneEuroFilter>>,
+    mut expressions: ResMut<ExpressionWeights>,
+) {
+    let dt = time.delta_seconds();
+    let Some(face) = faces.faces.first().filter(|_| dt > 0.) else {
+        filters.clear();
+        return;
+    };
+
+    let tracked = &face.blend_shapes;
+    filters.retain(|name, _| tracked.contains_key(name));
+    for name in tracked.keys() {
+        let params = settings.for_blend_shape(name);
+        if !params.enabled {
+            filters.remove(name);
+            continue;
+        }
+        let Some(index) = expressions.index_of(name) else {
+            continue;
+        };
+        let value = expressions.get_index(index);
+        let smoothed = match filters.get_mut(name) {
+            Some(filter) => filter.filter(value, dt, params),
+            None => {
+                filters.insert(name.clone(), OneEuroFilter::new(value));
+                value
+            }
+        };
+        expressions.set(name, smoothed.clamp(0., 1.));
+    }
+}
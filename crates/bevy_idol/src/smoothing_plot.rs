@@ -0,0 +1,220 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use bevy::prelude::*;
+use bevy_egui::egui::{self, Color32, Stroke};
+use bevy_egui::EguiContexts;
+
+use crate::expressions::ExpressionWeights;
+use crate::tracking::{Face, Faces};
+
+const INCOMING_COLOR: Color32 = Color32::GRAY;
+const APPLIED_COLOR: Color32 = Color32::YELLOW;
+
+/// A tracked channel which can be plotted.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlotChannel {
+    HeadYaw,
+    HeadPitch,
+    HeadRoll,
+    HeadX,
+    HeadY,
+    HeadZ,
+    BlendShape(String),
+}
+
+impl PlotChannel {
+    const HEAD: [PlotChannel; 6] = [
+        PlotChannel::HeadYaw,
+        PlotChannel::HeadPitch,
+        PlotChannel::HeadRoll,
+        PlotChannel::HeadX,
+        PlotChannel::HeadY,
+        PlotChannel::HeadZ,
+    ];
+
+    /// The channel's value in a face, with angles in degrees and
+    /// translation in metres.
+    fn face_value(&self, face: &Face) -> Option<f32> {
+        let transform = &face.transform;
+        let (yaw, pitch, roll) = transform.rotation.to_euler(EulerRot::YXZ);
+        Some(match self {
+            PlotChannel::HeadYaw => yaw.to_degrees(),
+            PlotChannel::HeadPitch => pitch.to_degrees(),
+            PlotChannel::HeadRoll => roll.to_degrees(),
+            PlotChannel::HeadX => transform.translation.x,
+            PlotChannel::HeadY => transform.translation.y,
+            PlotChannel::HeadZ => transform.translation.z,
+            PlotChannel::BlendShape(name) => *face.blend_shapes.get(name)?,
+        })
+    }
+}
+
+impl fmt::Display for PlotChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlotChannel::HeadYaw => write!(f, "head yaw"),
+            PlotChannel::HeadPitch => write!(f, "head pitch"),
+            PlotChannel::HeadRoll => write!(f, "head roll"),
+            PlotChannel::HeadX => write!(f, "head x"),
+            PlotChannel::HeadY => write!(f, "head y"),
+            PlotChannel::HeadZ => write!(f, "head z"),
+            PlotChannel::BlendShape(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PlotSample {
+    time: f32,
+    incoming: Option<f32>,
+    applied: Option<f32>,
+}
+
+/// A debug plot of one tracked channel as it arrives and as it is applied,
+/// for tuning smoothing. Toggled with End.
+#[derive(Debug, Clone, Resource)]
+pub struct SmoothingPlot {
+    pub open: bool,
+    pub channel: PlotChannel,
+    /// How much history to show, in seconds.
+    pub window: f32,
+    incoming: Option<f32>,
+    samples: VecDeque<PlotSample>,
+}
+
+impl SmoothingPlot {
+    pub fn new(open: bool) -> SmoothingPlot {
+        SmoothingPlot {
+            open,
+            channel: PlotChannel::HeadYaw,
+            window: 5.,
+            incoming: None,
+            samples: VecDeque::new(),
+        }
+    }
+}
+
+pub fn toggle_smoothing_plot(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut plot: ResMut<SmoothingPlot>,
+) {
+    if keys.just_pressed(KeyCode::End) {
+        plot.open = !plot.open;
+    }
+}
+
+/// Record the incoming value of the plotted channel. This must run before
+/// any smoothing is applied to the faces.
+pub fn sample_incoming(
+    faces: Res<Faces>,
+    mut plot: ResMut<SmoothingPlot>,
+) {
+    if !plot.open || !faces.is_changed() {
+        return;
+    }
+
+    plot.incoming = faces.faces.first().and_then(|face| plot.channel.face_value(face));
+}
+
+/// Record the applied value of the plotted channel, alongside the last
+/// incoming value.
+pub fn sample_applied(
+    time: Res<Time>,
+    faces: Res<Faces>,
+    expressions: Res<ExpressionWeights>,
+    mut plot: ResMut<SmoothingPlot>,
+) {
+    if !plot.open {
+        return;
+    }
+
+    let applied = match &plot.channel {
+        PlotChannel::BlendShape(name) => expressions.index_of(name).map(|index| expressions.get_index(index)),
+        channel => faces.faces.first().and_then(|face| channel.face_value(face)),
+    };
+    let now = time.elapsed_seconds();
+    let sample = PlotSample {
+        time: now,
+        incoming: plot.incoming,
+        applied,
+    };
+    let window = plot.window;
+    plot.samples.push_back(sample);
+    while plot.samples.front().map_or(false, |sample| now - sample.time > window) {
+        plot.samples.pop_front();
+    }
+}
+
+pub fn draw_smoothing_plot(
+    mut contexts: EguiContexts,
+    time: Res<Time>,
+    faces: Res<Faces>,
+    mut plot: ResMut<SmoothingPlot>,
+) {
+    if !plot.open {
+        return;
+    }
+
+    let mut channels = PlotChannel::HEAD.to_vec();
+    if let Some(face) = faces.faces.first() {
+        let mut names = face.blend_shapes.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        channels.extend(names.into_iter().map(PlotChannel::BlendShape));
+    }
+
+    let plot = &mut *plot;
+    let mut open = plot.open;
+    let mut channel = plot.channel.clone();
+    egui::Window::new("Smoothing").open(&mut open).show(contexts.ctx_mut(), |ui| {
+        egui::ComboBox::from_label("Channel")
+            .selected_text(channel.to_string())
+            .show_ui(ui, |ui| {
+                for option in channels {
+                    let label = option.to_string();
+                    ui.selectable_value(&mut channel, option, label);
+                }
+            });
+
+        let size = egui::vec2(ui.available_width().max(320.), 160.);
+        let (response, painter) = ui.allocate_painter(size, egui::Sense::hover());
+        let rect = response.rect;
+        painter.rect_filled(rect, 0., Color32::from_gray(20));
+
+        let values = plot.samples.iter().flat_map(|sample| sample.incoming.into_iter().chain(sample.applied));
+        let (min, max) = values.fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), value| {
+            (min.min(value), max.max(value))
+        });
+        if min > max {
+            return;
+        }
+        let range = (max - min).max(f32::EPSILON);
+
+        let start = time.elapsed_seconds() - plot.window;
+        let to_screen = |time: f32, value: f32| egui::pos2(
+            rect.left() + (time - start) / plot.window * rect.width(),
+            rect.bottom() - (value - min) / range * rect.height(),
+        );
+        let incoming = plot.samples.iter()
+            .filter_map(|sample| Some(to_screen(sample.time, sample.incoming?)))
+            .collect();
+        let applied = plot.samples.iter()
+            .filter_map(|sample| Some(to_screen(sample.time, sample.applied?)))
+            .collect();
+        painter.add(egui::Shape::line(incoming, Stroke::new(1., INCOMING_COLOR)));
+        painter.add(egui::Shape::line(applied, Stroke::new(2., APPLIED_COLOR)));
+
+        ui.horizontal(|ui| {
+            ui.colored_label(INCOMING_COLOR, "incoming");
+            ui.colored_label(APPLIED_COLOR, "applied");
+            ui.label(format!("{min:.3} to {max:.3}"));
+        });
+    });
+
+    plot.open = open;
+    if channel != plot.channel {
+        plot.channel = channel;
+        plot.incoming = None;
+        plot.samples.clear();
+    }
+}
@@ -0,0 +1,166 @@
+use bevy::core_pipeline::tonemapping::Tonemapping;
+use bevy::prelude::*;
+use bevy::render::camera::{ClearColorConfig, RenderTarget};
+use bevy::render::mesh::MeshVertexBufferLayoutRef;
+use bevy::render::render_resource::{AsBindGroup, Extent3d, RenderPipelineDescriptor, ShaderRef, SpecializedMeshPipelineError, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages};
+use bevy::render::view::RenderLayers;
+use bevy::sprite::{Material2d, Material2dKey, MaterialMesh2dBundle};
+use bevy::window::WindowRef;
+
+/// The render layer of the downsampling quad, which no other camera sees.
+const DOWNSAMPLE_LAYER: usize = 3;
+
+/// Averages each `scale` by `scale` block of the supersampled output into
+/// one pixel of the output window.
+#[derive(Debug, Clone, Asset, TypePath, AsBindGroup)]
+#[bind_group_data(DownsampleMaterialKey)]
+pub struct DownsampleMaterial {
+    #[uniform(0)]
+    pub scale: u32,
+    #[texture(1)]
+    pub source: Handle<Image>,
+    /// Convert to straight alpha after averaging, which must happen here
+    /// rather than before, as averaging straight colour darkens edges.
+    pub straight_alpha: bool,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct DownsampleMaterialKey {
+    straight_alpha: bool,
+}
+
+impl From<&DownsampleMaterial> for DownsampleMaterialKey {
+    fn from(material: &DownsampleMaterial) -> Self {
+        DownsampleMaterialKey {
+            straight_alpha: material.straight_alpha,
+        }
+    }
+}
+
+impl Material2d for DownsampleMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/downsample.wgsl".into()
+    }
+
+    fn specialize(
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayoutRef,
+        key: Material2dKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        if key.bind_group_data.straight_alpha {
+            if let Some(fragment) = descriptor.fragment.as_mut() {
+                fragment.shader_defs.push("STRAIGHT_ALPHA".into());
+            }
+        }
+        // Replace the cleared window outright, so the premultiplied output
+        // isn't blended a second time.
+        if let Some(target) = descriptor.fragment.as_mut()
+            .and_then(|fragment| fragment.targets.first_mut())
+            .and_then(Option::as_mut) {
+            target.blend = None;
+        }
+        Ok(())
+    }
+}
+
+/// The camera which downsamples the supersampled output into its window.
+#[derive(Debug, Clone, Copy, Component)]
+pub struct OutputDownsample;
+
+/// Shrink a `width` by `height` output, keeping its aspect ratio, until it
+/// fits within `max_dimension`.
+pub fn fit_output_size(width: u32, height: u32, max_dimension: u32) -> (u32, u32) {
+    let largest = width.max(height);
+    if largest <= max_dimension {
+        return (width, height);
+    }
+    let fit = |size: u32| ((size as u64 * max_dimension as u64) / largest as u64).max(1) as u32;
+    (fit(width), fit(height))
+}
+
+/// The largest scale, no more than `requested`, at which a `width` by
+/// `height` output fits within `max_dimension`.
+pub fn supersampling_scale(requested: u32, width: u32, height: u32, max_dimension: u32) -> u32 {
+    let max_scale = (max_dimension / width.max(height).max(1)).max(1);
+    requested.clamp(1, max_scale)
+}
+
+/// The size and format of a supersampled output.
+#[derive(Debug, Clone, Copy)]
+pub struct SupersampledOutput {
+    /// The window's width, in pixels.
+    pub width: u32,
+    /// The window's height, in pixels.
+    pub height: u32,
+    /// How many times the window's resolution the output is rendered at.
+    pub scale: u32,
+    /// Convert to straight alpha when downsampling.
+    pub straight_alpha: bool,
+}
+
+/// Render the output at `scale` times the window's resolution, and
+/// downsample it into the window. Returns the target for the output camera.
+pub fn spawn_supersampled_output(
+    commands: &mut Commands,
+    images: &mut Assets<Image>,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<DownsampleMaterial>,
+    window: Entity,
+    output: SupersampledOutput,
+) -> RenderTarget {
+    let SupersampledOutput { width, height, scale, straight_alpha } = output;
+    let size = Extent3d {
+        width: width * scale,
+        height: height * scale,
+        depth_or_array_layers: 1,
+    };
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: Some("supersampled_output"),
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Bgra8UnormSrgb,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST
+                | TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        },
+        ..default()
+    };
+    image.resize(size);
+    let image = images.add(image);
+
+    commands.spawn((
+        Name::from("Output Downsample Camera"),
+        Camera2dBundle {
+            camera: Camera {
+                target: RenderTarget::Window(WindowRef::Entity(window)),
+                // Draw after the output camera has rendered the image.
+                order: 1,
+                clear_color: ClearColorConfig::Custom(Color::NONE),
+                ..default()
+            },
+            tonemapping: Tonemapping::None,
+            ..default()
+        },
+        RenderLayers::layer(DOWNSAMPLE_LAYER),
+        OutputDownsample,
+    ));
+    commands.spawn((
+        Name::from("Output Downsample"),
+        MaterialMesh2dBundle {
+            mesh: meshes.add(Rectangle::new(width as f32, height as f32)).into(),
+            material: materials.add(DownsampleMaterial {
+                scale,
+                source: image.clone(),
+                straight_alpha,
+            }),
+            ..default()
+        },
+        RenderLayers::layer(DOWNSAMPLE_LAYER),
+    ));
+
+    RenderTarget::Image(image)
+}
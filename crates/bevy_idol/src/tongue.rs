@@ -0,0 +1,87 @@
+use bevy::prelude::*;
+use bevy::render::mesh::morph::MeshMorphWeights;
+
+use crate::expressions::ExpressionWeights;
+
+/// The ARKit expression which drives the tongue.
+pub const TONGUE_OUT: &str = "tongueOut";
+
+/// Drive a separate tongue morph target or bone from the tracked `tongueOut`
+/// weight, for avatars which don't have a `tongueOut` morph of their own.
+#[derive(Debug, Clone, Default, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct Tongue {
+    /// Name of a morph target to drive, if not empty.
+    pub morph: String,
+    /// Name of a bone to move, if not empty.
+    pub bone: String,
+    /// Translation of the bone when the tongue is fully out, in the bone's
+    /// parent space.
+    pub offset: Vec3,
+}
+
+/// The translation of the tongue bone in the avatar's rest pose.
+#[derive(Debug, Clone, Copy, Component)]
+pub struct TongueRest(Vec3);
+
+pub fn parse_vec3(s: &str) -> Result<Vec3, String> {
+    let components = s.split(',')
+        .map(|c| c.trim().parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("invalid vector {s:?}: {err}"))?;
+    match components[..] {
+        [x, y, z] => Ok(Vec3::new(x, y, z)),
+        _ => Err(format!("invalid vector {s:?}: expected x,y,z")),
+    }
+}
+
+pub fn add_tongue_rest(
+    mut commands: Commands,
+    settings: Res<Tongue>,
+    bones: Query<(Entity, &Name, &Transform), Added<Name>>,
+) {
+    if settings.bone.is_empty() {
+        return;
+    }
+
+    for (entity, name, transform) in &bones {
+        if name.as_str() == settings.bone {
+            commands.entity(entity).insert(TongueRest(transform.translation));
+        }
+    }
+}
+
+pub fn apply_tongue_bone(
+    settings: Res<Tongue>,
+    expressions: Res<ExpressionWeights>,
+    mut bones: Query<(&TongueRest, &mut Transform)>,
+) {
+    let weight = expressions.get(TONGUE_OUT);
+    for (rest, mut transform) in &mut bones {
+        transform.translation = rest.0 + settings.offset * weight;
+    }
+}
+
+pub fn apply_tongue_morph(
+    settings: Res<Tongue>,
+    expressions: Res<ExpressionWeights>,
+    meshes: Res<Assets<Mesh>>,
+    mut entities: Query<(&Handle<Mesh>, &mut MeshMorphWeights)>,
+) {
+    if settings.morph.is_empty() {
+        return;
+    }
+
+    let tongue = expressions.get(TONGUE_OUT);
+    for (mesh, mut weights) in &mut entities {
+        let Some(index) = meshes.get(mesh)
+            .and_then(|mesh| mesh.morph_target_names())
+            .and_then(|names| names.iter().position(|name| *name == settings.morph)) else {
+            continue;
+        };
+
+        if let Some(weight) = weights.weights_mut().get_mut(index) {
+            *weight = weight.max(tongue);
+        }
+    }
+}
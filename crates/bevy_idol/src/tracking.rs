@@ -1,9 +1,83 @@
 use std::collections::HashMap;
+use std::time::Duration;
 
-use bevy::prelude::{Resource, Transform};
+use bevy::math::{BVec3, Mat3, Mat4, Vec3};
+use bevy::prelude::{DetectChanges, DetectChangesMut, Real, Reflect, ReflectResource, Res, ResMut, Resource, Time, Transform};
 
 use idol_api::FaceLandmark;
 
+use crate::calibration::Calibration;
+
+/// How the axes of a tracker's face transforms map onto ours, which follow
+/// MediaPipe: right-handed and Y-up, with +Z from the face towards the
+/// camera.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AxisConvention {
+    // Converts from the tracker's axes to ours.
+    basis: Mat3,
+}
+
+impl AxisConvention {
+    pub const MEDIAPIPE: AxisConvention = AxisConvention {
+        basis: Mat3::IDENTITY,
+    };
+
+    /// Parse a preset, or a remapping like `-x,y,z` giving the tracker axis
+    /// (and sign) for each of our axes.
+    ///
+    /// - `mediapipe`: our convention, unchanged.
+    /// - `arkit`: ARKit face anchors, which are mirrored (+X is the face's
+    ///   left): `-x,y,z`.
+    /// - `unity`: left-handed, with +Z away from the camera: `x,y,-z`.
+    pub fn parse(s: &str) -> Result<AxisConvention, String> {
+        let remap = match s {
+            "mediapipe" => return Ok(AxisConvention::MEDIAPIPE),
+            "arkit" => "-x,y,z",
+            "unity" => "x,y,-z",
+            remap => remap,
+        };
+
+        let mut rows = [Vec3::ZERO; 3];
+        let mut used = [false; 3];
+        let axes: Vec<&str> = remap.split(',').map(str::trim).collect();
+        if axes.len() != 3 {
+            return Err(format!("invalid axis convention {s:?}: expected a preset or three axes like -x,y,z"));
+        }
+        for (row, axis) in rows.iter_mut().zip(axes) {
+            let (sign, name) = match axis.strip_prefix('-') {
+                Some(name) => (-1., name),
+                None => (1., axis.strip_prefix('+').unwrap_or(axis)),
+            };
+            let index = match name {
+                "x" => 0,
+                "y" => 1,
+                "z" => 2,
+                _ => return Err(format!("invalid axis {axis:?} in {s:?}")),
+            };
+            if std::mem::replace(&mut used[index], true) {
+                return Err(format!("axis {name} is used twice in {s:?}"));
+            }
+            row[index] = sign;
+        }
+
+        Ok(AxisConvention {
+            basis: Mat3::from_cols(rows[0], rows[1], rows[2]).transpose(),
+        })
+    }
+
+    /// Convert a transform from the tracker's axes to ours.
+    pub fn convert(&self, transform: Mat4) -> Mat4 {
+        let basis = Mat4::from_mat3(self.basis);
+        basis * transform * basis.transpose()
+    }
+}
+
+impl Default for AxisConvention {
+    fn default() -> Self {
+        AxisConvention::MEDIAPIPE
+    }
+}
+
 #[derive(Debug)]
 pub struct Face {
     pub landmarks: Vec<FaceLandmark>,
@@ -14,4 +88,195 @@ pub struct Face {
 #[derive(Debug, Default, Resource)]
 pub struct Faces {
     pub faces: Vec<Face>,
+    /// The first face's transform after calibration, but before noise and
+    /// translation locks, which is what calibrating takes as neutral.
+    pub calibrated: Option<Transform>,
+}
+
+/// Which axes of the tracked head translation are applied. Locked axes hold
+/// a tracked translation, so leaning can turn the head without moving it
+/// along them.
+///
+/// The held translation is captured from the first face after the lock is
+/// reset, which happens when the axes or calibration change, or tracking is
+/// lost.
+#[derive(Debug, Clone, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct TrackedTranslation {
+    pub x: bool,
+    pub y: bool,
+    pub z: bool,
+    /// The translation which locked axes hold.
+    pub reference: Option<Vec3>,
+    /// The applied axes when `reference` was captured.
+    #[reflect(ignore)]
+    reference_axes: BVec3,
+}
+
+impl TrackedTranslation {
+    pub fn new(axes: BVec3) -> TrackedTranslation {
+        TrackedTranslation {
+            x: axes.x,
+            y: axes.y,
+            z: axes.z,
+            reference: None,
+            reference_axes: axes,
+        }
+    }
+
+    pub fn apply(&mut self, face: &mut Face) {
+        let axes = BVec3::new(self.x, self.y, self.z);
+        if axes != self.reference_axes {
+            self.reference = None;
+            self.reference_axes = axes;
+        }
+        if axes.all() {
+            return;
+        }
+
+        let translation = &mut face.transform.translation;
+        let reference = *self.reference.get_or_insert(*translation);
+        *translation = Vec3::select(axes, *translation, reference);
+    }
+
+    /// Forget the held translation, so that the next face captures it again.
+    pub fn reset(&mut self) {
+        self.reference = None;
+    }
+}
+
+/// Let locked axes capture a new reference when the calibration changes, as
+/// the held translation was calibrated differently, or when tracking is
+/// lost, as the performer may have moved.
+pub fn reset_translation_lock(
+    calibration: Res<Calibration>,
+    status: Res<TrackingStatus>,
+    mut translation: ResMut<TrackedTranslation>,
+) {
+    let lost = status.is_changed() && !status.active;
+    if calibration.is_changed() || lost {
+        translation.reset();
+    }
+}
+
+/// Parse a set of axes, such as `xz`, or `none`.
+pub fn parse_axes(s: &str) -> Result<BVec3, String> {
+    let mut axes = BVec3::FALSE;
+    if s == "none" {
+        return Ok(axes);
+    }
+    for c in s.chars() {
+        match c.to_ascii_lowercase() {
+            'x' => axes.x = true,
+            'y' => axes.y = true,
+            'z' => axes.z = true,
+            _ => return Err(format!("invalid axes {s:?}: expected some of x, y and z, or none")),
+        }
+    }
+    Ok(axes)
+}
+
+#[derive(Debug)]
+pub struct Pose {
+    pub landmarks: Vec<FaceLandmark>,
+}
+
+#[derive(Debug, Default, Resource)]
+pub struct Poses {
+    pub poses: Vec<Pose>,
+}
+
+/// Whether a face is currently being tracked, with hysteresis.
+///
+/// Tracking becomes active once `min_frames` face frames have arrived with no
+/// gap longer than `timeout`, and inactive once no frame has arrived for
+/// `timeout`, so brief dropouts don't make it flicker.
+#[derive(Debug, Clone, Resource)]
+pub struct TrackingStatus {
+    pub active: bool,
+    pub timeout: Duration,
+    pub min_frames: u32,
+    frames: u32,
+    last_frame: Option<Duration>,
+}
+
+impl TrackingStatus {
+    pub fn new(timeout: Duration, min_frames: u32) -> TrackingStatus {
+        TrackingStatus {
+            active: false,
+            timeout,
+            min_frames,
+            frames: 0,
+            last_frame: None,
+        }
+    }
+}
+
+pub fn update_tracking_status(
+    time: Res<Time<Real>>,
+    faces: Res<Faces>,
+    mut status: ResMut<TrackingStatus>,
+) {
+    let now = time.elapsed();
+    let timed_out = status.last_frame
+        .map_or(true, |last| now.saturating_sub(last) > status.timeout);
+
+    if faces.is_changed() && !faces.faces.is_empty() {
+        let status = status.bypass_change_detection();
+        status.frames = if timed_out { 1 } else { status.frames.saturating_add(1) };
+        status.last_frame = Some(now);
+    } else if timed_out {
+        status.bypass_change_detection().frames = 0;
+    }
+
+    let active = status.frames >= status.min_frames;
+    if status.active != active {
+        status.active = active;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn face(translation: Vec3) -> Face {
+        Face {
+            landmarks: Vec::new(),
+            blend_shapes: HashMap::new(),
+            transform: Transform::from_translation(translation),
+        }
+    }
+
+    fn applied(lock: &mut TrackedTranslation, translation: Vec3) -> Vec3 {
+        let mut face = face(translation);
+        lock.apply(&mut face);
+        face.transform.translation
+    }
+
+    #[test]
+    fn locked_axes_hold_the_first_translation() {
+        let mut lock = TrackedTranslation::new(BVec3::new(true, true, false));
+        assert_eq!(applied(&mut lock, Vec3::new(1., 2., 3.)), Vec3::new(1., 2., 3.));
+        assert_eq!(applied(&mut lock, Vec3::new(4., 5., 6.)), Vec3::new(4., 5., 3.));
+    }
+
+    #[test]
+    fn lock_recaptures_after_reset() {
+        let mut lock = TrackedTranslation::new(BVec3::new(true, true, false));
+        applied(&mut lock, Vec3::new(1., 2., 3.));
+
+        lock.reset();
+        assert_eq!(applied(&mut lock, Vec3::new(4., 5., 6.)), Vec3::new(4., 5., 6.));
+        assert_eq!(applied(&mut lock, Vec3::new(7., 8., 9.)), Vec3::new(7., 8., 6.));
+    }
+
+    #[test]
+    fn lock_recaptures_when_axes_change() {
+        let mut lock = TrackedTranslation::new(BVec3::new(true, true, false));
+        applied(&mut lock, Vec3::new(1., 2., 3.));
+
+        lock.x = false;
+        assert_eq!(applied(&mut lock, Vec3::new(4., 5., 6.)), Vec3::new(4., 5., 6.));
+        assert_eq!(applied(&mut lock, Vec3::new(7., 8., 9.)), Vec3::new(4., 8., 6.));
+    }
 }
@@ -0,0 +1,89 @@
+use bevy::prelude::*;
+use bevy::ui::TargetCamera;
+use clap::ValueEnum;
+
+use crate::tracking::TrackingStatus;
+
+/// Where the tracking lost indicator sits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum IndicatorPlacement {
+    /// Never show the indicator.
+    None,
+    TopLeft,
+    #[default]
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// A notice shown while no face is being tracked, as a reminder to
+/// re-centre.
+#[derive(Debug, Clone, Copy, Component)]
+pub struct TrackingLostIndicator;
+
+/// Spawn the indicator in the corner given by `placement`, over the camera
+/// `camera` (or the preview camera if `None`).
+pub fn spawn_tracking_indicator(
+    commands: &mut Commands,
+    placement: IndicatorPlacement,
+    camera: Option<Entity>,
+    font: Handle<Font>,
+) {
+    let margin = Val::Px(12.);
+    let (top, bottom) = match placement {
+        IndicatorPlacement::None => return,
+        IndicatorPlacement::TopLeft | IndicatorPlacement::TopRight => (margin, Val::Auto),
+        IndicatorPlacement::BottomLeft | IndicatorPlacement::BottomRight => (Val::Auto, margin),
+    };
+    let (left, right) = match placement {
+        IndicatorPlacement::TopLeft | IndicatorPlacement::BottomLeft => (margin, Val::Auto),
+        _ => (Val::Auto, margin),
+    };
+
+    let mut indicator = commands.spawn((
+        Name::from("Tracking Lost Indicator"),
+        TextBundle {
+            text: Text::from_section("tracking lost", TextStyle {
+                font,
+                font_size: 24.,
+                color: Color::srgb(1., 0.3, 0.3),
+            }),
+            style: Style {
+                position_type: PositionType::Absolute,
+                top,
+                bottom,
+                left,
+                right,
+                padding: UiRect::axes(Val::Px(8.), Val::Px(4.)),
+                ..default()
+            },
+            background_color: Color::srgba(0., 0., 0., 0.6).into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        TrackingLostIndicator,
+    ));
+    if let Some(camera) = camera {
+        indicator.insert(TargetCamera(camera));
+    }
+}
+
+/// Show the indicators while tracking is inactive.
+pub fn update_tracking_indicators(
+    status: Res<TrackingStatus>,
+    mut indicators: Query<&mut Visibility, With<TrackingLostIndicator>>,
+    added: Query<(), Added<TrackingLostIndicator>>,
+) {
+    if !status.is_changed() && added.is_empty() {
+        return;
+    }
+
+    let visibility = if status.active {
+        Visibility::Hidden
+    } else {
+        Visibility::Visible
+    };
+    for mut indicator in &mut indicators {
+        *indicator = visibility;
+    }
+}
@@ -0,0 +1,64 @@
+use bevy::prelude::*;
+
+use crate::procedural::Rng;
+use crate::tracking::Face;
+
+/// Gaussian noise added to incoming face frames, for testing smoothing.
+///
+/// The RNG is seeded, so a recorded session replays with the same noise.
+#[derive(Debug, Clone, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct TrackingNoise {
+    pub enabled: bool,
+    /// Standard deviation of the face translation, in metres.
+    pub translation: f32,
+    /// Standard deviation of the face rotation about each axis, in radians.
+    pub rotation: f32,
+    /// Standard deviation of each blend shape weight.
+    pub blend_shapes: f32,
+    #[reflect(ignore)]
+    rng: Rng,
+}
+
+impl TrackingNoise {
+    pub fn new(enabled: bool, seed: u32, translation: f32, rotation: f32, blend_shapes: f32) -> TrackingNoise {
+        TrackingNoise {
+            enabled,
+            translation,
+            rotation,
+            blend_shapes,
+            rng: Rng::new(seed),
+        }
+    }
+
+    /// A normally distributed value, by the Box-Muller transform.
+    fn gaussian(&mut self, std_dev: f32) -> f32 {
+        let u1 = 1. - self.rng.next_f32();
+        let u2 = self.rng.next_f32();
+        std_dev * (-2. * u1.ln()).sqrt() * (std::f32::consts::TAU * u2).cos()
+    }
+
+    fn gaussian_vec3(&mut self, std_dev: f32) -> Vec3 {
+        Vec3::new(self.gaussian(std_dev), self.gaussian(std_dev), self.gaussian(std_dev))
+    }
+
+    pub fn apply(&mut self, face: &mut Face) {
+        if !self.enabled {
+            return;
+        }
+
+        face.transform.translation += self.gaussian_vec3(self.translation);
+        let rotation = self.gaussian_vec3(self.rotation);
+        face.transform.rotation *= Quat::from_euler(EulerRot::XYZ, rotation.x, rotation.y, rotation.z);
+
+        // Sort the names so the same seed perturbs the same shapes.
+        let mut names: Vec<_> = face.blend_shapes.keys().cloned().collect();
+        names.sort();
+        for name in names {
+            let noise = self.gaussian(self.blend_shapes);
+            if let Some(weight) = face.blend_shapes.get_mut(&name) {
+                *weight = (*weight + noise).clamp(0., 1.);
+            }
+        }
+    }
+}
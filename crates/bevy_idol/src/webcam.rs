@@ -1,8 +1,29 @@
 use bevy::pbr::StandardMaterial;
-use bevy::prelude::{Handle, Image, Resource};
+use bevy::prelude::{Handle, Image, Reflect, ReflectResource, Resource};
 
 #[derive(Resource)]
 pub struct WebcamTexture {
     pub image: Handle<Image>,
     pub material: Handle<StandardMaterial>,
 }
+
+/// Placement of the camera plane which shows the webcam, so it can be lined
+/// up with the tracked face.
+#[derive(Debug, Clone, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct CameraPlaneSettings {
+    /// Distance from the origin along -Z, in metres.
+    pub distance: f32,
+    /// Length of the plane's shorter side, in metres. The longer side
+    /// follows the webcam's aspect ratio.
+    pub size: f32,
+}
+
+impl Default for CameraPlaneSettings {
+    fn default() -> Self {
+        CameraPlaneSettings {
+            distance: 5.,
+            size: 10.,
+        }
+    }
+}
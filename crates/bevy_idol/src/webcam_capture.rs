@@ -0,0 +1,133 @@
+use std::io;
+use std::sync::Arc;
+
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use parking_lot::Mutex;
+use v4l::buffer::Type;
+use v4l::io::mmap::Stream;
+use v4l::io::traits::CaptureStream;
+use v4l::video::Capture;
+use v4l::{Device, FourCC};
+
+use crate::error::IdolError;
+use crate::webcam::WebcamTexture;
+
+struct CapturedFrame {
+    width: u32,
+    height: u32,
+    rgba: Vec<u8>,
+}
+
+/// A local webcam, read on its own thread, which replaces frames pushed to
+/// `/v1/camera`.
+#[derive(Resource)]
+pub struct WebcamCapture {
+    latest: Arc<Mutex<Option<CapturedFrame>>>,
+}
+
+impl WebcamCapture {
+    /// Open a Video4Linux device, given either its index or its path, and
+    /// start capturing from it.
+    pub fn open(device: &str) -> Result<WebcamCapture, IdolError> {
+        let webcam_error = |source| IdolError::Webcam {
+            device: device.to_string(),
+            source,
+        };
+
+        let opened = match device.parse::<usize>() {
+            Ok(index) => Device::new(index),
+            Err(_) => Device::with_path(device),
+        };
+        let opened = opened.map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound => webcam_error(io::Error::new(io::ErrorKind::NotFound, "no such device")),
+            _ => webcam_error(err),
+        })?;
+
+        // Most webcams offer YUYV, which is cheap to convert without pulling in
+        // a JPEG decoder.
+        let mut format = opened.format().map_err(webcam_error)?;
+        format.fourcc = FourCC::new(b"YUYV");
+        let format = opened.set_format(&format).map_err(webcam_error)?;
+        if format.fourcc != FourCC::new(b"YUYV") {
+            return Err(webcam_error(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("unsupported pixel format {}, only YUYV is supported", format.fourcc),
+            )));
+        }
+        info!("capturing {}x{} from webcam {}", format.width, format.height, device);
+
+        let latest = Arc::new(Mutex::new(None));
+        let thread_latest = latest.clone();
+        let thread_device = device.to_string();
+        std::thread::spawn(move || {
+            let mut stream = match Stream::with_buffers(&opened, Type::VideoCapture, 4) {
+                Ok(stream) => stream,
+                Err(err) => {
+                    error!("failed to start webcam {}: {}", thread_device, err);
+                    return;
+                }
+            };
+
+            loop {
+                let buffer = match stream.next() {
+                    Ok((buffer, _)) => buffer,
+                    Err(err) => {
+                        error!("stopped capturing from webcam {}: {}", thread_device, err);
+                        return;
+                    }
+                };
+
+                *thread_latest.lock() = Some(CapturedFrame {
+                    width: format.width,
+                    height: format.height,
+                    rgba: yuyv_to_rgba(buffer),
+                });
+            }
+        });
+
+        Ok(WebcamCapture {
+            latest,
+        })
+    }
+}
+
+fn yuyv_to_rgba(yuyv: &[u8]) -> Vec<u8> {
+    let mut rgba = Vec::with_capacity(yuyv.len() * 2);
+    for chunk in yuyv.chunks_exact(4) {
+        let u = chunk[1] as f32 - 128.;
+        let v = chunk[3] as f32 - 128.;
+        for y in [chunk[0], chunk[2]] {
+            let y = y as f32;
+            rgba.extend_from_slice(&[
+                (y + 1.402 * v) as u8,
+                (y - 0.344 * u - 0.714 * v) as u8,
+                (y + 1.772 * u) as u8,
+                0xff,
+            ]);
+        }
+    }
+    rgba
+}
+
+/// Show the most recent captured frame on the camera plane.
+pub fn apply_webcam_capture(
+    capture: Res<WebcamCapture>,
+    webcam: Res<WebcamTexture>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let Some(frame) = capture.latest.lock().take() else {
+        return;
+    };
+
+    let size = Extent3d {
+        width: frame.width,
+        height: frame.height,
+        depth_or_array_layers: 1,
+    };
+    let image = Image::new(size, TextureDimension::D2, frame.rgba, TextureFormat::Rgba8UnormSrgb, RenderAssetUsages::RENDER_WORLD);
+    let _ = images.insert(&webcam.image, image);
+    materials.get_mut(&webcam.material);
+}
@@ -0,0 +1,82 @@
+//! End-to-end VRM loading benchmark.
+//!
+//! There are two default cases:
+//! - Small: the humanoid fixture checked in under `tests/fixtures`, which
+//!   is always run.
+//! - Large: the demo avatar shipped with bevy_idol, with its meshes and
+//!   textures. It is stored in Git LFS, so it is skipped (with a message)
+//!   until `git lfs pull` has fetched it.
+//!
+//! Additional avatars can be benchmarked by listing their paths (relative to
+//! the bevy_idol asset directory) in `BEVY_VRM_BENCH_AVATARS`, separated by
+//! commas.
+//!
+//! Criterion only times whole loads. For a per-phase breakdown (buffers,
+//! materials, meshes, textures and scenes), run with
+//! `RUST_LOG=bevy_vrm=debug`, and the loader logs the phases of each load.
+//! Otherwise only warnings are logged.
+
+use std::sync::Once;
+
+use bevy::log::{Level, LogPlugin};
+use bevy::prelude::*;
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::{FIXTURES, IDOL_ASSETS};
+
+const DEFAULT_AVATARS: &[(&str, &str)] = &[
+    (FIXTURES, "humanoid.vrm"),
+    (IDOL_ASSETS, "avatars/demo.vrm"),
+];
+
+fn new_app(asset_root: &str) -> App {
+    let mut app = common::new_app(asset_root);
+    // The loader's per-phase timings are only logged with a subscriber, which
+    // can only be installed once per process.
+    static LOG: Once = Once::new();
+    LOG.call_once(|| {
+        app.add_plugins(LogPlugin {
+            level: Level::WARN,
+            ..default()
+        });
+    });
+    app
+}
+
+fn load(mut app: App, path: &str) -> App {
+    let handle = common::load(&mut app, path);
+    // Check that the load produced a complete avatar, so a broken loader
+    // isn't mistaken for a fast one.
+    common::humanoid(common::default_scene(&mut app, &handle));
+    app
+}
+
+fn bench_load_vrm(c: &mut Criterion) {
+    let extra_avatars = std::env::var("BEVY_VRM_BENCH_AVATARS").unwrap_or_default();
+    let avatars = DEFAULT_AVATARS.iter()
+        .copied()
+        .chain(extra_avatars.split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|path| (IDOL_ASSETS, path)));
+
+    let mut group = c.benchmark_group("load_vrm");
+    group.sample_size(10);
+    for (asset_root, path) in avatars {
+        if !common::is_available(asset_root, path) {
+            eprintln!("Skipping {path}, it's missing or a Git LFS pointer (run `git lfs pull`)");
+            continue;
+        }
+
+        group.bench_function(path, |b| {
+            b.iter_batched(|| new_app(asset_root), |app| load(app, path), BatchSize::PerIteration);
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_load_vrm);
+criterion_main!(benches);
@@ -2,6 +2,9 @@ use serde::{Deserialize, Serialize};
 
 pub mod vrm;
 pub mod mtoon;
+pub mod node_constraint;
+pub mod pbr;
+pub mod spring_bone;
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -15,12 +18,18 @@ pub struct TextureInfo {
 pub struct RootExtensions {
     #[serde(rename = "VRMC_vrm")]
     pub vrm: vrm::VrmExtensionJson,
+    #[serde(rename = "VRMC_springBone")]
+    pub spring_bone: Option<spring_bone::SpringBoneExtensionJson>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct MaterialExtensions {
     #[serde(rename = "VRMC_materials_mtoon")]
     pub mtoon: Option<mtoon::MToonExtensionJson>,
+    #[serde(rename = "KHR_materials_specular")]
+    pub specular: Option<pbr::SpecularExtensionJson>,
+    #[serde(rename = "KHR_materials_clearcoat")]
+    pub clearcoat: Option<pbr::ClearcoatExtensionJson>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -28,8 +37,22 @@ pub struct ExtendedMaterial {
     pub extensions: MaterialExtensions,
 }
 
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct NodeExtensions {
+    #[serde(rename = "VRMC_node_constraint")]
+    pub node_constraint: Option<node_constraint::NodeConstraintExtensionJson>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ExtendedNode {
+    #[serde(default)]
+    pub extensions: NodeExtensions,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ExtendedRoot {
     pub extensions: RootExtensions,
     pub materials: Vec<ExtendedMaterial>,
+    #[serde(default)]
+    pub nodes: Vec<ExtendedNode>,
 }
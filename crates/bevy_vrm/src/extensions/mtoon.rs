@@ -1,6 +1,6 @@
 use bevy::asset::{Asset, Handle, ReflectAsset};
 use bevy::math::{Vec3, Vec4};
-use bevy::pbr::{Material, MaterialPipeline, MaterialPipelineKey};
+use bevy::pbr::{ExtendedMaterial, Material, MaterialExtension, MaterialExtensionKey, MaterialExtensionPipeline, MaterialPipeline, MaterialPipelineKey};
 use bevy::prelude::*;
 use bevy::reflect::Reflect;
 use bevy::render::mesh::MeshVertexBufferLayoutRef;
@@ -39,6 +39,44 @@ pub enum OutlineWidthMode {
     ScreenCoordinates,
 }
 
+/// Which faces a material culls.
+///
+/// This stands in for `Option<Face>`, which can't be reflected, so that it
+/// can be changed in the inspector.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, Reflect, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[reflect(Debug, Default)]
+pub enum MaterialCullMode {
+    #[default]
+    Back,
+    Front,
+    None,
+}
+
+impl MaterialCullMode {
+    /// Cull back faces unless the material is double-sided, as glTF does.
+    pub fn from_double_sided(double_sided: bool) -> MaterialCullMode {
+        if double_sided {
+            MaterialCullMode::None
+        } else {
+            MaterialCullMode::Back
+        }
+    }
+
+    pub fn face(self) -> Option<Face> {
+        match self {
+            MaterialCullMode::Back => Some(Face::Back),
+            MaterialCullMode::Front => Some(Face::Front),
+            MaterialCullMode::None => None,
+        }
+    }
+
+    /// Whether back faces are drawn, and so need their normals flipping.
+    pub fn double_sided(self) -> bool {
+        self != MaterialCullMode::Back
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default, rename_all = "camelCase")]
 pub struct MToonExtensionJson {
@@ -101,6 +139,13 @@ impl Default for MToonExtensionJson {
     }
 }
 
+/// View depth added to the transparent sort distance per render queue step.
+///
+/// The meshes of an avatar usually share an origin, so this only needs to
+/// outweigh the small differences between them without reordering the avatar
+/// against the rest of the scene.
+pub const RENDER_QUEUE_SORT_STEP: f32 = 0.1;
+
 #[derive(Clone, Debug, Reflect, Asset, AsBindGroup)]
 #[bind_group_data(MToonMaterialKey)]
 #[uniform(0, MToonMaterialUniform)]
@@ -109,10 +154,13 @@ pub struct MToonMaterial {
     pub alpha_mode: AlphaMode,
     pub double_sided: bool,
     pub fog_enabled: bool,
-    #[reflect(ignore)]
-    pub cull_mode: Option<Face>,
+    pub cull_mode: MaterialCullMode,
     pub transparent_with_z_write: bool,
+    /// Constant depth bias applied to the pipeline, in depth buffer units.
     pub depth_bias: i32,
+    /// Offset from the default render queue, which orders transparent
+    /// materials drawn at the same depth. Higher values draw later.
+    pub render_queue_offset: i32,
     pub base_color: LinearRgba,
     #[texture(1)]
     #[sampler(2)]
@@ -134,6 +182,8 @@ pub struct MToonMaterial {
     #[texture(9)]
     #[sampler(10)]
     pub normal_map_texture: Option<Handle<Image>>,
+    /// Scale of the normal map's X and Y, from the glTF `normalTexture`.
+    pub normal_scale: f32,
     pub gi_equalization_factor: f32,
     pub matcap_factor: Vec3,
     #[texture(11)]
@@ -146,19 +196,30 @@ pub struct MToonMaterial {
     pub rim_lighting_mix_factor: f32,
     pub parametric_rim_fresnel_power_factor: f32,
     pub parametric_rim_lift_factor: f32,
-    // pub outline_width_mode: OutlineWidthMode,
-    // pub outline_width_factor: f32,
-    // #[texture(15)]
-    // #[sampler(16)]
-    // pub outline_width_multiply_texture: Option<Handle<Image>>,
-    // pub outline_color_factor: Vec3,
-    // pub outline_lighting_mix_factor: f32,
+    /// How the outline is sized, if the material has one. The outline itself
+    /// is drawn by a [`MToonOutlineMaterial`].
+    pub outline_width_mode: OutlineWidthMode,
     #[texture(15)]
     #[sampler(16)]
     pub uv_animation_mask_texture: Option<Handle<Image>>,
     pub uv_animation_scroll_x_speed_factor: f32,
     pub uv_animation_scroll_y_speed_factor: f32,
     pub uv_animation_rotation_speed_factor: f32,
+    /// Which UV set each texture is sampled with: 0 for `TEXCOORD_0`, or 1
+    /// for `TEXCOORD_1`.
+    pub uv_sets: MToonUvSets,
+}
+
+/// The UV set of each of an [`MToonMaterial`]'s textures which are sampled
+/// with vertex UVs.
+#[derive(Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Debug, Default)]
+pub struct MToonUvSets {
+    pub base_color: u32,
+    pub emissive: u32,
+    pub shade_color: u32,
+    pub shading_shift: u32,
+    pub normal_map: u32,
 }
 
 impl Default for MToonMaterial {
@@ -166,10 +227,11 @@ impl Default for MToonMaterial {
         Self {
             alpha_mode: AlphaMode::Opaque,
             double_sided: false,
-            cull_mode: Some(Face::Back),
+            cull_mode: MaterialCullMode::Back,
             fog_enabled: true,
             transparent_with_z_write: false,
             depth_bias: 0,
+            render_queue_offset: 0,
             base_color: Color::WHITE.into(),
             base_color_texture: None,
             emissive: Color::NONE.into(),
@@ -181,6 +243,7 @@ impl Default for MToonMaterial {
             shading_shift_texture: None,
             shading_toony_factor: 0.9,
             normal_map_texture: None,
+            normal_scale: 1.0,
             gi_equalization_factor: 0.9,
             matcap_factor: Vec3::ZERO,
             matcap_texture: None,
@@ -189,15 +252,12 @@ impl Default for MToonMaterial {
             rim_lighting_mix_factor: 0.0,
             parametric_rim_fresnel_power_factor: 1.0,
             parametric_rim_lift_factor: 0.0,
-            // outline_width_mode: OutlineWidthMode::None,
-            // outline_width_factor: 0.0,
-            // outline_width_multiply_texture: None,
-            // outline_color_factor: Vec3::ZERO,
-            // outline_lighting_mix_factor: 1.0,
+            outline_width_mode: OutlineWidthMode::None,
             uv_animation_mask_texture: None,
             uv_animation_scroll_x_speed_factor: 0.0,
             uv_animation_scroll_y_speed_factor: 0.0,
             uv_animation_rotation_speed_factor: 0.0,
+            uv_sets: MToonUvSets::default(),
         }
     }
 }
@@ -212,9 +272,15 @@ impl Material for MToonMaterial {
         self.alpha_mode
     }
 
+    /// Bevy only uses this to offset the sort distance in the transparent
+    /// phase, so it carries the render queue offset rather than the
+    /// pipeline depth bias.
     #[inline]
     fn depth_bias(&self) -> f32 {
-        self.depth_bias as f32
+        match self.alpha_mode {
+            AlphaMode::Opaque | AlphaMode::Mask(_) => 0.,
+            _ => self.render_queue_offset as f32 * RENDER_QUEUE_SORT_STEP,
+        }
     }
 
     fn specialize(
@@ -225,20 +291,23 @@ impl Material for MToonMaterial {
     ) -> Result<(), SpecializedMeshPipelineError> {
         descriptor.primitive.cull_mode = key.bind_group_data.cull_mode;
 
-        // if let Some(fragment) = descriptor.fragment.as_mut() {
-        //     let shader_defs = &mut fragment.shader_defs;
-        //
-        //     match key.bind_group_data.outline_width_mode {
-        //         OutlineWidthMode::None => {},
-        //         OutlineWidthMode::ScreenCoordinates =>
-        //             shader_defs.push("OUTLINE_WIDTH_SCREEN".into()),
-        //         OutlineWidthMode::WorldCoordinates =>
-        //             shader_defs.push("OUTLINE_WIDTH_WORLD".into()),
-        //     }
-        // }
+        let outline_def = match key.bind_group_data.outline_width_mode {
+            OutlineWidthMode::None => None,
+            OutlineWidthMode::ScreenCoordinates => Some("OUTLINE_WIDTH_SCREEN"),
+            OutlineWidthMode::WorldCoordinates => Some("OUTLINE_WIDTH_WORLD"),
+        };
+        if let Some(outline_def) = outline_def {
+            descriptor.vertex.shader_defs.push(outline_def.into());
+            if let Some(fragment) = descriptor.fragment.as_mut() {
+                fragment.shader_defs.push(outline_def.into());
+            }
+        }
 
         if let Some(depth_stencil) = descriptor.depth_stencil.as_mut() {
             depth_stencil.bias.constant = key.bind_group_data.depth_bias;
+            if key.bind_group_data.transparent_with_z_write {
+                depth_stencil.depth_write_enabled = true;
+            }
         }
 
         Ok(())
@@ -287,18 +356,21 @@ pub struct MToonMaterialUniform {
     pub shading_shift_factor: f32,
     pub shading_shift_scale: f32,
     pub shading_toony_factor: f32,
+    pub normal_scale: f32,
     pub gi_equalization_factor: f32,
     pub matcap_factor: Vec3,
     pub parametric_rim_color_factor: Vec3,
     pub rim_lighting_mix_factor: f32,
     pub parametric_rim_fresnel_power_factor: f32,
     pub parametric_rim_lift_factor: f32,
-    // pub outline_width_factor: f32,
-    // pub outline_color_factor: Vec3,
-    // pub outline_lighting_mix_factor: f32,
     pub uv_animation_scroll_x_speed_factor: f32,
     pub uv_animation_scroll_y_speed_factor: f32,
     pub uv_animation_rotation_speed_factor: f32,
+    pub base_color_uv_set: u32,
+    pub emissive_uv_set: u32,
+    pub shade_color_uv_set: u32,
+    pub shading_shift_uv_set: u32,
+    pub normal_map_uv_set: u32,
 }
 
 impl AsBindGroupShaderType<MToonMaterialUniform> for MToonMaterial {
@@ -383,18 +455,21 @@ impl AsBindGroupShaderType<MToonMaterialUniform> for MToonMaterial {
             shading_shift_factor: self.shading_shift_factor,
             shading_shift_scale: self.shading_shift_scale,
             shading_toony_factor: self.shading_toony_factor,
+            normal_scale: self.normal_scale,
             gi_equalization_factor: self.gi_equalization_factor,
             matcap_factor: self.matcap_factor,
             parametric_rim_color_factor: self.parametric_rim_color_factor,
             rim_lighting_mix_factor: self.rim_lighting_mix_factor,
             parametric_rim_fresnel_power_factor: self.parametric_rim_fresnel_power_factor,
             parametric_rim_lift_factor: self.parametric_rim_lift_factor,
-            // outline_width_factor: self.outline_width_factor,
-            // outline_color_factor: self.outline_color_factor,
-            // outline_lighting_mix_factor: self.rim_lighting_mix_factor,
             uv_animation_scroll_x_speed_factor: self.uv_animation_scroll_x_speed_factor,
             uv_animation_scroll_y_speed_factor: self.uv_animation_scroll_y_speed_factor,
             uv_animation_rotation_speed_factor: self.uv_animation_rotation_speed_factor,
+            base_color_uv_set: self.uv_sets.base_color,
+            emissive_uv_set: self.uv_sets.emissive,
+            shade_color_uv_set: self.uv_sets.shade_color,
+            shading_shift_uv_set: self.uv_sets.shading_shift,
+            normal_map_uv_set: self.uv_sets.normal_map,
         }
     }
 }
@@ -403,16 +478,103 @@ impl AsBindGroupShaderType<MToonMaterialUniform> for MToonMaterial {
 pub struct MToonMaterialKey {
     cull_mode: Option<Face>,
     depth_bias: i32,
-    // outline_width_mode: OutlineWidthMode,
+    transparent_with_z_write: bool,
+    outline_width_mode: OutlineWidthMode,
 }
 
 impl From<&MToonMaterial> for MToonMaterialKey {
     fn from(material: &MToonMaterial) -> Self {
         MToonMaterialKey {
-            cull_mode: material.cull_mode,
+            cull_mode: material.cull_mode.face(),
             depth_bias: material.depth_bias,
-            // outline_width_mode: material.outline_width_mode,
+            transparent_with_z_write: material.transparent_with_z_write,
+            outline_width_mode: material.outline_width_mode,
+        }
+    }
+}
+
+
+/// An MToon material drawn as an inverted hull outline.
+///
+/// This is drawn as well as the [`MToonMaterial`] on the same mesh, with its
+/// front faces culled and its vertices pushed out along their normals, so
+/// only the rim of the hull shows around the mesh.
+pub type MToonOutlineMaterial = ExtendedMaterial<MToonMaterial, MToonOutline>;
+
+#[derive(Clone, Debug, Reflect, Asset, AsBindGroup)]
+#[uniform(100, MToonOutlineUniform)]
+#[reflect(Debug, Default)]
+pub struct MToonOutline {
+    /// The outline width, in metres for world coordinates, or as a fraction
+    /// of the screen height for screen coordinates.
+    pub width_factor: f32,
+    /// Scales the width by its green channel. Without a texture, the fallback
+    /// image is white and so leaves the width alone.
+    #[texture(101)]
+    #[sampler(102)]
+    pub width_multiply_texture: Option<Handle<Image>>,
+    /// The UV set the width multiply texture is sampled with: 0 for
+    /// `TEXCOORD_0`, or 1 for `TEXCOORD_1`.
+    pub width_multiply_uv_set: u32,
+    pub color_factor: Vec3,
+    /// How much the lit surface colour tints the outline, from none at 0 to
+    /// fully at 1.
+    pub lighting_mix_factor: f32,
+}
+
+impl Default for MToonOutline {
+    fn default() -> Self {
+        Self {
+            width_factor: 0.0,
+            width_multiply_texture: None,
+            width_multiply_uv_set: 0,
+            color_factor: Vec3::ZERO,
+            lighting_mix_factor: 1.0,
+        }
+    }
+}
+
+impl MaterialExtension for MToonOutline {
+    fn vertex_shader() -> ShaderRef {
+        "shaders/mtoon/mtoon_outline.wgsl".into()
+    }
+
+    fn fragment_shader() -> ShaderRef {
+        "shaders/mtoon/mtoon.wgsl".into()
+    }
+
+    fn specialize(
+        _pipeline: &MaterialExtensionPipeline,
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayoutRef,
+        _key: MaterialExtensionKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        descriptor.primitive.cull_mode = Some(Face::Front);
+
+        descriptor.vertex.shader_defs.push("MTOON_OUTLINE".into());
+        if let Some(fragment) = descriptor.fragment.as_mut() {
+            fragment.shader_defs.push("MTOON_OUTLINE".into());
         }
+
+        Ok(())
     }
 }
 
+#[derive(Clone, Default, ShaderType)]
+pub struct MToonOutlineUniform {
+    pub width_factor: f32,
+    pub color_factor: Vec3,
+    pub lighting_mix_factor: f32,
+    pub width_multiply_uv_set: u32,
+}
+
+impl AsBindGroupShaderType<MToonOutlineUniform> for MToonOutline {
+    fn as_bind_group_shader_type(&self, _images: &RenderAssets<GpuImage>) -> MToonOutlineUniform {
+        MToonOutlineUniform {
+            width_factor: self.width_factor,
+            color_factor: self.color_factor,
+            lighting_mix_factor: self.lighting_mix_factor,
+            width_multiply_uv_set: self.width_multiply_uv_set,
+        }
+    }
+}
@@ -0,0 +1,416 @@
+use bevy::ecs::entity::{EntityMapper, MapEntities};
+use bevy::ecs::reflect::ReflectMapEntities;
+use bevy::prelude::*;
+use bevy::reflect::Reflect;
+use bevy::utils::HashSet;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum RollAxisJson {
+    X,
+    Y,
+    Z,
+}
+
+impl RollAxisJson {
+    pub fn to_vec3(self) -> Vec3 {
+        match self {
+            RollAxisJson::X => Vec3::X,
+            RollAxisJson::Y => Vec3::Y,
+            RollAxisJson::Z => Vec3::Z,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum AimAxisJson {
+    PositiveX,
+    NegativeX,
+    PositiveY,
+    NegativeY,
+    PositiveZ,
+    NegativeZ,
+}
+
+impl AimAxisJson {
+    pub fn to_vec3(self) -> Vec3 {
+        match self {
+            AimAxisJson::PositiveX => Vec3::X,
+            AimAxisJson::NegativeX => Vec3::NEG_X,
+            AimAxisJson::PositiveY => Vec3::Y,
+            AimAxisJson::NegativeY => Vec3::NEG_Y,
+            AimAxisJson::PositiveZ => Vec3::Z,
+            AimAxisJson::NegativeZ => Vec3::NEG_Z,
+        }
+    }
+}
+
+fn default_weight() -> f32 {
+    1.
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RollConstraintJson {
+    pub source: u32,
+    pub roll_axis: RollAxisJson,
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AimConstraintJson {
+    pub source: u32,
+    pub aim_axis: AimAxisJson,
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RotationConstraintJson {
+    pub source: u32,
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConstraintJson {
+    pub roll: Option<RollConstraintJson>,
+    pub aim: Option<AimConstraintJson>,
+    pub rotation: Option<RotationConstraintJson>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeConstraintExtensionJson {
+    pub spec_version: String,
+    pub constraint: ConstraintJson,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+pub enum ConstraintKind {
+    /// Copy the source's rotation about `axis`, in the constrained node's
+    /// rest space.
+    Roll { axis: Vec3 },
+    /// Point `axis` (in the constrained node's rest space) at the source.
+    Aim { axis: Vec3 },
+    /// Copy the source's rotation.
+    Rotation,
+}
+
+/// A `VRMC_node_constraint` constraint on a node's rotation.
+///
+/// Rotations are relative to the rest poses of both nodes, which are
+/// captured when the VRM is loaded.
+#[derive(Debug, Clone, Reflect, Component)]
+#[reflect(Debug, Component, MapEntities)]
+pub struct NodeConstraint {
+    pub source: Entity,
+    pub kind: ConstraintKind,
+    pub weight: f32,
+    pub rest: Quat,
+    pub source_rest: Quat,
+}
+
+impl FromWorld for NodeConstraint {
+    fn from_world(_world: &mut World) -> Self {
+        NodeConstraint {
+            source: Entity::PLACEHOLDER,
+            kind: ConstraintKind::Rotation,
+            weight: 1.,
+            rest: Quat::IDENTITY,
+            source_rest: Quat::IDENTITY,
+        }
+    }
+}
+
+impl MapEntities for NodeConstraint {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        self.source = entity_mapper.map_entity(self.source);
+    }
+}
+
+impl NodeConstraint {
+    /// Build the constraint from its JSON, given the source's entity.
+    ///
+    /// The spec allows only one constraint per node, so if several are
+    /// present the first of roll, aim and rotation is used.
+    pub fn from_json(
+        json: &ConstraintJson,
+        source: impl Fn(u32) -> Option<(Entity, Quat)>,
+        rest: Quat,
+    ) -> Option<NodeConstraint> {
+        let (index, kind, weight) = if let Some(roll) = &json.roll {
+            (roll.source, ConstraintKind::Roll { axis: roll.roll_axis.to_vec3() }, roll.weight)
+        } else if let Some(aim) = &json.aim {
+            (aim.source, ConstraintKind::Aim { axis: aim.aim_axis.to_vec3() }, aim.weight)
+        } else if let Some(rotation) = &json.rotation {
+            (rotation.source, ConstraintKind::Rotation, rotation.weight)
+        } else {
+            return None;
+        };
+
+        let (source, source_rest) = source(index)?;
+        Some(NodeConstraint {
+            source,
+            kind,
+            weight,
+            rest,
+            source_rest,
+        })
+    }
+
+    /// The constrained local rotation.
+    ///
+    /// `source_rotation` is the source's local rotation. For aim constraints,
+    /// `parent` is the constrained node's parent world transform, and
+    /// `position` and `source_position` are the world positions of both nodes.
+    pub fn evaluate(
+        &self,
+        source_rotation: Quat,
+        parent_rotation: Quat,
+        position: Vec3,
+        source_position: Vec3,
+    ) -> Quat {
+        let delta = match self.kind {
+            ConstraintKind::Rotation => self.source_rest.inverse() * source_rotation,
+            ConstraintKind::Roll { axis } => {
+                // The source's rotation since rest, in this node's rest space.
+                let source_delta = self.source_rest.inverse() * source_rotation;
+                let delta = self.rest.inverse() * self.source_rest * source_delta
+                    * self.source_rest.inverse() * self.rest;
+                // Keep only the twist about the roll axis.
+                let swing = Quat::from_rotation_arc(axis, (delta * axis).normalize());
+                swing.inverse() * delta
+            }
+            ConstraintKind::Aim { axis } => {
+                let rest_world = parent_rotation * self.rest;
+                let from = rest_world * axis;
+                let Some(to) = (source_position - position).try_normalize() else {
+                    return self.rest;
+                };
+                let aim = Quat::from_rotation_arc(from, to);
+                rest_world.inverse() * aim * rest_world
+            }
+        };
+
+        self.rest * Quat::IDENTITY.slerp(delta, self.weight)
+    }
+}
+
+/// Propagate a changed local transform to an entity and its descendants.
+pub(crate) fn propagate_subtree(
+    entity: Entity,
+    parent: GlobalTransform,
+    transforms: &mut Query<(&mut Transform, &mut GlobalTransform)>,
+    children: &Query<&Children>,
+) {
+    let Ok((transform, mut global)) = transforms.get_mut(entity) else {
+        return;
+    };
+    let new_global = parent.mul_transform(*transform);
+    *global = new_global;
+
+    if let Ok(entity_children) = children.get(entity) {
+        for child in entity_children {
+            propagate_subtree(*child, new_global, transforms, children);
+        }
+    }
+}
+
+/// Order constrained nodes so that each comes after every constrained node
+/// it depends on. Cycles, which the spec forbids, are broken arbitrarily.
+fn evaluation_order(nodes: &[Entity], dependencies: impl Fn(Entity) -> Vec<Entity>) -> Vec<Entity> {
+    fn visit(
+        node: Entity,
+        dependencies: &impl Fn(Entity) -> Vec<Entity>,
+        constrained: &HashSet<Entity>,
+        visited: &mut HashSet<Entity>,
+        order: &mut Vec<Entity>,
+    ) {
+        if !visited.insert(node) {
+            return;
+        }
+        for dependency in dependencies(node) {
+            if constrained.contains(&dependency) {
+                visit(dependency, dependencies, constrained, visited, order);
+            }
+        }
+        order.push(node);
+    }
+
+    let constrained = nodes.iter().copied().collect::<HashSet<_>>();
+    let mut visited = HashSet::default();
+    let mut order = Vec::with_capacity(nodes.len());
+    for &node in nodes {
+        visit(node, &dependencies, &constrained, &mut visited, &mut order);
+    }
+    order
+}
+
+/// Apply node constraints, after transform propagation so that they see the
+/// final pose of their sources, including any look at.
+///
+/// Each constraint is evaluated after the constraints on its source and on
+/// the ancestors of both nodes. The constrained nodes (and their children)
+/// are propagated again, so the result is visible in the same frame and to
+/// the constraints which follow.
+pub fn apply_node_constraints(
+    constraints: Query<(Entity, &NodeConstraint, Option<&Parent>)>,
+    mut transforms: Query<(&mut Transform, &mut GlobalTransform)>,
+    children: Query<&Children>,
+    parents: Query<&Parent>,
+) {
+    let nodes = constraints.iter().map(|(entity, _, _)| entity).collect::<Vec<_>>();
+    let order = evaluation_order(&nodes, |entity| {
+        let source = constraints.get(entity).map_or(Entity::PLACEHOLDER, |(_, c, _)| c.source);
+        parents.iter_ancestors(entity)
+            .chain(std::iter::once(source))
+            .chain(parents.iter_ancestors(source))
+            .collect()
+    });
+
+    for entity in order {
+        let Ok((_, constraint, parent)) = constraints.get(entity) else {
+            continue;
+        };
+        let parent_global = parent
+            .and_then(|p| transforms.get(p.get()).ok())
+            .map_or(GlobalTransform::IDENTITY, |(_, global)| *global);
+        let Ok((source_transform, source_global)) = transforms.get(constraint.source) else {
+            continue;
+        };
+        let source_rotation = source_transform.rotation;
+        let source_position = source_global.translation();
+        let Ok((_, global)) = transforms.get(entity) else {
+            continue;
+        };
+        let position = global.translation();
+
+        let parent_rotation = parent_global.compute_transform().rotation;
+        let rotation = constraint.evaluate(source_rotation, parent_rotation, position, source_position);
+        if let Ok((mut transform, _)) = transforms.get_mut(entity) {
+            transform.rotation = rotation;
+        }
+        propagate_subtree(entity, parent_global, &mut transforms, &children);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(index: u32) -> Entity {
+        Entity::from_raw(index)
+    }
+
+    fn constraint(kind: ConstraintKind, weight: f32, rest: Quat, source_rest: Quat) -> NodeConstraint {
+        NodeConstraint {
+            source: entity(1),
+            kind,
+            weight,
+            rest,
+            source_rest,
+        }
+    }
+
+    fn assert_rotation_eq(actual: Quat, expected: Quat) {
+        assert!(
+            actual.abs_diff_eq(expected, 1e-5) || actual.abs_diff_eq(-expected, 1e-5),
+            "expected {expected:?}, got {actual:?}",
+        );
+    }
+
+    #[test]
+    fn rotation_copies_the_source_since_rest() {
+        let rest = Quat::from_rotation_y(0.3);
+        let source_rest = Quat::from_rotation_x(0.2);
+        let source_rotation = source_rest * Quat::from_rotation_z(1.);
+
+        let full = constraint(ConstraintKind::Rotation, 1., rest, source_rest);
+        assert_rotation_eq(full.evaluate(source_rotation, Quat::IDENTITY, Vec3::ZERO, Vec3::ZERO),
+            rest * Quat::from_rotation_z(1.));
+
+        let half = constraint(ConstraintKind::Rotation, 0.5, rest, source_rest);
+        assert_rotation_eq(half.evaluate(source_rotation, Quat::IDENTITY, Vec3::ZERO, Vec3::ZERO),
+            rest * Quat::from_rotation_z(0.5));
+
+        let none = constraint(ConstraintKind::Rotation, 0., rest, source_rest);
+        assert_rotation_eq(none.evaluate(source_rotation, Quat::IDENTITY, Vec3::ZERO, Vec3::ZERO), rest);
+    }
+
+    #[test]
+    fn roll_keeps_only_the_twist() {
+        // Twist about Y, then swing about X.
+        let source_rotation = Quat::from_rotation_x(0.5) * Quat::from_rotation_y(0.8);
+        let kind = ConstraintKind::Roll { axis: Vec3::Y };
+
+        let full = constraint(kind, 1., Quat::IDENTITY, Quat::IDENTITY);
+        assert_rotation_eq(full.evaluate(source_rotation, Quat::IDENTITY, Vec3::ZERO, Vec3::ZERO),
+            Quat::from_rotation_y(0.8));
+
+        let half = constraint(kind, 0.5, Quat::IDENTITY, Quat::IDENTITY);
+        assert_rotation_eq(half.evaluate(source_rotation, Quat::IDENTITY, Vec3::ZERO, Vec3::ZERO),
+            Quat::from_rotation_y(0.4));
+    }
+
+    #[test]
+    fn roll_axis_is_in_the_constrained_rest_space() {
+        // Turning the rest a quarter about Z maps the source's Y onto the
+        // constrained node's X.
+        let rest = Quat::from_rotation_z(std::f32::consts::FRAC_PI_2);
+        let roll = constraint(ConstraintKind::Roll { axis: Vec3::X }, 1., rest, Quat::IDENTITY);
+        assert_rotation_eq(roll.evaluate(Quat::from_rotation_y(0.6), Quat::IDENTITY, Vec3::ZERO, Vec3::ZERO),
+            rest * Quat::from_rotation_x(0.6));
+    }
+
+    #[test]
+    fn aim_points_the_axis_at_the_source() {
+        use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+
+        let kind = ConstraintKind::Aim { axis: Vec3::Z };
+        let full = constraint(kind, 1., Quat::IDENTITY, Quat::IDENTITY);
+        assert_rotation_eq(full.evaluate(Quat::IDENTITY, Quat::IDENTITY, Vec3::ZERO, Vec3::X),
+            Quat::from_rotation_y(FRAC_PI_2));
+
+        let half = constraint(kind, 0.5, Quat::IDENTITY, Quat::IDENTITY);
+        assert_rotation_eq(half.evaluate(Quat::IDENTITY, Quat::IDENTITY, Vec3::ZERO, Vec3::X),
+            Quat::from_rotation_y(FRAC_PI_4));
+
+        // The parent already turns the axis to face +X, so facing -Z is only
+        // another quarter turn locally.
+        let parent = Quat::from_rotation_y(FRAC_PI_2);
+        assert_rotation_eq(full.evaluate(Quat::IDENTITY, parent, Vec3::ONE, Vec3::ONE - Vec3::Z),
+            Quat::from_rotation_y(FRAC_PI_2));
+    }
+
+    #[test]
+    fn aim_at_coincident_source_keeps_rest() {
+        let rest = Quat::from_rotation_x(0.4);
+        let aim = constraint(ConstraintKind::Aim { axis: Vec3::Z }, 1., rest, Quat::IDENTITY);
+        assert_rotation_eq(aim.evaluate(Quat::IDENTITY, Quat::IDENTITY, Vec3::ONE, Vec3::ONE), rest);
+    }
+
+    #[test]
+    fn sources_and_ancestors_are_evaluated_first() {
+        // 0 is constrained by 1, whose parent 2 is constrained by 3.
+        let dependencies = |node: Entity| match node.index() {
+            0 => vec![entity(1), entity(2)],
+            1 => vec![entity(2)],
+            2 => vec![entity(3)],
+            _ => vec![],
+        };
+        let order = evaluation_order(&[entity(0), entity(1), entity(2)], dependencies);
+        assert_eq!(order, vec![entity(2), entity(1), entity(0)]);
+    }
+
+    #[test]
+    fn cycles_are_broken() {
+        let dependencies = |node: Entity| vec![entity(1 - node.index())];
+        let order = evaluation_order(&[entity(0), entity(1)], dependencies);
+        assert_eq!(order, vec![entity(1), entity(0)]);
+    }
+}
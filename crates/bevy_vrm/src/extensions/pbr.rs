@@ -0,0 +1,89 @@
+use bevy::math::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::extensions::TextureInfo;
+
+/// Reflectance of a dielectric with an index of refraction of 1.5, as used
+/// by glTF when there is no `KHR_materials_ior`.
+const DEFAULT_F0: f32 = 0.04;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SpecularExtensionJson {
+    pub specular_factor: f32,
+    pub specular_texture: Option<TextureInfo>,
+    pub specular_color_factor: Vec3,
+    pub specular_color_texture: Option<TextureInfo>,
+}
+
+impl Default for SpecularExtensionJson {
+    fn default() -> Self {
+        Self {
+            specular_factor: 1.,
+            specular_texture: None,
+            specular_color_factor: Vec3::ONE,
+            specular_color_texture: None,
+        }
+    }
+}
+
+impl SpecularExtensionJson {
+    /// Bevy's `reflectance`, which sets the dielectric F0 as
+    /// `0.16 * reflectance²`.
+    ///
+    /// Bevy has no specular tint, so the strongest channel of the specular
+    /// colour is used.
+    pub fn reflectance(&self) -> f32 {
+        let f0 = DEFAULT_F0 * self.specular_factor * self.specular_color_factor.max_element();
+        (f0 / 0.16).max(0.).sqrt()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ClearcoatExtensionJson {
+    pub clearcoat_factor: f32,
+    pub clearcoat_texture: Option<TextureInfo>,
+    pub clearcoat_roughness_factor: f32,
+    pub clearcoat_roughness_texture: Option<TextureInfo>,
+    pub clearcoat_normal_texture: Option<TextureInfo>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_specular() {
+        let json = r#"{
+            "specularFactor": 0.5,
+            "specularColorFactor": [1.0, 0.5, 0.25],
+            "specularTexture": {"index": 3}
+        }"#;
+        let specular: SpecularExtensionJson = serde_json::from_str(json).unwrap();
+        assert_eq!(specular.specular_factor, 0.5);
+        assert_eq!(specular.specular_color_factor, Vec3::new(1., 0.5, 0.25));
+        assert_eq!(specular.specular_texture.unwrap().index, 3);
+        assert!((specular.reflectance() - 0.5f32.sqrt() * 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn default_specular_matches_bevy() {
+        let specular: SpecularExtensionJson = serde_json::from_str("{}").unwrap();
+        assert!((specular.reflectance() - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn parses_clearcoat() {
+        let json = r#"{
+            "clearcoatFactor": 1.0,
+            "clearcoatRoughnessFactor": 0.2,
+            "clearcoatNormalTexture": {"index": 1, "texCoord": 1}
+        }"#;
+        let clearcoat: ClearcoatExtensionJson = serde_json::from_str(json).unwrap();
+        assert_eq!(clearcoat.clearcoat_factor, 1.);
+        assert_eq!(clearcoat.clearcoat_roughness_factor, 0.2);
+        assert_eq!(clearcoat.clearcoat_normal_texture.unwrap().tex_coord, 1);
+        assert!(clearcoat.clearcoat_texture.is_none());
+    }
+}
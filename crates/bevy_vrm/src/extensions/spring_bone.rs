@@ -0,0 +1,387 @@
+use bevy::ecs::entity::{EntityMapper, MapEntities};
+use bevy::ecs::reflect::ReflectMapEntities;
+use bevy::prelude::*;
+use bevy::reflect::Reflect;
+use serde::{Deserialize, Serialize};
+
+use crate::extensions::node_constraint::propagate_subtree;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SphereShapeJson {
+    #[serde(default)]
+    pub offset: Vec3,
+    #[serde(default)]
+    pub radius: f32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CapsuleShapeJson {
+    #[serde(default)]
+    pub offset: Vec3,
+    #[serde(default)]
+    pub radius: f32,
+    #[serde(default)]
+    pub tail: Vec3,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColliderShapeJson {
+    pub sphere: Option<SphereShapeJson>,
+    pub capsule: Option<CapsuleShapeJson>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColliderJson {
+    pub node: u32,
+    pub shape: ColliderShapeJson,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColliderGroupJson {
+    #[serde(default)]
+    pub name: String,
+    pub colliders: Vec<u32>,
+}
+
+fn default_stiffness() -> f32 {
+    1.
+}
+
+fn default_gravity_dir() -> Vec3 {
+    Vec3::NEG_Y
+}
+
+fn default_drag_force() -> f32 {
+    0.5
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpringJointJson {
+    pub node: u32,
+    #[serde(default)]
+    pub hit_radius: f32,
+    #[serde(default = "default_stiffness")]
+    pub stiffness: f32,
+    #[serde(default)]
+    pub gravity_power: f32,
+    #[serde(default = "default_gravity_dir")]
+    pub gravity_dir: Vec3,
+    #[serde(default = "default_drag_force")]
+    pub drag_force: f32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpringJson {
+    #[serde(default)]
+    pub name: String,
+    pub joints: Vec<SpringJointJson>,
+    #[serde(default)]
+    pub collider_groups: Vec<u32>,
+    pub center: Option<u32>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpringBoneExtensionJson {
+    pub spec_version: String,
+    #[serde(default)]
+    pub colliders: Vec<ColliderJson>,
+    #[serde(default)]
+    pub collider_groups: Vec<ColliderGroupJson>,
+    #[serde(default)]
+    pub springs: Vec<SpringJson>,
+}
+
+/// A sphere which spring bones are pushed out of, centred on its entity.
+///
+/// The radius is in the entity's space, so it grows and shrinks with the
+/// avatar's scale.
+#[derive(Debug, Clone, Copy, Default, Reflect, Component)]
+#[reflect(Debug, Component)]
+pub struct SpringBoneCollider {
+    pub radius: f32,
+}
+
+/// The simulated position of a spring bone's tail, in world space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpringBoneState {
+    pub current_tail: Vec3,
+    pub previous_tail: Vec3,
+}
+
+impl SpringBoneState {
+    pub fn at_rest(tail: Vec3) -> SpringBoneState {
+        SpringBoneState {
+            current_tail: tail,
+            previous_tail: tail,
+        }
+    }
+}
+
+/// A `VRMC_springBone` joint, which swings its entity so that the next joint
+/// in the chain (its tail) trails behind with inertia, springs back to its
+/// rest pose and sags under gravity.
+///
+/// The simulation runs in world space. The spring's `center` node, which
+/// would let the avatar move without dragging its hair, is not supported.
+#[derive(Debug, Clone, Reflect, Component)]
+#[reflect(Debug, Component, MapEntities)]
+pub struct SpringBone {
+    pub tail: Entity,
+    /// How strongly the bone returns to its rest pose.
+    pub stiffness: f32,
+    /// How much of the tail's velocity is lost each step, from 0 to 1.
+    pub drag_force: f32,
+    pub gravity_dir: Vec3,
+    pub gravity_power: f32,
+    /// Radius of the tail, for collisions, in the bone's space.
+    pub hit_radius: f32,
+    /// The entities with a [`SpringBoneCollider`] which the tail avoids.
+    pub colliders: Vec<Entity>,
+    /// Local rotation at rest.
+    pub rest: Quat,
+    /// Direction of the tail at rest, in the bone's local space.
+    pub axis: Vec3,
+    #[reflect(ignore)]
+    pub state: Option<SpringBoneState>,
+}
+
+impl FromWorld for SpringBone {
+    fn from_world(_world: &mut World) -> Self {
+        SpringBone {
+            tail: Entity::PLACEHOLDER,
+            stiffness: default_stiffness(),
+            drag_force: default_drag_force(),
+            gravity_dir: default_gravity_dir(),
+            gravity_power: 0.,
+            hit_radius: 0.,
+            colliders: Vec::new(),
+            rest: Quat::IDENTITY,
+            axis: Vec3::Y,
+            state: None,
+        }
+    }
+}
+
+impl MapEntities for SpringBone {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        self.tail = entity_mapper.map_entity(self.tail);
+        for collider in &mut self.colliders {
+            *collider = entity_mapper.map_entity(*collider);
+        }
+    }
+}
+
+impl SpringBone {
+    /// Build a joint from its JSON, given the rest transforms of the joint
+    /// and its tail, relative to the joint's parent and to the joint.
+    pub fn from_json(
+        json: &SpringJointJson,
+        tail: Entity,
+        rest: Quat,
+        tail_translation: Vec3,
+        colliders: Vec<Entity>,
+    ) -> SpringBone {
+        SpringBone {
+            tail,
+            stiffness: json.stiffness,
+            drag_force: json.drag_force,
+            gravity_dir: json.gravity_dir.try_normalize().unwrap_or(Vec3::NEG_Y),
+            gravity_power: json.gravity_power,
+            hit_radius: json.hit_radius,
+            colliders,
+            rest,
+            axis: tail_translation.try_normalize().unwrap_or(Vec3::Y),
+            state: None,
+        }
+    }
+
+    /// Advance the tail by one verlet step of `dt` seconds, and return the
+    /// new state and the local rotation which points the bone at it.
+    ///
+    /// `head` is the bone's world position, `parent_rotation` its parent's
+    /// world rotation, `length` the distance to the tail and `colliders` the
+    /// world positions of its colliders, with the world distances the tail
+    /// is kept from them (the collider's radius plus the tail's).
+    pub fn step(
+        &self,
+        state: SpringBoneState,
+        head: Vec3,
+        parent_rotation: Quat,
+        length: f32,
+        colliders: &[(Vec3, f32)],
+        dt: f32,
+    ) -> (SpringBoneState, Quat) {
+        let rest_world = parent_rotation * self.rest;
+        let inertia = (state.current_tail - state.previous_tail) * (1. - self.drag_force.clamp(0., 1.));
+        let stiffness = rest_world * self.axis * self.stiffness * dt;
+        let gravity = self.gravity_dir * self.gravity_power * dt;
+        let mut next_tail = state.current_tail + inertia + stiffness + gravity;
+
+        let constrain_length = |tail: Vec3| {
+            head + (tail - head).try_normalize().unwrap_or(rest_world * self.axis) * length
+        };
+        next_tail = constrain_length(next_tail);
+
+        for &(center, min_distance) in colliders {
+            let offset = next_tail - center;
+            if offset.length_squared() < min_distance * min_distance {
+                let direction = offset.try_normalize().unwrap_or(rest_world * self.axis);
+                next_tail = constrain_length(center + direction * min_distance);
+            }
+        }
+
+        let to = rest_world.inverse() * (next_tail - head);
+        let rotation = match to.try_normalize() {
+            Some(to) => self.rest * Quat::from_rotation_arc(self.axis, to),
+            None => self.rest,
+        };
+
+        let state = SpringBoneState {
+            current_tail: next_tail,
+            previous_tail: state.current_tail,
+        };
+        (state, rotation)
+    }
+}
+
+/// The longest time simulated in one step. Longer frames, such as while an
+/// avatar loads or the window is dragged, are slowed down to this, as the
+/// whole of their stiffness and gravity would otherwise snap the bones.
+pub const MAX_SPRING_BONE_STEP: f32 = 1. / 30.;
+
+/// The scale of a transform's space, taking the largest axis for non-uniform
+/// scales.
+fn world_scale(global: &GlobalTransform) -> f32 {
+    global.compute_transform().scale.abs().max_element()
+}
+
+/// Simulate spring bones, after transform propagation and node constraints
+/// so that they follow the final pose of the body.
+///
+/// Bones are stepped from the root of each chain outwards, and each one's
+/// subtree is propagated again so the next bone sees where it moved.
+pub fn update_spring_bones(
+    time: Res<Time>,
+    mut bones: Query<(Entity, &mut SpringBone, Option<&Parent>)>,
+    colliders: Query<&SpringBoneCollider>,
+    mut transforms: Query<(&mut Transform, &mut GlobalTransform)>,
+    children: Query<&Children>,
+    parents: Query<&Parent>,
+) {
+    let dt = time.delta_seconds().min(MAX_SPRING_BONE_STEP);
+    if dt <= 0. {
+        return;
+    }
+
+    let mut order = bones.iter()
+        .map(|(entity, _, _)| (parents.iter_ancestors(entity).count(), entity))
+        .collect::<Vec<_>>();
+    order.sort_unstable();
+
+    let mut bone_colliders = Vec::new();
+    for (_, entity) in order {
+        let Ok((_, mut bone, parent)) = bones.get_mut(entity) else {
+            continue;
+        };
+        let parent_global = parent
+            .and_then(|p| transforms.get(p.get()).ok())
+            .map_or(GlobalTransform::IDENTITY, |(_, global)| *global);
+        let (Ok((_, global)), Ok((_, tail_global))) = (transforms.get(entity), transforms.get(bone.tail)) else {
+            continue;
+        };
+        let head = global.translation();
+        let tail = tail_global.translation();
+        let length = head.distance(tail);
+        let hit_radius = bone.hit_radius * world_scale(global);
+
+        bone_colliders.clear();
+        bone_colliders.extend(bone.colliders.iter()
+            .filter_map(|entity| {
+                let collider = colliders.get(*entity).ok()?;
+                let (_, global) = transforms.get(*entity).ok()?;
+                Some((global.translation(), collider.radius * world_scale(global) + hit_radius))
+            }));
+
+        let state = bone.state.unwrap_or(SpringBoneState::at_rest(tail));
+        let parent_rotation = parent_global.compute_transform().rotation;
+        let (state, rotation) = bone.step(state, head, parent_rotation, length, &bone_colliders, dt);
+        bone.state = Some(state);
+
+        if let Ok((mut transform, _)) = transforms.get_mut(entity) {
+            transform.rotation = rotation;
+        }
+        propagate_subtree(entity, parent_global, &mut transforms, &children);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn bone() -> SpringBone {
+        SpringBone {
+            axis: Vec3::NEG_Y,
+            ..SpringBone::from_world(&mut World::new())
+        }
+    }
+
+    #[test]
+    fn joint_defaults_follow_the_spec() {
+        let json: SpringJointJson = serde_json::from_str(r#"{"node": 3}"#).unwrap();
+        assert_eq!(json.stiffness, 1.);
+        assert_eq!(json.drag_force, 0.5);
+        assert_eq!(json.gravity_power, 0.);
+        assert_eq!(json.gravity_dir, Vec3::NEG_Y);
+        assert_eq!(json.hit_radius, 0.);
+    }
+
+    #[test]
+    fn bone_at_rest_stays_at_rest() {
+        let bone = bone();
+        let tail = Vec3::new(0., -1., 0.);
+        let (state, rotation) = bone.step(SpringBoneState::at_rest(tail), Vec3::ZERO, Quat::IDENTITY, 1., &[], 0.016);
+        assert!(state.current_tail.abs_diff_eq(tail, EPSILON), "tail moved to {}", state.current_tail);
+        assert!(rotation.abs_diff_eq(Quat::IDENTITY, EPSILON));
+    }
+
+    #[test]
+    fn tail_keeps_its_length() {
+        let bone = SpringBone {
+            gravity_dir: Vec3::X,
+            gravity_power: 100.,
+            ..bone()
+        };
+        let state = SpringBoneState::at_rest(Vec3::new(0., -2., 0.));
+        let (state, _) = bone.step(state, Vec3::ZERO, Quat::IDENTITY, 2., &[], 0.016);
+        assert!((state.current_tail.length() - 2.).abs() < EPSILON);
+        assert!(state.current_tail.x > 0.);
+    }
+
+    #[test]
+    fn tail_is_pushed_out_of_colliders() {
+        let bone = bone();
+        let state = SpringBoneState::at_rest(Vec3::new(0., -1., 0.));
+        // A collider of radius 0.3 and a tail of radius 0.1.
+        let collider = (Vec3::new(0.2, -1., 0.), 0.4);
+        let (state, _) = bone.step(state, Vec3::ZERO, Quat::IDENTITY, 1., &[collider], 0.016);
+        // Pushed away from the collider, then back onto the bone's length.
+        assert!(state.current_tail.x < -0.1, "tail at {}", state.current_tail);
+        assert!((state.current_tail.length() - 1.).abs() < EPSILON);
+    }
+
+    #[test]
+    fn world_scale_takes_the_largest_axis() {
+        let global = GlobalTransform::from(Transform::from_scale(Vec3::new(2., 3., 1.)));
+        assert!((world_scale(&global) - 3.).abs() < EPSILON);
+    }
+}
@@ -121,12 +121,153 @@ pub struct LookAtJson {
     pub range_map_vertical_up: RangeMapJson,
 }
 
+impl Default for LookAtJson {
+    /// Bone look-at with the range maps the VRM 1.0 spec defaults to, for
+    /// avatars exported without any look-at metadata.
+    fn default() -> Self {
+        let range_map = RangeMapJson {
+            input_max_value: 90.,
+            output_scale: 10.,
+        };
+        LookAtJson {
+            mode: LookAtModeJson::Bone,
+            // Roughly eye height above the head bone.
+            offset_from_head_bone: Vec3::new(0., 0.06, 0.),
+            range_map_horizontal_inner: range_map.clone(),
+            range_map_horizontal_outer: range_map.clone(),
+            range_map_vertical_down: range_map.clone(),
+            range_map_vertical_up: range_map,
+        }
+    }
+}
+
+/// Who may use the avatar as themselves.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[reflect(Debug, Default)]
+pub enum AvatarPermission {
+    #[default]
+    OnlyAuthor,
+    OnlySeparatelyLicensedPerson,
+    Everyone,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[reflect(Debug, Default)]
+pub enum CommercialUsage {
+    #[default]
+    PersonalNonProfit,
+    PersonalProfit,
+    Corporation,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[reflect(Debug, Default)]
+pub enum CreditNotation {
+    #[default]
+    Required,
+    Unnecessary,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[reflect(Debug, Default)]
+pub enum Modification {
+    #[default]
+    Prohibited,
+    AllowModification,
+    AllowModificationRedistribution,
+}
+
+/// The `meta` of `VRMC_vrm`. Missing permissions take the spec's defaults,
+/// which are the most restrictive.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct MetaJson {
+    pub name: String,
+    pub version: Option<String>,
+    pub authors: Vec<String>,
+    pub copyright_information: Option<String>,
+    pub contact_information: Option<String>,
+    pub references: Vec<String>,
+    pub third_party_licenses: Option<String>,
+    /// Index of the thumbnail in the glTF images.
+    pub thumbnail_image: Option<usize>,
+    pub license_url: String,
+    pub avatar_permission: AvatarPermission,
+    pub allow_excessively_violent_usage: bool,
+    pub allow_excessively_sexual_usage: bool,
+    pub commercial_usage: CommercialUsage,
+    pub allow_political_or_religious_usage: bool,
+    pub allow_antisocial_or_hate_usage: bool,
+    pub credit_notation: CreditNotation,
+    pub allow_redistribution: bool,
+    pub modification: Modification,
+    pub other_license_url: Option<String>,
+}
+
+/// An avatar's attribution and license, which applications are expected to
+/// show before the avatar is used.
+#[derive(Debug, Clone, Default, PartialEq, Reflect)]
+#[reflect(Debug, Default)]
+pub struct VrmMeta {
+    pub title: String,
+    pub version: Option<String>,
+    pub authors: Vec<String>,
+    pub copyright_information: Option<String>,
+    pub contact_information: Option<String>,
+    pub references: Vec<String>,
+    pub third_party_licenses: Option<String>,
+    pub license_url: String,
+    pub other_license_url: Option<String>,
+    /// Who may use the avatar, `allowedUserName` in VRM 0.x.
+    pub avatar_permission: AvatarPermission,
+    pub commercial_usage: CommercialUsage,
+    pub credit_notation: CreditNotation,
+    pub allow_redistribution: bool,
+    pub modification: Modification,
+    pub allow_excessively_violent_usage: bool,
+    pub allow_excessively_sexual_usage: bool,
+    pub allow_political_or_religious_usage: bool,
+    pub allow_antisocial_or_hate_usage: bool,
+}
+
+impl From<&MetaJson> for VrmMeta {
+    fn from(json: &MetaJson) -> Self {
+        VrmMeta {
+            title: json.name.clone(),
+            version: json.version.clone(),
+            authors: json.authors.clone(),
+            copyright_information: json.copyright_information.clone(),
+            contact_information: json.contact_information.clone(),
+            references: json.references.clone(),
+            third_party_licenses: json.third_party_licenses.clone(),
+            license_url: json.license_url.clone(),
+            other_license_url: json.other_license_url.clone(),
+            avatar_permission: json.avatar_permission,
+            commercial_usage: json.commercial_usage,
+            credit_notation: json.credit_notation,
+            allow_redistribution: json.allow_redistribution,
+            modification: json.modification,
+            allow_excessively_violent_usage: json.allow_excessively_violent_usage,
+            allow_excessively_sexual_usage: json.allow_excessively_sexual_usage,
+            allow_political_or_religious_usage: json.allow_political_or_religious_usage,
+            allow_antisocial_or_hate_usage: json.allow_antisocial_or_hate_usage,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct VrmExtensionJson {
     pub spec_version: String,
+    #[serde(default)]
+    pub meta: Option<MetaJson>,
     pub humanoid: HumanoidJson,
-    pub look_at: LookAtJson,
+    #[serde(default)]
+    pub look_at: Option<LookAtJson>,
 }
 
 #[derive(Debug, Clone, Default, Reflect, Component)]
@@ -234,6 +375,32 @@ impl MapEntities for LookAtTarget {
     }
 }
 
+/// The entity which the eyes of a humanoid look at.
+///
+/// By default this is parented to a point in front of the head, but it can be
+/// reparented to aim the gaze elsewhere.
+#[derive(Debug, Clone, Reflect, Component)]
+#[reflect(Debug, Component, MapEntities)]
+pub struct LookAtTargetRoot {
+    pub default_parent: Entity,
+    pub default_transform: Transform,
+}
+
+impl FromWorld for LookAtTargetRoot {
+    fn from_world(_world: &mut World) -> Self {
+        LookAtTargetRoot {
+            default_parent: Entity::PLACEHOLDER,
+            default_transform: Transform::default(),
+        }
+    }
+}
+
+impl MapEntities for LookAtTargetRoot {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        self.default_parent = entity_mapper.map_entity(self.default_parent);
+    }
+}
+
 #[derive(Debug, Clone, Reflect, Component)]
 #[reflect(Debug, Component)]
 pub struct TransformLookAt {
@@ -310,3 +477,154 @@ pub fn apply_transform_look_at(
         *global_transform = parent_transform * *local_transform;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-5;
+
+    fn assert_vec2_eq(actual: Vec2, expected: Vec2) {
+        assert!(
+            actual.abs_diff_eq(expected, EPSILON),
+            "expected {expected}, got {actual}",
+        );
+    }
+
+    fn asymmetric_range_map() -> LookAtRangeMap {
+        LookAtRangeMap {
+            input_scale: Vec4::new(0.5, 1.0, 0.25, 2.0),
+            output_scale: Vec4::new(2.0, 0.5, 3.0, 0.75),
+        }
+    }
+
+    fn range_map_json(input_max_value: f32, output_scale: f32) -> RangeMapJson {
+        RangeMapJson {
+            input_max_value,
+            output_scale,
+        }
+    }
+
+    #[test]
+    fn evaluate_straight_ahead_is_zero() {
+        let range_map = asymmetric_range_map();
+        assert_vec2_eq(range_map.evaluate(Vec3::Z), Vec2::ZERO);
+        assert_vec2_eq(LookAtRangeMap::default().evaluate(Vec3::Z * 10.), Vec2::ZERO);
+    }
+
+    #[test]
+    fn evaluate_sides() {
+        let range_map = LookAtRangeMap::default();
+        let angle = 0.5f32.atan();
+        assert_vec2_eq(range_map.evaluate(Vec3::new(0.5, 0., 1.)), Vec2::new(-angle, 0.));
+        assert_vec2_eq(range_map.evaluate(Vec3::new(-0.5, 0., 1.)), Vec2::new(angle, 0.));
+    }
+
+    #[test]
+    fn evaluate_up_and_down() {
+        let range_map = LookAtRangeMap::default();
+        let angle = 0.5f32.atan();
+        assert_vec2_eq(range_map.evaluate(Vec3::new(0., 0.5, 1.)), Vec2::new(0., -angle));
+        assert_vec2_eq(range_map.evaluate(Vec3::new(0., -0.5, 1.)), Vec2::new(0., angle));
+    }
+
+    #[test]
+    fn evaluate_clamps_to_input_scale() {
+        let range_map = asymmetric_range_map();
+        // atan(10) is about 1.47: past the inner, outer and down limits, which
+        // clamp it, but inside the up limit of 2, which passes it through.
+        assert_vec2_eq(range_map.evaluate(Vec3::new(-10., 0., 1.)), Vec2::new(0.5 * 2.0, 0.));
+        assert_vec2_eq(range_map.evaluate(Vec3::new(10., 0., 1.)), Vec2::new(-1.0 * 0.5, 0.));
+        assert_vec2_eq(range_map.evaluate(Vec3::new(0., -10., 1.)), Vec2::new(0., 0.25 * 3.0));
+        assert_vec2_eq(range_map.evaluate(Vec3::new(0., 10., 1.)), Vec2::new(0., -10f32.atan() * 0.75));
+    }
+
+    #[test]
+    fn flipped_mirrors_horizontally() {
+        let range_map = asymmetric_range_map();
+        let flipped = range_map.flipped();
+        for target in [
+            Vec3::new(0.3, 0.2, 1.),
+            Vec3::new(-0.7, -0.1, 1.),
+            Vec3::new(4., 0.5, 1.),
+        ] {
+            let expected = range_map.evaluate(target);
+            let mirrored = flipped.evaluate(target * Vec3::new(-1., 1., 1.));
+            assert_vec2_eq(mirrored, Vec2::new(-expected.x, expected.y));
+        }
+    }
+
+    #[test]
+    fn flipped_twice_is_identity() {
+        let range_map = asymmetric_range_map();
+        let flipped = range_map.flipped().flipped();
+        assert_eq!(flipped.input_scale, range_map.input_scale);
+        assert_eq!(flipped.output_scale, range_map.output_scale);
+    }
+
+    #[test]
+    fn evaluate_both_matches_flipped() {
+        let range_map = asymmetric_range_map();
+        let target = Vec3::new(0.4, -0.3, 1.);
+        let (left, right) = range_map.evaluate_both(target);
+        assert_vec2_eq(left, range_map.evaluate(target));
+        assert_vec2_eq(right, range_map.flipped().evaluate(target));
+    }
+
+    #[test]
+    fn missing_look_at_is_none() {
+        let json = r#"{
+            "specVersion": "1.0",
+            "humanoid": {"humanBones": {}}
+        }"#;
+        let vrm: VrmExtensionJson = serde_json::from_str(json).unwrap();
+        assert!(vrm.look_at.is_none());
+    }
+
+    #[test]
+    fn default_look_at_is_bone() {
+        let json = LookAtJson::default();
+        assert_eq!(json.mode, LookAtModeJson::Bone);
+        let range_map = LookAtRangeMap::from(&json);
+        assert_eq!(range_map.input_scale, Vec4::splat(90.));
+        assert!(range_map.output_scale.abs_diff_eq(Vec4::splat(10. / 90.), EPSILON));
+    }
+
+    #[test]
+    fn from_look_at_json() {
+        let json = LookAtJson {
+            mode: LookAtModeJson::Bone,
+            offset_from_head_bone: Vec3::ZERO,
+            range_map_horizontal_inner: range_map_json(90., 10.),
+            range_map_horizontal_outer: range_map_json(80., 12.),
+            range_map_vertical_down: range_map_json(70., 7.),
+            range_map_vertical_up: range_map_json(60., 6.),
+        };
+        let range_map = LookAtRangeMap::from(&json);
+
+        // Inputs are stored as-is, outputs become a ratio of output to input.
+        assert_eq!(range_map.input_scale, Vec4::new(90., 80., 70., 60.));
+        assert!(range_map.output_scale.abs_diff_eq(
+            Vec4::new(10. / 90., 12. / 80., 7. / 70., 6. / 60.),
+            EPSILON,
+        ));
+    }
+
+    #[test]
+    fn meta_defaults_to_most_restrictive() {
+        let json: MetaJson = serde_json::from_str(r#"{
+            "name": "Demo",
+            "authors": ["Someone"],
+            "licenseUrl": "https://vrm.dev/licenses/1.0/",
+            "commercialUsage": "personalProfit"
+        }"#).unwrap();
+        let meta = VrmMeta::from(&json);
+        assert_eq!(meta.title, "Demo");
+        assert_eq!(meta.authors, vec!["Someone".to_string()]);
+        assert_eq!(meta.commercial_usage, CommercialUsage::PersonalProfit);
+        assert_eq!(meta.avatar_permission, AvatarPermission::OnlyAuthor);
+        assert_eq!(meta.credit_notation, CreditNotation::Required);
+        assert_eq!(meta.modification, Modification::Prohibited);
+        assert!(!meta.allow_redistribution);
+    }
+}
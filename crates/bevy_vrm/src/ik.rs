@@ -0,0 +1,155 @@
+//! Analytic inverse kinematics.
+
+use bevy::math::{Quat, Vec3};
+use bevy::prelude::Transform;
+
+// Keeps the chain from fully straightening or folding, where the solve
+// becomes unstable.
+const EPSILON: f32 = 1e-3;
+
+fn angle_between(a: Vec3, b: Vec3) -> f32 {
+    a.normalize_or_zero().dot(b.normalize_or_zero()).clamp(-1., 1.).acos()
+}
+
+/// The result of solving a two bone chain.
+///
+/// Both rotations are world-space deltas, pre-multiplied onto the bones'
+/// current world rotations.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TwoBoneSolution {
+    pub root: Quat,
+    pub mid: Quat,
+}
+
+impl TwoBoneSolution {
+    /// Apply the solution to the local transforms of the root and middle
+    /// bones, given their world rotations before solving.
+    pub fn apply(
+        &self,
+        root: &mut Transform,
+        root_world: Quat,
+        mid: &mut Transform,
+        mid_world: Quat,
+    ) {
+        root.rotation = root.rotation * root_world.inverse() * self.root * root_world;
+        mid.rotation = mid.rotation * mid_world.inverse() * self.mid * mid_world;
+    }
+}
+
+/// Solve a two bone chain with joints at `a` (root), `b` (middle) and `c`
+/// (end) so that `c` reaches `target`, with the middle joint bending towards
+/// `pole`.
+///
+/// The interior angle at the middle joint is kept at or above
+/// `min_mid_angle` (in radians). Unreachable targets straighten the chain
+/// towards the target.
+///
+/// This uses the method described at
+/// <https://theorangeduck.com/page/simple-two-joint>.
+pub fn solve_two_bone(
+    a: Vec3,
+    b: Vec3,
+    c: Vec3,
+    target: Vec3,
+    pole: Vec3,
+    min_mid_angle: f32,
+) -> TwoBoneSolution {
+    let lab = (b - a).length();
+    let lcb = (b - c).length();
+    let lat = (target - a).length().clamp(EPSILON, (lab + lcb - EPSILON).max(EPSILON));
+
+    let ac_ab_0 = angle_between(c - a, b - a);
+    let ba_bc_0 = angle_between(a - b, c - b);
+    let ac_at_0 = angle_between(c - a, target - a);
+
+    let ac_ab_1 = ((lcb * lcb - lab * lab - lat * lat) / (-2. * lab * lat))
+        .clamp(-1., 1.).acos();
+    let ba_bc_1 = ((lat * lat - lab * lab - lcb * lcb) / (-2. * lab * lcb))
+        .clamp(-1., 1.).acos()
+        .max(min_mid_angle);
+
+    // Bend in the chain's current plane, or towards the pole if the chain is
+    // straight.
+    let axis0 = (c - a).cross(b - a).try_normalize()
+        .or_else(|| (c - a).cross(pole - a).try_normalize())
+        .unwrap_or(Vec3::X);
+    let axis1 = (c - a).cross(target - a).try_normalize()
+        .unwrap_or(axis0);
+
+    let r0 = Quat::from_axis_angle(axis0, ac_ab_1 - ac_ab_0);
+    let r1 = Quat::from_axis_angle(axis0, ba_bc_1 - ba_bc_0);
+    let r2 = Quat::from_axis_angle(axis1, ac_at_0);
+    let root = r2 * r0;
+
+    // Twist the whole chain around the root-target axis so that the middle
+    // joint points towards the pole.
+    let twist = match (target - a).try_normalize() {
+        Some(n) => {
+            let bend = root * (b - a);
+            let bend = bend - n * bend.dot(n);
+            let hint = pole - a;
+            let hint = hint - n * hint.dot(n);
+            if bend.length_squared() > EPSILON * EPSILON && hint.length_squared() > EPSILON * EPSILON {
+                let angle = angle_between(bend, hint);
+                let sign = n.dot(bend.cross(hint)).signum();
+                Quat::from_axis_angle(n, angle * sign)
+            } else {
+                Quat::IDENTITY
+            }
+        }
+        None => Quat::IDENTITY,
+    };
+
+    TwoBoneSolution {
+        root: twist * root,
+        mid: r1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solve_positions(a: Vec3, b: Vec3, c: Vec3, target: Vec3, pole: Vec3) -> (Vec3, Vec3) {
+        let solution = solve_two_bone(a, b, c, target, pole, 0.);
+        let new_b = a + solution.root * (b - a);
+        let new_c = new_b + solution.root * solution.mid * (c - b);
+        (new_b, new_c)
+    }
+
+    #[test]
+    fn reaches_target() {
+        let a = Vec3::ZERO;
+        let b = Vec3::new(1., 0., 0.);
+        let c = Vec3::new(2., 0., 0.);
+        let target = Vec3::new(1., 1., 0.);
+        let (new_b, new_c) = solve_positions(a, b, c, target, Vec3::new(0., 0., 1.));
+        assert!(new_c.distance(target) < 1e-3, "{new_c} != {target}");
+        assert!((new_b.distance(a) - 1.).abs() < 1e-4);
+        assert!((new_c.distance(new_b) - 1.).abs() < 1e-4);
+    }
+
+    #[test]
+    fn bends_towards_pole() {
+        let a = Vec3::ZERO;
+        let b = Vec3::new(1., 0.1, 0.);
+        let c = Vec3::new(2., 0., 0.);
+        let target = Vec3::new(1.5, 0., 0.);
+        let pole = Vec3::new(0.75, 0., 1.);
+        let (new_b, new_c) = solve_positions(a, b, c, target, pole);
+        assert!(new_c.distance(target) < 1e-3, "{new_c} != {target}");
+        assert!(new_b.z > 0.1, "elbow {new_b} should bend towards +Z");
+    }
+
+    #[test]
+    fn unreachable_target_extends() {
+        let a = Vec3::ZERO;
+        let b = Vec3::new(1., 0., 0.);
+        let c = Vec3::new(1., 1., 0.);
+        let target = Vec3::new(0., 10., 0.);
+        let (_, new_c) = solve_positions(a, b, c, target, Vec3::X);
+        let direction = new_c.normalize();
+        assert!(direction.dot(Vec3::Y) > 0.999, "{direction} should point at the target");
+        assert!(new_c.length() > 1.99);
+    }
+}
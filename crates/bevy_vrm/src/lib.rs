@@ -5,14 +5,19 @@ use bevy::prelude::*;
 use bevy::render::renderer::RenderDevice;
 use bevy::render::texture::CompressedImageFormats;
 use bevy::scene::Scene;
+use bevy::transform::TransformSystem;
 use bevy::utils::HashMap;
 
-pub use loader::{VrmError, VrmLoader};
+pub use loader::{MissingLookAt, MissingNormals, RootCorrection, VrmError, VrmLoader, VrmLoaderSettings};
 
-use crate::extensions::mtoon::MToonMaterial;
-use crate::extensions::vrm::{apply_transform_look_at, Eye, Humanoid, LookAtRangeMap, LookAtTarget, MorphTargetLookAt, TransformLookAt};
+use crate::extensions::mtoon::{MToonMaterial, MToonOutline, MToonOutlineMaterial};
+use crate::extensions::node_constraint::{apply_node_constraints, NodeConstraint};
+use crate::extensions::spring_bone::{SpringBone, SpringBoneCollider, update_spring_bones};
+use crate::extensions::vrm::{apply_transform_look_at, Eye, Humanoid, LookAtRangeMap, LookAtTarget, LookAtTargetRoot, MorphTargetLookAt, TransformLookAt, VrmMeta};
 
 pub mod extensions;
+pub mod ik;
+pub mod morph_aliases;
 
 mod loader;
 
@@ -26,19 +31,25 @@ pub struct VrmBundle {
     pub view_visibility: ViewVisibility,
 }
 
+/// Spawn the default scene of each VRM once it has loaded.
+///
+/// Changing the `Handle<Vrm>` on an entity swaps its scene for the new VRM's,
+/// once the new VRM is ready.
 pub fn spawn_vrms(
     mut commands: Commands,
     vrms: Res<Assets<Vrm>>,
-    to_spawn: Query<(Entity, &Handle<Vrm>), Without<Handle<Scene>>>,
+    to_spawn: Query<(Entity, &Handle<Vrm>, Option<&Handle<Scene>>)>,
 ) {
-    for (entity, vrm) in &to_spawn {
+    for (entity, vrm, current_scene) in &to_spawn {
         if let Some(vrm) = vrms.get(vrm) {
             let scene = if let Some(scene_name) = vrm.default_scene.as_ref() {
                 vrm.scenes.get(scene_name).unwrap().clone()
             } else {
                 Handle::default()
             };
-            commands.entity(entity).insert(scene);
+            if current_scene != Some(&scene) {
+                commands.entity(entity).insert(scene);
+            }
         }
     }
 }
@@ -49,6 +60,21 @@ pub struct Vrm {
     pub meshes: Vec<Handle<Mesh>>,
     pub default_scene: Option<String>,
     pub scenes: HashMap<String, Handle<Scene>>,
+    /// Animation clips embedded in the file, by name, which target the
+    /// nodes of every scene and play from its root's `AnimationPlayer`.
+    pub animations: HashMap<String, Handle<AnimationClip>>,
+    /// The thumbnail image from the VRM meta, if it has one.
+    pub thumbnail: Option<Handle<Image>>,
+    /// The canonical expression name of each aliased morph target.
+    pub morph_aliases: HashMap<String, String>,
+    pub(crate) meta: Option<VrmMeta>,
+}
+
+impl Vrm {
+    /// The avatar's attribution and license, if its VRM has a meta block.
+    pub fn meta(&self) -> Option<&VrmMeta> {
+        self.meta.as_ref()
+    }
 }
 
 pub struct VrmPlugin;
@@ -61,21 +87,34 @@ impl Plugin for VrmPlugin {
         };
         app
             .add_plugins(MaterialPlugin::<MToonMaterial>::default())
-            .register_asset_loader(VrmLoader {
-                supported_compressed_formats,
-                custom_vertex_attributes: Default::default(),
+            // The outline hull would only thicken the avatar's shadow.
+            .add_plugins(MaterialPlugin::<MToonOutlineMaterial> {
+                prepass_enabled: false,
+                shadows_enabled: false,
+                ..default()
             })
+            .register_asset_loader(VrmLoader::new(supported_compressed_formats))
             .add_systems(Update, (spawn_vrms, apply_transform_look_at))
+            .add_systems(PostUpdate, (
+                apply_node_constraints,
+                update_spring_bones,
+            ).chain().after(TransformSystem::TransformPropagate))
             .init_asset::<MToonMaterial>()
             .register_asset_reflect::<MToonMaterial>()
+            .init_asset::<MToonOutlineMaterial>()
+            .register_type::<MToonOutline>()
             .init_asset::<Vrm>()
             .register_asset_reflect::<Vrm>()
             .register_type::<Humanoid>()
             .register_type::<Eye>()
             .register_type::<LookAtTarget>()
+            .register_type::<LookAtTargetRoot>()
             .register_type::<LookAtRangeMap>()
             .register_type::<TransformLookAt>()
             .register_type::<MorphTargetLookAt>()
+            .register_type::<NodeConstraint>()
+            .register_type::<SpringBone>()
+            .register_type::<SpringBoneCollider>()
             .init_asset::<Vrm>();
     }
 
@@ -3,12 +3,13 @@ use std::path::Path;
 use anyhow::Result;
 use base64::Engine;
 use bevy::asset::{AssetLoader, AsyncReadExt, Handle, LoadContext, ReadAssetBytesError};
+use bevy::animation::{AnimationClip, AnimationPlayer, AnimationTarget, AnimationTargetId, Interpolation, Keyframes, VariableCurve};
 use bevy::asset::io::Reader;
 use bevy::core::Name;
 use bevy::hierarchy::{BuildWorldChildren, WorldChildBuilder};
 use bevy::log;
 use bevy::math::{Mat4, Vec3};
-use bevy::pbr::{MaterialMeshBundle, PbrBundle, StandardMaterial};
+use bevy::pbr::{MaterialMeshBundle, PbrBundle, StandardMaterial, UvChannel};
 use bevy::prelude::*;
 use bevy::render::{
     alpha::AlphaMode,
@@ -20,25 +21,32 @@ use bevy::render::{
     },
     prelude::SpatialBundle,
     primitives::Aabb,
-    render_resource::{Face, PrimitiveTopology},
-    texture::{CompressedImageFormats, Image, ImageSampler, ImageType, TextureError},
+    render_resource::PrimitiveTopology,
+    texture::{CompressedImageFormats, Image, ImageSampler, TextureError},
 };
 use bevy::render::render_asset::RenderAssetUsages;
 use bevy::render::texture::{ImageAddressMode, ImageFilterMode, ImageSamplerDescriptor};
 use bevy::scene::Scene;
 use bevy::transform::components::Transform;
-use bevy::utils::{ConditionalSendFuture, HashMap, HashSet};
-use gltf::{accessor::Iter, Glb, mesh::{Mode, util::ReadIndices}, Primitive, texture::{MagFilter, MinFilter, WrappingMode}};
-use serde::Deserialize;
+use bevy::utils::{ConditionalSendFuture, HashMap, HashSet, Instant};
+use gltf::{accessor::Iter, animation::util::ReadOutputs, Glb, mesh::{Mode, util::ReadIndices}, Primitive, texture::{MagFilter, MinFilter, WrappingMode}};
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+pub use root_correction::RootCorrection;
 use vertex_attributes::*;
 
 use crate::extensions::{ExtendedMaterial, ExtendedRoot};
-use crate::extensions::mtoon::MToonMaterial;
-use crate::extensions::vrm::{Eye, Humanoid, HumanoidBone, LookAtModeJson, TransformLookAt, LookAtTarget, LookAtRangeMap};
+use crate::extensions::mtoon::{MaterialCullMode, MToonMaterial, MToonOutline, MToonOutlineMaterial, MToonUvSets, OutlineWidthMode};
+use crate::extensions::node_constraint::NodeConstraint;
+use crate::extensions::spring_bone::{SpringBone, SpringBoneCollider};
+use crate::extensions::vrm::{Eye, Humanoid, HumanoidBone, LookAtModeJson, TransformLookAt, LookAtTarget, LookAtTargetRoot, LookAtRangeMap, LookAtJson, VrmMeta};
+use crate::morph_aliases::MorphAliases;
 use crate::Vrm;
 
+mod normals;
+mod root_correction;
+mod textures;
 mod vertex_attributes;
 
 /// An error that occurs when loading a glTF file.
@@ -70,24 +78,95 @@ pub enum VrmError {
     MorphTarget(#[from] bevy::render::mesh::morph::MorphBuildError),
 }
 
+/// How to generate normals for meshes which don't have them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MissingNormals {
+    /// Split every vertex and use face normals, like Bevy's glTF loader.
+    #[default]
+    Flat,
+    /// Angle-weighted vertex normals, keeping shared vertices shared.
+    Smooth,
+}
+
+/// What to do with avatars which have no look-at metadata.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MissingLookAt {
+    /// Look with the eye bones, using the VRM spec's default range maps.
+    #[default]
+    Default,
+    /// Leave the eyes where they are.
+    Ignore,
+}
+
+/// Distance of the default look target in front of the eyes, in metres.
+pub const DEFAULT_LOOK_DISTANCE: f32 = 10.;
+
+fn default_look_distance() -> f32 {
+    DEFAULT_LOOK_DISTANCE
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VrmLoaderSettings {
+    pub missing_normals: MissingNormals,
+    pub morph_aliases: MorphAliases,
+    pub root_correction: RootCorrection,
+    /// How far in front of the head the eyes focus by default. Nearer
+    /// targets make the eyes converge more.
+    #[serde(default = "default_look_distance")]
+    pub look_distance: f32,
+    #[serde(default)]
+    pub missing_look_at: MissingLookAt,
+    /// Alpha cutoffs for masked materials, by material name or index,
+    /// replacing the ones in the file.
+    #[serde(default)]
+    pub alpha_cutoffs: HashMap<String, f32>,
+    /// Which faces to cull, by material name or index, replacing the
+    /// culling implied by the file's `doubleSided`.
+    #[serde(default)]
+    pub cull_modes: HashMap<String, MaterialCullMode>,
+}
+
+impl Default for VrmLoaderSettings {
+    fn default() -> Self {
+        VrmLoaderSettings {
+            missing_normals: MissingNormals::default(),
+            morph_aliases: MorphAliases::default(),
+            root_correction: RootCorrection::default(),
+            look_distance: DEFAULT_LOOK_DISTANCE,
+            missing_look_at: MissingLookAt::default(),
+            alpha_cutoffs: HashMap::default(),
+            cull_modes: HashMap::default(),
+        }
+    }
+}
+
 /// Loads glTF files with all of their data as their corresponding bevy representations.
 pub struct VrmLoader {
     pub(crate) supported_compressed_formats: CompressedImageFormats,
     pub(crate) custom_vertex_attributes: HashMap<String, MeshVertexAttribute>,
 }
 
+impl VrmLoader {
+    pub fn new(supported_compressed_formats: CompressedImageFormats) -> VrmLoader {
+        VrmLoader {
+            supported_compressed_formats,
+            custom_vertex_attributes: Default::default(),
+        }
+    }
+}
+
 impl AssetLoader for VrmLoader {
     type Asset = Vrm;
-    type Settings = ();
+    type Settings = VrmLoaderSettings;
     type Error = VrmError;
 
     fn load<'a>(
         &'a self,
         reader: &'a mut Reader,
-        _settings: &'a Self::Settings,
+        settings: &'a Self::Settings,
         load_context: &'a mut LoadContext,
     ) -> impl ConditionalSendFuture<Output=std::result::Result<Self::Asset, Self::Error>> {
-        load_vrm(reader, load_context, self)
+        load_vrm(reader, load_context, self, settings)
     }
 
     fn extensions(&self) -> &[&str] {
@@ -98,7 +177,9 @@ impl AssetLoader for VrmLoader {
 #[derive(Debug, Clone, Copy)]
 enum MaterialType {
     StandardMaterial,
-    MToonMaterial,
+    MToonMaterial {
+        outline: bool,
+    },
 }
 
 fn load_maybe_glb(src: &[u8]) -> Result<Glb, gltf::Error> {
@@ -121,7 +202,9 @@ async fn load_vrm<'a, 'b>(
     reader: &'a mut Reader<'_>,
     load_context: &'a mut LoadContext<'b>,
     loader: &VrmLoader,
+    settings: &VrmLoaderSettings,
 ) -> Result<Vrm, VrmError> {
+    let start_time = Instant::now();
     let mut bytes = Vec::new();
     reader.read_to_end(&mut bytes).await
         .map_err(gltf::Error::from)?;
@@ -134,6 +217,7 @@ async fn load_vrm<'a, 'b>(
         blob: glb.bin.map(From::from),
     };
     let buffer_data = load_buffers(&gltf, load_context).await?;
+    let buffers_time = Instant::now();
     let vrm_root = serde_json::from_slice::<ExtendedRoot>(&glb.json)
         .map_err(gltf::Error::from)?;
     let vrm_metadata = &vrm_root.extensions.vrm;
@@ -143,7 +227,7 @@ async fn load_vrm<'a, 'b>(
     for material in gltf.materials() {
         let extended_material = material.index().map(|i| &vrm_root.materials[i]);
 
-        let material_type = load_material(&material, extended_material, load_context);
+        let material_type = load_material(&material, extended_material, settings, load_context);
         material_types.push(material_type);
 
         if let Some(texture) = material.normal_texture() {
@@ -158,9 +242,18 @@ async fn load_vrm<'a, 'b>(
         {
             linear_textures.insert(texture.texture().index());
         }
+        // The outline width is a mask rather than a colour.
+        if let Some(info) = extended_material
+            .and_then(|m| m.extensions.mtoon.as_ref())
+            .and_then(|mtoon| mtoon.outline_width_multiply_texture.as_ref()) {
+            linear_textures.insert(info.index as usize);
+        }
     }
 
+    let materials_time = Instant::now();
+
     let mut meshes = Vec::new();
+    let mut morph_aliases = HashMap::new();
     for gltf_mesh in gltf.meshes() {
         for primitive in gltf_mesh.primitives() {
             let primitive_label = primitive_label(&gltf_mesh, &primitive);
@@ -191,38 +284,32 @@ async fn load_vrm<'a, 'b>(
                 });
             };
 
-            {
-                let morph_target_reader = reader.read_morph_targets();
-                if morph_target_reader.len() != 0 {
-                    let morph_targets_label = morph_targets_label(&gltf_mesh, &primitive);
-                    let morph_target_image = MorphTargetImage::new(
-                        morph_target_reader.map(PrimitiveMorphAttributesIter),
-                        mesh.count_vertices(),
-                        RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
-                    )?;
-                    let handle = load_context.add_labeled_asset(
-                        morph_targets_label,
-                        morph_target_image.0,
-                    );
-
-                    mesh.set_morph_targets(handle);
-                    let extras = gltf_mesh.extras().as_ref();
-                    if let Option::<MorphTargetNames>::Some(names) =
-                        extras.and_then(|extras| serde_json::from_str(extras.get()).ok())
-                    {
-                        mesh.set_morph_target_names(names.target_names);
-                    }
-                }
-            }
+            // Morph targets are per vertex, so read them before any vertices
+            // are duplicated and remap them along with the vertices.
+            let mut morph_targets: Vec<Vec<MorphAttributes>> = reader.read_morph_targets()
+                .map(|target| PrimitiveMorphAttributesIter(target).collect())
+                .collect();
 
-            if mesh.attribute(Mesh::ATTRIBUTE_NORMAL).is_none()
-                && matches!(mesh.primitive_topology(), PrimitiveTopology::TriangleList)
-            {
+            let generated_normals = mesh.attribute(Mesh::ATTRIBUTE_NORMAL).is_none()
+                && matches!(mesh.primitive_topology(), PrimitiveTopology::TriangleList);
+            if generated_normals && settings.missing_normals == MissingNormals::Smooth {
+                normals::compute_smooth_normals(&mut mesh);
+                log::debug!("Missing vertex normals, computing them as smooth.");
+            } else if generated_normals {
                 let vertex_count_before = mesh.count_vertices();
+                let duplicated: Option<Vec<usize>> = mesh.indices().map(|indices| indices.iter().collect());
                 mesh.duplicate_vertices();
                 mesh.compute_flat_normals();
                 let vertex_count_after = mesh.count_vertices();
 
+                if let Some(duplicated) = duplicated {
+                    for target in &mut morph_targets {
+                        *target = duplicated.iter()
+                            .map(|&index| target.get(index).copied().unwrap_or_default())
+                            .collect();
+                    }
+                }
+
                 if vertex_count_before != vertex_count_after {
                     log::debug!("Missing vertex normals in indexed geometry, computing them as flat. Vertex count increased from {} to {}", vertex_count_before, vertex_count_after);
                 } else {
@@ -232,6 +319,40 @@ async fn load_vrm<'a, 'b>(
                 }
             }
 
+            if !morph_targets.is_empty() {
+                // glTF morph normals are relative to the file's normals, so
+                // there are none to use when the normals were generated.
+                // Generate them from the morphed positions instead, so that
+                // morphs which bend the surface shade correctly.
+                if generated_normals {
+                    normals::compute_morph_normals(&mesh, &mut morph_targets);
+                }
+
+                let morph_targets_label = morph_targets_label(&gltf_mesh, &primitive);
+                let morph_target_image = MorphTargetImage::new(
+                    morph_targets.into_iter().map(|target| target.into_iter()),
+                    mesh.count_vertices(),
+                    RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
+                )?;
+                let handle = load_context.add_labeled_asset(
+                    morph_targets_label,
+                    morph_target_image.0,
+                );
+
+                mesh.set_morph_targets(handle);
+                let extras = gltf_mesh.extras().as_ref();
+                if let Option::<MorphTargetNames>::Some(names) =
+                    extras.and_then(|extras| serde_json::from_str(extras.get()).ok())
+                {
+                    for name in &names.target_names {
+                        if let Some(canonical) = settings.morph_aliases.canonical(name) {
+                            morph_aliases.insert(name.clone(), canonical.to_string());
+                        }
+                    }
+                    mesh.set_morph_target_names(names.target_names);
+                }
+            }
+
             if let Some(vertex_attribute) = reader
                 .read_tangents()
                 .map(|v| VertexAttributeValues::Float32x4(v.collect()))
@@ -256,6 +377,8 @@ async fn load_vrm<'a, 'b>(
         }
     }
 
+    let meshes_time = Instant::now();
+
     for gltf_texture in gltf.textures() {
         let (texture, label) = load_texture(
             gltf_texture,
@@ -267,6 +390,19 @@ async fn load_vrm<'a, 'b>(
         load_context.add_labeled_asset(label, texture);
     }
 
+    let thumbnail = match vrm_metadata.meta.as_ref().and_then(|meta| meta.thumbnail_image) {
+        Some(image_index) => load_thumbnail(
+            &gltf,
+            image_index,
+            &buffer_data,
+            load_context,
+            loader.supported_compressed_formats,
+        ).await,
+        None => None,
+    };
+
+    let textures_time = Instant::now();
+
     let skinned_mesh_inverse_bindposes: Vec<_> = gltf
         .skins()
         .map(|gltf_skin| {
@@ -284,6 +420,9 @@ async fn load_vrm<'a, 'b>(
         })
         .collect();
 
+    let node_paths = node_paths(&gltf);
+    let animations = load_animations(&gltf, &buffer_data, &node_paths, load_context)?;
+
     let mut default_scene = None;
     let mut scenes = HashMap::new();
     let mut active_camera_found = false;
@@ -332,6 +471,97 @@ async fn load_vrm<'a, 'b>(
             });
         }
 
+        // Animations target nodes by their path from the scene, and are
+        // all played from the scene's root.
+        if !animations.is_empty() {
+            for (index, &entity) in &node_index_to_entity_map {
+                if let Some(path) = node_paths.get(index) {
+                    world.entity_mut(entity).insert(AnimationTarget {
+                        id: AnimationTargetId::from_names(path.iter()),
+                        player: root_entity,
+                    });
+                }
+            }
+            world.entity_mut(root_entity).insert(AnimationPlayer::default());
+        }
+
+        // Build node constraints
+        for (index, node) in vrm_root.nodes.iter().enumerate() {
+            let Some(extension) = node.extensions.node_constraint.as_ref() else {
+                continue;
+            };
+            let Some(entity) = node_index_to_entity_map.get(&index).copied() else {
+                continue;
+            };
+
+            let rest = world.entity(entity).get::<Transform>().unwrap().rotation;
+            let source = |source: u32| {
+                let entity = *node_index_to_entity_map.get(&(source as usize))?;
+                Some((entity, world.entity(entity).get::<Transform>()?.rotation))
+            };
+            match NodeConstraint::from_json(&extension.constraint, source, rest) {
+                Some(constraint) => {
+                    world.entity_mut(entity).insert(constraint);
+                }
+                None => log::warn!("Node {index} has an invalid or empty constraint"),
+            }
+        }
+
+        // Build spring bones
+        if let Some(spring_bone) = vrm_root.extensions.spring_bone.as_ref() {
+            let mut collider_entities = Vec::with_capacity(spring_bone.colliders.len());
+            for (index, collider) in spring_bone.colliders.iter().enumerate() {
+                let node = node_index_to_entity_map.get(&(collider.node as usize)).copied();
+                let entity = match (node, &collider.shape.sphere) {
+                    (Some(node), Some(sphere)) => Some(world
+                        .spawn((
+                            Name::new("Spring Bone Collider"),
+                            SpatialBundle::from_transform(Transform::from_translation(sphere.offset)),
+                            SpringBoneCollider {
+                                radius: sphere.radius,
+                            },
+                        ))
+                        .set_parent(node)
+                        .id()),
+                    (None, _) => {
+                        log::warn!("Spring bone collider {index} references invalid node ID {}", collider.node);
+                        None
+                    }
+                    (_, None) => {
+                        log::warn!("Spring bone collider {index} is not a sphere, capsules are unsupported");
+                        None
+                    }
+                };
+                collider_entities.push(entity);
+            }
+
+            for spring in &spring_bone.springs {
+                let colliders: Vec<Entity> = spring.collider_groups.iter()
+                    .filter_map(|group| spring_bone.collider_groups.get(*group as usize))
+                    .flat_map(|group| &group.colliders)
+                    .filter_map(|collider| collider_entities.get(*collider as usize).copied().flatten())
+                    .collect();
+
+                // Each joint swings towards the next, which is its child. The
+                // last joint only marks the end of the chain.
+                for joints in spring.joints.windows(2) {
+                    let (joint, tail) = (&joints[0], &joints[1]);
+                    let (Some(entity), Some(tail_entity)) = (
+                        node_index_to_entity_map.get(&(joint.node as usize)).copied(),
+                        node_index_to_entity_map.get(&(tail.node as usize)).copied(),
+                    ) else {
+                        log::warn!("Spring {:?} references invalid node IDs", spring.name);
+                        continue;
+                    };
+
+                    let rest = world.entity(entity).get::<Transform>().unwrap().rotation;
+                    let tail_translation = world.entity(tail_entity).get::<Transform>().unwrap().translation;
+                    world.entity_mut(entity)
+                        .insert(SpringBone::from_json(joint, tail_entity, rest, tail_translation, colliders.clone()));
+                }
+            }
+        }
+
         // Build humanoid component
         let humanoid = &vrm_metadata.humanoid;
         let mut bones = HashMap::with_capacity(humanoid.human_bones.len());
@@ -344,67 +574,95 @@ async fn load_vrm<'a, 'b>(
         }
 
         // Build look-at component
-        let look_at = &vrm_metadata.look_at;
-        let look_target = world.spawn((
-            Name::new("Look Target"),
-            SpatialBundle::from_transform(Transform::from_xyz(0., 0., -10.)),
-        )).id();
-        let look_at_range_map = LookAtRangeMap::from(look_at);
-
-        for left in [true, false] {
-            let bone = if left { HumanoidBone::LeftEye } else { HumanoidBone::RightEye };
-            let Some(entity) = bones.get(&bone).copied() else {
-                continue;
-            };
-
-            let range_map = if left {
-                look_at_range_map
-            } else {
-                look_at_range_map.flipped()
-            };
+        let default_look_at;
+        let look_at = match (&vrm_metadata.look_at, settings.missing_look_at) {
+            (Some(look_at), _) => Some(look_at),
+            (None, MissingLookAt::Default) => {
+                log::info!("{} has no look-at, using defaults", load_context.path().display());
+                default_look_at = LookAtJson::default();
+                Some(&default_look_at)
+            }
+            (None, MissingLookAt::Ignore) => {
+                log::info!("{} has no look-at, eyes won't move", load_context.path().display());
+                None
+            }
+        };
+        if let Some(look_at) = look_at {
+            let look_target_transform = Transform::from_xyz(0., 0., -settings.look_distance);
+            let look_target = world.spawn((
+                Name::new("Look Target"),
+                SpatialBundle::from_transform(look_target_transform),
+            )).id();
+            let look_at_range_map = LookAtRangeMap::from(look_at);
+
+            for left in [true, false] {
+                let bone = if left { HumanoidBone::LeftEye } else { HumanoidBone::RightEye };
+                let Some(entity) = bones.get(&bone).copied() else {
+                    continue;
+                };
 
-            let look_target = world
-                .spawn((
-                    Name::new(if left { "Left Offset" } else { "Right Offset" }),
-                    SpatialBundle::default(),
-                ))
-                .set_parent(look_target)
-                .id();
-            let base_transform = world.entity(entity).get::<Transform>().unwrap().clone();
-            world.entity_mut(entity)
-                .insert((
-                    Eye,
-                    LookAtTarget(look_target),
-                    range_map,
-                ));
+                let range_map = if left {
+                    look_at_range_map
+                } else {
+                    look_at_range_map.flipped()
+                };
 
-            match look_at.mode {
-                LookAtModeJson::Bone => {
-                    world.entity_mut(entity)
-                        .insert((
-                            TransformLookAt {
-                                offset: base_transform.rotation,
-                            },
-                        ));
+                let look_target = world
+                    .spawn((
+                        Name::new(if left { "Left Offset" } else { "Right Offset" }),
+                        SpatialBundle::default(),
+                    ))
+                    .set_parent(look_target)
+                    .id();
+                let base_transform = world.entity(entity).get::<Transform>().unwrap().clone();
+                world.entity_mut(entity)
+                    .insert((
+                        Eye,
+                        LookAtTarget(look_target),
+                        range_map,
+                    ));
+
+                match look_at.mode {
+                    LookAtModeJson::Bone => {
+                        world.entity_mut(entity)
+                            .insert((
+                                TransformLookAt {
+                                    offset: base_transform.rotation,
+                                },
+                            ));
+                    }
+                    LookAtModeJson::Expression => {}
                 }
-                LookAtModeJson::Expression => {}
             }
-        }
 
-        if let Some(entity) = bones.get(&HumanoidBone::Head).copied() {
-            world.entity_mut(entity)
-                .with_children(|parent| {
-                    parent.spawn(SpatialBundle::from_transform(
-                        Transform::from_translation(look_at.offset_from_head_bone)))
-                        .add_child(look_target);
-                });
-        }
+            if let Some(entity) = bones.get(&HumanoidBone::Head).copied() {
+                let look_origin = world
+                    .spawn((
+                        Name::new("Look Origin"),
+                        SpatialBundle::from_transform(
+                            Transform::from_translation(look_at.offset_from_head_bone)),
+                    ))
+                    .set_parent(entity)
+                    .add_child(look_target)
+                    .id();
+                world.entity_mut(look_target)
+                    .insert(LookAtTargetRoot {
+                        default_parent: look_origin,
+                        default_transform: look_target_transform,
+                    });
+            }
 
-        if look_at.mode == LookAtModeJson::Expression {
-            log::warn!("expression look at unsupported");
+            if look_at.mode == LookAtModeJson::Expression {
+                log::warn!("expression look at unsupported");
+            }
         }
 
 
+        if let Some(rotation) = settings.root_correction.rotation(&world, &bones) {
+            log::info!("Correcting the orientation of {}", load_context.path().display());
+            world.get_mut::<Transform>(root_entity).unwrap().rotation = rotation;
+        }
+
         world
             .entity_mut(root_entity)
             .insert(Name::new("Humanoid"))
@@ -423,13 +681,104 @@ async fn load_vrm<'a, 'b>(
         scenes.insert(scene_name, scene_handle);
     }
 
+    let end_time = Instant::now();
+    log::debug!(
+        "Loaded {} in {:?} (buffers {:?}, materials {:?}, meshes {:?}, textures {:?}, scenes {:?})",
+        load_context.path().display(),
+        end_time - start_time,
+        buffers_time - start_time,
+        materials_time - buffers_time,
+        meshes_time - materials_time,
+        textures_time - meshes_time,
+        end_time - textures_time,
+    );
+
     Ok(Vrm {
         meshes,
         default_scene,
         scenes,
+        animations,
+        thumbnail,
+        morph_aliases,
+        meta: vrm_metadata.meta.as_ref().map(VrmMeta::from),
     })
 }
 
+/// The names of each node and its ancestors, from the root of its scene,
+/// which identify it as an animation target.
+fn node_paths(gltf: &gltf::Gltf) -> HashMap<usize, Vec<Name>> {
+    fn visit(node: gltf::Node, mut path: Vec<Name>, paths: &mut HashMap<usize, Vec<Name>>) {
+        path.push(node_name(&node));
+        for child in node.children() {
+            visit(child, path.clone(), paths);
+        }
+        paths.insert(node.index(), path);
+    }
+
+    let mut paths = HashMap::new();
+    for scene in gltf.scenes() {
+        for node in scene.nodes() {
+            visit(node, Vec::new(), &mut paths);
+        }
+    }
+    paths
+}
+
+/// Loads each glTF animation as an [`AnimationClip`], keyed by its name.
+fn load_animations(
+    gltf: &gltf::Gltf,
+    buffer_data: &[Vec<u8>],
+    node_paths: &HashMap<usize, Vec<Name>>,
+    load_context: &mut LoadContext,
+) -> Result<HashMap<String, Handle<AnimationClip>>, VrmError> {
+    let mut animations = HashMap::new();
+    for animation in gltf.animations() {
+        let mut clip = AnimationClip::default();
+        for channel in animation.channels() {
+            let interpolation = match channel.sampler().interpolation() {
+                gltf::animation::Interpolation::Linear => Interpolation::Linear,
+                gltf::animation::Interpolation::Step => Interpolation::Step,
+                gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline,
+            };
+            let node = channel.target().node();
+            let reader = channel.reader(|buffer| Some(&buffer_data[buffer.index()]));
+            let keyframe_timestamps: Vec<f32> = reader.read_inputs()
+                .ok_or(VrmError::MissingAnimationSampler(animation.index()))?
+                .collect();
+            let keyframes = match reader.read_outputs()
+                .ok_or(VrmError::MissingAnimationSampler(animation.index()))? {
+                ReadOutputs::Translations(translations) => Keyframes::Translation(
+                    translations.map(Vec3::from).collect()),
+                ReadOutputs::Rotations(rotations) => Keyframes::Rotation(
+                    rotations.into_f32().map(Quat::from_array).collect()),
+                ReadOutputs::Scales(scales) => Keyframes::Scale(
+                    scales.map(Vec3::from).collect()),
+                ReadOutputs::MorphTargetWeights(weights) => Keyframes::Weights(
+                    weights.into_f32().collect()),
+            };
+
+            let Some(path) = node_paths.get(&node.index()) else {
+                log::warn!("Animation {} targets node {}, which isn't in any scene", animation.index(), node.index());
+                continue;
+            };
+            clip.add_curve_to_target(
+                AnimationTargetId::from_names(path.iter()),
+                VariableCurve {
+                    keyframe_timestamps,
+                    keyframes,
+                    interpolation,
+                },
+            );
+        }
+
+        let label = animation_label(&animation);
+        let name = animation.name().map_or_else(|| label.clone(), str::to_string);
+        let handle = load_context.add_labeled_asset(label, clip);
+        animations.insert(name, handle);
+    }
+    Ok(animations)
+}
+
 fn node_name(node: &gltf::Node) -> Name {
     let name = node
         .name()
@@ -447,18 +796,40 @@ async fn load_texture(
     supported_compressed_formats: CompressedImageFormats,
 ) -> Result<(Image, String), VrmError> {
     let is_srgb = !linear_textures.contains(&gltf_texture.index());
-    let texture = match gltf_texture.source().source() {
+    let texture = load_image(
+        gltf_texture.source(),
+        ImageSampler::Descriptor(texture_sampler(&gltf_texture)),
+        is_srgb,
+        buffer_data,
+        load_context,
+        supported_compressed_formats,
+    ).await?;
+    Ok((texture, texture_label(&gltf_texture)))
+}
+
+/// Loads a glTF image as a bevy [`Image`].
+///
+/// 16 bit and floating point images (including HDR, and EXR when Bevy's `exr`
+/// feature is enabled) are converted to half floats.
+async fn load_image(
+    gltf_image: gltf::Image<'_>,
+    sampler: ImageSampler,
+    is_srgb: bool,
+    buffer_data: &[Vec<u8>],
+    load_context: &mut LoadContext<'_>,
+    supported_compressed_formats: CompressedImageFormats,
+) -> Result<Image, VrmError> {
+    let mut image = match gltf_image.source() {
         gltf::image::Source::View { view, mime_type } => {
             let start = view.offset();
             let end = view.offset() + view.length();
             let buffer = &buffer_data[view.buffer().index()][start..end];
-            Image::from_buffer(
+            textures::decode_image(
                 buffer,
-                ImageType::MimeType(mime_type),
+                textures::image_format(Some(mime_type), None)?,
                 supported_compressed_formats,
                 is_srgb,
-                ImageSampler::Descriptor(texture_sampler(&gltf_texture)),
-                RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
+                sampler,
             )?
         }
         gltf::image::Source::Uri { uri, mime_type } => {
@@ -466,37 +837,73 @@ async fn load_texture(
                 .decode_utf8()
                 .unwrap();
             let uri = uri.as_ref();
-            let (bytes, image_type) = if let Ok(data_uri) = DataUri::parse(uri) {
-                (data_uri.decode()?, ImageType::MimeType(data_uri.mime_type))
+            let (bytes, format) = if let Ok(data_uri) = DataUri::parse(uri) {
+                let format = textures::image_format(Some(mime_type.unwrap_or(data_uri.mime_type)), None)?;
+                (data_uri.decode()?, format)
             } else {
                 let parent = load_context.path().parent().unwrap();
                 let image_path = parent.join(uri);
                 let bytes = load_context.read_asset_bytes(image_path.clone()).await?;
 
-                let extension = Path::new(uri).extension().unwrap().to_str().unwrap();
-                let image_type = ImageType::Extension(extension);
-
-                (bytes, image_type)
+                let extension = Path::new(uri).extension().and_then(|extension| extension.to_str());
+                (bytes, textures::image_format(mime_type, extension)?)
             };
 
-            Image::from_buffer(
+            textures::decode_image(
                 &bytes,
-                mime_type.map(ImageType::MimeType).unwrap_or(image_type),
+                format,
                 supported_compressed_formats,
                 is_srgb,
-                ImageSampler::Descriptor(texture_sampler(&gltf_texture)),
-                RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
+                sampler,
             )?
         }
     };
+    textures::convert_high_precision(&mut image, is_srgb);
 
-    Ok((texture, texture_label(&gltf_texture)))
+    Ok(image)
+}
+
+/// Find the thumbnail referenced by the VRM meta, reusing the texture which
+/// already loaded the image if there is one. The thumbnail is optional, so a
+/// missing or broken image is warned about rather than failing the load.
+async fn load_thumbnail(
+    gltf: &gltf::Gltf,
+    image_index: usize,
+    buffer_data: &[Vec<u8>],
+    load_context: &mut LoadContext<'_>,
+    supported_compressed_formats: CompressedImageFormats,
+) -> Option<Handle<Image>> {
+    let Some(gltf_image) = gltf.images().nth(image_index) else {
+        log::warn!("VRM thumbnail refers to missing image {image_index}");
+        return None;
+    };
+
+    if let Some(gltf_texture) = gltf.textures().find(|t| t.source().index() == image_index) {
+        return Some(load_context.get_label_handle(texture_label(&gltf_texture)));
+    }
+
+    let image = load_image(
+        gltf_image,
+        ImageSampler::Default,
+        true,
+        buffer_data,
+        load_context,
+        supported_compressed_formats,
+    ).await;
+    match image {
+        Ok(image) => Some(load_context.add_labeled_asset("Thumbnail".to_string(), image)),
+        Err(err) => {
+            log::warn!("Failed to load VRM thumbnail: {err}");
+            None
+        }
+    }
 }
 
 /// Loads a glTF material as a bevy [`StandardMaterial`] and returns it.
 fn load_material(
     material: &gltf::Material,
     ext: Option<&ExtendedMaterial>,
+    settings: &VrmLoaderSettings,
     load_context: &mut LoadContext,
 ) -> MaterialType {
     let material_label = material_label(material);
@@ -505,42 +912,47 @@ fn load_material(
 
     let color = pbr.base_color_factor();
     let base_color = LinearRgba::new(color[0], color[1], color[2], color[3]);
+    let base_color_uv_set = pbr.base_color_texture()
+        .map_or(0, |info| uv_set(&material_label, info.tex_coord()));
     let base_color_texture = pbr.base_color_texture().map(|info| {
-        // TODO: handle info.tex_coord() (the *set* index for the right texcoords)
         let label = texture_label(&info.texture());
         load_context.get_label_handle(label)
     });
 
+    let normal_map_uv_set = material.normal_texture()
+        .map_or(0, |normal_texture| uv_set(&material_label, normal_texture.tex_coord()));
     let normal_map_texture: Option<Handle<Image>> =
         material.normal_texture().map(|normal_texture| {
-            // TODO: handle normal_texture.scale
-            // TODO: handle normal_texture.tex_coord() (the *set* index for the right texcoords)
             let label = texture_label(&normal_texture.texture());
             load_context.get_label_handle(label)
         });
 
+    let metallic_roughness_uv_set = pbr.metallic_roughness_texture()
+        .map_or(0, |info| uv_set(&material_label, info.tex_coord()));
     let metallic_roughness_texture = pbr.metallic_roughness_texture().map(|info| {
-        // TODO: handle info.tex_coord() (the *set* index for the right texcoords)
         let label = texture_label(&info.texture());
         load_context.get_label_handle(label)
     });
 
+    let occlusion_uv_set = material.occlusion_texture()
+        .map_or(0, |occlusion_texture| uv_set(&material_label, occlusion_texture.tex_coord()));
     let occlusion_texture = material.occlusion_texture().map(|occlusion_texture| {
-        // TODO: handle occlusion_texture.tex_coord() (the *set* index for the right texcoords)
-        // TODO: handle occlusion_texture.strength() (a scalar multiplier for occlusion strength)
         let label = texture_label(&occlusion_texture.texture());
         load_context.get_label_handle(label)
     });
 
     let emissive = material.emissive_factor();
     let emissive = LinearRgba::rgb(emissive[0], emissive[1], emissive[2]);
+    let emissive_uv_set = material.emissive_texture()
+        .map_or(0, |info| uv_set(&material_label, info.tex_coord()));
     let emissive_texture = material.emissive_texture().map(|info| {
-        // TODO: handle occlusion_texture.tex_coord() (the *set* index for the right texcoords)
-        // TODO: handle occlusion_texture.strength() (a scalar multiplier for occlusion strength)
         let label = texture_label(&info.texture());
         load_context.get_label_handle(label)
     });
 
+    let cull_mode = material_override(material, &settings.cull_modes).copied()
+        .unwrap_or_else(|| MaterialCullMode::from_double_sided(material.double_sided()));
+
     if let Some(mtoon) = ext.and_then(|m| m.extensions.mtoon.as_ref()) {
         let shade_color_texture = mtoon.shade_multiply_texture.as_ref().map(|info| {
             let label = texture_label_index(info.index as usize);
@@ -564,29 +976,23 @@ fn load_material(
             load_context.get_label_handle(label)
         });
 
-        // let outline_width_multiply_texture = mtoon.outline_width_multiply_texture
-        //     .as_ref()
-        //     .map(|info| {
-        //         let label = texture_label_index(info.index as usize);
-        //         let path = AssetPath::new_ref(load_context.path(), Some(&label));
-        //         load_context.get_handle(path)
-        //     });
+        let outline_width_multiply_texture = mtoon.outline_width_multiply_texture.as_ref().map(|info| {
+            let label = texture_label_index(info.index as usize);
+            load_context.get_label_handle(label)
+        });
 
         let material = MToonMaterial {
-            alpha_mode: alpha_mode(material),
-            double_sided: material.double_sided(),
-            cull_mode: if material.double_sided() {
-                None
-            } else {
-                Some(Face::Back)
-            },
+            alpha_mode: alpha_mode(material, settings),
+            double_sided: cull_mode.double_sided(),
+            cull_mode,
             transparent_with_z_write: mtoon.transparent_with_z_write,
-            depth_bias: mtoon.render_queue_offset_number,
+            render_queue_offset: mtoon.render_queue_offset_number,
             base_color,
             base_color_texture,
             emissive,
             emissive_texture,
             normal_map_texture,
+            normal_scale: normal_scale(material),
             shade_color: LinearRgba::rgb(
                 mtoon.shade_color_factor.x,
                 mtoon.shade_color_factor.y,
@@ -605,41 +1011,84 @@ fn load_material(
             rim_lighting_mix_factor: mtoon.rim_lighting_mix_factor,
             parametric_rim_fresnel_power_factor: mtoon.parametric_rim_fresnel_power_factor,
             parametric_rim_lift_factor: mtoon.parametric_rim_lift_factor,
-            // outline_width_mode: mtoon.outline_width_mode,
-            // outline_width_factor: mtoon.outline_width_factor,
-            // outline_width_multiply_texture,
-            // outline_color_factor: mtoon.outline_color_factor,
-            // outline_lighting_mix_factor: mtoon.outline_lighting_mix_factor,
+            outline_width_mode: mtoon.outline_width_mode,
             uv_animation_scroll_x_speed_factor: mtoon.uv_animation_scroll_x_speed_factor,
             uv_animation_scroll_y_speed_factor: mtoon.uv_animation_scroll_y_speed_factor,
             uv_animation_rotation_speed_factor: mtoon.uv_animation_rotation_speed_factor,
+            uv_sets: MToonUvSets {
+                base_color: base_color_uv_set,
+                emissive: emissive_uv_set,
+                shade_color: mtoon.shade_multiply_texture.as_ref()
+                    .map_or(0, |info| uv_set(&material_label, info.tex_coord)),
+                shading_shift: mtoon.shading_shift_texture.as_ref()
+                    .map_or(0, |info| uv_set(&material_label, info.texture_info.tex_coord)),
+                normal_map: normal_map_uv_set,
+            },
             ..Default::default()
         };
 
+        let outline = mtoon.outline_width_mode != OutlineWidthMode::None
+            && mtoon.outline_width_factor > 0.;
+        if outline {
+            load_context.add_labeled_asset(outline_label(&material_label), MToonOutlineMaterial {
+                base: material.clone(),
+                extension: MToonOutline {
+                    width_factor: mtoon.outline_width_factor,
+                    width_multiply_texture: outline_width_multiply_texture,
+                    width_multiply_uv_set: mtoon.outline_width_multiply_texture.as_ref()
+                        .map_or(0, |info| uv_set(&material_label, info.tex_coord)),
+                    color_factor: mtoon.outline_color_factor,
+                    lighting_mix_factor: mtoon.outline_lighting_mix_factor,
+                },
+            });
+        }
+
         load_context.add_labeled_asset(material_label, material);
-        return MaterialType::MToonMaterial;
+        return MaterialType::MToonMaterial { outline };
+    }
+
+    let specular = ext.and_then(|m| m.extensions.specular.as_ref());
+    let clearcoat = ext.and_then(|m| m.extensions.clearcoat.as_ref());
+    if specular.is_some_and(|s| s.specular_texture.is_some() || s.specular_color_texture.is_some())
+        || clearcoat.is_some_and(|c| c.clearcoat_texture.is_some()
+            || c.clearcoat_roughness_texture.is_some()
+            || c.clearcoat_normal_texture.is_some()) {
+        log::debug!("{material_label}: specular and clearcoat textures are unsupported, using factors only");
+    }
+    // Bevy's StandardMaterial has no factors for these, so both maps are used
+    // at full strength, and the material renders differently to the file.
+    let (scale, strength) = (normal_scale(material), occlusion_strength(material));
+    if scale != 1. || strength != 1. {
+        log::warn!(
+            "{material_label}: standard materials can't scale their normal and occlusion maps, \
+            ignoring normal scale {scale} and occlusion strength {strength}"
+        );
     }
 
     load_context.add_labeled_asset(
         material_label,
         StandardMaterial {
             base_color: base_color.into(),
+            base_color_channel: uv_channel(base_color_uv_set),
             base_color_texture,
             perceptual_roughness: pbr.roughness_factor(),
             metallic: pbr.metallic_factor(),
+            metallic_roughness_channel: uv_channel(metallic_roughness_uv_set),
             metallic_roughness_texture,
+            normal_map_channel: uv_channel(normal_map_uv_set),
             normal_map_texture,
-            double_sided: material.double_sided(),
-            cull_mode: if material.double_sided() {
-                None
-            } else {
-                Some(Face::Back)
-            },
+            double_sided: cull_mode.double_sided(),
+            cull_mode: cull_mode.face(),
+            occlusion_channel: uv_channel(occlusion_uv_set),
             occlusion_texture,
             emissive: emissive.into(),
+            emissive_channel: uv_channel(emissive_uv_set),
             emissive_texture,
             unlit: material.unlit(),
-            alpha_mode: alpha_mode(material),
+            alpha_mode: alpha_mode(material, settings),
+            reflectance: specular.map_or(0.5, |s| s.reflectance()),
+            clearcoat: clearcoat.map_or(0., |c| c.clearcoat_factor),
+            clearcoat_perceptual_roughness: clearcoat.map_or(0.5, |c| c.clearcoat_roughness_factor),
             ..Default::default()
         },
     );
@@ -733,7 +1182,7 @@ fn load_node(
                 // added when iterating over all the gltf materials (since the default material is
                 // not explicitly listed in the gltf).
                 if !load_context.has_labeled_asset(&material_label) {
-                    load_material(&material, None, load_context);
+                    load_material(&material, None, settings, load_context);
                 }
 
                 let primitive_label = primitive_label(&mesh, &primitive);
@@ -750,11 +1199,18 @@ fn load_node(
                         material: load_context.get_label_handle(material_label),
                         ..Default::default()
                     }),
-                    MaterialType::MToonMaterial => parent.spawn(MaterialMeshBundle {
-                        mesh: mesh_handle,
-                        material: load_context.get_label_handle::<MToonMaterial>(material_label),
-                        ..Default::default()
-                    }),
+                    MaterialType::MToonMaterial { outline } => {
+                        let mut entity = parent.spawn(MaterialMeshBundle {
+                            mesh: mesh_handle,
+                            material: load_context.get_label_handle::<MToonMaterial>(material_label.as_str()),
+                            ..Default::default()
+                        });
+                        if outline {
+                            let label = outline_label(&material_label);
+                            entity.insert(load_context.get_label_handle::<MToonOutlineMaterial>(label));
+                        }
+                        entity
+                    }
                 };
                 let target_count = primitive.morph_targets().len();
                 if target_count != 0 {
@@ -839,6 +1295,38 @@ fn material_label(material: &gltf::Material) -> String {
     }
 }
 
+/// Returns the label for the outline of the material labelled `material_label`.
+fn outline_label(material_label: &str) -> String {
+    format!("{material_label}Outline")
+}
+
+/// The scale of a material's normal map, which is 1 without one.
+fn normal_scale(material: &gltf::Material) -> f32 {
+    material.normal_texture().map_or(1., |normal_texture| normal_texture.scale())
+}
+
+/// The strength of a material's occlusion map, which is 1 without one.
+fn occlusion_strength(material: &gltf::Material) -> f32 {
+    material.occlusion_texture().map_or(1., |occlusion_texture| occlusion_texture.strength())
+}
+
+/// The UV set a texture is sampled with, given its `texCoord`. Only the first
+/// two sets are loaded, so later sets fall back to the first.
+fn uv_set(material_label: &str, tex_coord: u32) -> u32 {
+    if tex_coord > 1 {
+        log::warn!("{material_label}: TEXCOORD_{tex_coord} is unsupported, using TEXCOORD_0");
+        return 0;
+    }
+    tex_coord
+}
+
+fn uv_channel(uv_set: u32) -> UvChannel {
+    match uv_set {
+        1 => UvChannel::Uv1,
+        _ => UvChannel::Uv0,
+    }
+}
+
 /// Returns the label for the `texture`.
 fn texture_label(texture: &gltf::Texture) -> String {
     texture_label_index(texture.index())
@@ -853,6 +1341,10 @@ fn scene_label(scene: &gltf::Scene) -> String {
     format!("Scene{}", scene.index())
 }
 
+fn animation_label(animation: &gltf::Animation) -> String {
+    format!("Animation{}", animation.index())
+}
+
 fn skin_label(skin: &gltf::Skin) -> String {
     format!("Skin{}", skin.index())
 }
@@ -923,14 +1415,30 @@ fn get_primitive_topology(mode: Mode) -> Result<PrimitiveTopology, VrmError> {
     }
 }
 
-fn alpha_mode(material: &gltf::Material) -> AlphaMode {
+fn alpha_mode(material: &gltf::Material, settings: &VrmLoaderSettings) -> AlphaMode {
     match material.alpha_mode() {
         gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
-        gltf::material::AlphaMode::Mask => AlphaMode::Mask(material.alpha_cutoff().unwrap_or(0.5)),
+        gltf::material::AlphaMode::Mask => AlphaMode::Mask(alpha_cutoff(material, settings)),
         gltf::material::AlphaMode::Blend => AlphaMode::Blend,
     }
 }
 
+/// The override for a material from the loader settings, by its name or
+/// failing that its index.
+fn material_override<'a, T>(material: &gltf::Material, overrides: &'a HashMap<String, T>) -> Option<&'a T> {
+    let index = material.index().map(|index| index.to_string());
+    material.name().into_iter()
+        .chain(index.as_deref())
+        .find_map(|key| overrides.get(key))
+}
+
+/// The cutoff of a masked material, from the loader settings if it is
+/// overridden, otherwise from the glTF file.
+fn alpha_cutoff(material: &gltf::Material, settings: &VrmLoaderSettings) -> f32 {
+    material_override(material, &settings.alpha_cutoffs).copied()
+        .unwrap_or_else(|| material.alpha_cutoff().unwrap_or(0.5))
+}
+
 /// Loads the raw glTF buffer data for a specific glTF file.
 async fn load_buffers(
     gltf: &gltf::Gltf,
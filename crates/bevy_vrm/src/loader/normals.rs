@@ -0,0 +1,150 @@
+use bevy::math::Vec3;
+use bevy::render::mesh::{Indices, Mesh, VertexAttributeValues};
+use bevy::render::mesh::morph::MorphAttributes;
+
+/// Compute smooth normals for a triangle list, keeping shared vertices
+/// shared.
+///
+/// Each triangle contributes its face normal to each of its corners,
+/// weighted by the angle at that corner, so the result doesn't depend on how
+/// the surface is tessellated.
+pub fn smooth_normals(positions: &[[f32; 3]], indices: impl Iterator<Item = usize>) -> Vec<[f32; 3]> {
+    let mut normals = vec![Vec3::ZERO; positions.len()];
+    let indices: Vec<usize> = indices.collect();
+
+    for triangle in indices.chunks_exact(3) {
+        if triangle.iter().any(|&i| i >= positions.len()) {
+            continue;
+        }
+
+        let p = [
+            Vec3::from(positions[triangle[0]]),
+            Vec3::from(positions[triangle[1]]),
+            Vec3::from(positions[triangle[2]]),
+        ];
+        let Some(face_normal) = (p[1] - p[0]).cross(p[2] - p[0]).try_normalize() else {
+            continue;
+        };
+
+        for corner in 0..3 {
+            let a = p[(corner + 1) % 3] - p[corner];
+            let b = p[(corner + 2) % 3] - p[corner];
+            normals[triangle[corner]] += face_normal * a.angle_between(b);
+        }
+    }
+
+    normals.into_iter()
+        .map(|n| n.try_normalize().unwrap_or(Vec3::Y).to_array())
+        .collect()
+}
+
+/// Insert smooth normals into a triangle list mesh which has positions.
+pub fn compute_smooth_normals(mesh: &mut Mesh) {
+    let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) else {
+        return;
+    };
+
+    let normals = mesh_normals(positions, mesh.indices());
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+}
+
+/// Normals for `positions`, treating every vertex as shared if there are
+/// `indices`, or every triangle as separate if there are none (which gives
+/// flat normals).
+fn mesh_normals(positions: &[[f32; 3]], indices: Option<&Indices>) -> Vec<[f32; 3]> {
+    match indices {
+        Some(indices) => smooth_normals(positions, indices.iter()),
+        None => smooth_normals(positions, 0..positions.len()),
+    }
+}
+
+/// Replace the normal deltas of morph targets with ones generated from the
+/// morphed positions, for a mesh whose own normals were generated.
+///
+/// The normals are generated the same way as the mesh's: smooth if it is
+/// indexed, and flat (the vertices having been duplicated) if not.
+pub fn compute_morph_normals(mesh: &Mesh, targets: &mut [Vec<MorphAttributes>]) {
+    let (
+        Some(VertexAttributeValues::Float32x3(positions)),
+        Some(VertexAttributeValues::Float32x3(normals)),
+    ) = (mesh.attribute(Mesh::ATTRIBUTE_POSITION), mesh.attribute(Mesh::ATTRIBUTE_NORMAL)) else {
+        return;
+    };
+
+    for target in targets {
+        if target.iter().all(|attributes| attributes.position == Vec3::ZERO) {
+            continue;
+        }
+
+        let morphed: Vec<[f32; 3]> = positions.iter()
+            .zip(target.iter())
+            .map(|(position, attributes)| (Vec3::from(*position) + attributes.position).to_array())
+            .collect();
+        let morphed_normals = mesh_normals(&morphed, mesh.indices());
+        for ((attributes, normal), morphed) in target.iter_mut().zip(normals).zip(morphed_normals) {
+            attributes.normal = Vec3::from(morphed) - Vec3::from(*normal);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::mesh::PrimitiveTopology;
+    use bevy::render::render_asset::RenderAssetUsages;
+
+    use super::*;
+
+    #[test]
+    fn shared_vertices_are_averaged() {
+        // Two triangles folded 90 degrees along the shared edge (0, 1).
+        let positions = [
+            [0., 0., 0.],
+            [1., 0., 0.],
+            [0., 1., 0.],
+            [0., 0., 1.],
+        ];
+        let indices = [0, 1, 2, 0, 3, 1];
+        let normals = smooth_normals(&positions, indices.into_iter());
+
+        assert_eq!(normals.len(), positions.len());
+        let shared = Vec3::from(normals[0]);
+        let expected = Vec3::new(0., 1., 1.).normalize();
+        assert!(shared.abs_diff_eq(expected, 1e-5), "{shared} != {expected}");
+        assert!(Vec3::from(normals[1]).abs_diff_eq(expected, 1e-5));
+        // Unshared vertices keep their face's normal.
+        assert!(Vec3::from(normals[2]).abs_diff_eq(Vec3::Z, 1e-5));
+        assert!(Vec3::from(normals[3]).abs_diff_eq(Vec3::Y, 1e-5));
+    }
+
+    #[test]
+    fn morph_normals_follow_morphed_positions() {
+        // A flat triangle facing +Z, whose third corner is pulled forwards.
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default());
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vec![[0., 0., 0.], [1., 0., 0.], [0., 1., 0.]]);
+        compute_smooth_normals(&mut mesh);
+
+        let moved = |position: Vec3| MorphAttributes {
+            position,
+            ..Default::default()
+        };
+        let mut targets = vec![
+            vec![moved(Vec3::ZERO), moved(Vec3::ZERO), moved(Vec3::Z)],
+            vec![moved(Vec3::ZERO); 3],
+        ];
+        compute_morph_normals(&mesh, &mut targets);
+
+        let expected = Vec3::new(0., -1., 1.).normalize() - Vec3::Z;
+        for attributes in &targets[0] {
+            assert!(attributes.normal.abs_diff_eq(expected, 1e-5), "{} != {expected}", attributes.normal);
+        }
+        // Targets which don't move anything are left alone.
+        assert!(targets[1].iter().all(|attributes| attributes.normal == Vec3::ZERO));
+    }
+
+    #[test]
+    fn degenerate_triangles_are_ignored() {
+        let positions = [[0., 0., 0.], [1., 0., 0.], [2., 0., 0.]];
+        let normals = smooth_normals(&positions, [0, 1, 2].into_iter());
+        assert!(normals.iter().all(|n| Vec3::from(*n) == Vec3::Y));
+    }
+}
@@ -0,0 +1,97 @@
+use bevy::ecs::world::World;
+use bevy::hierarchy::Parent;
+use bevy::math::{EulerRot, Mat3, Quat, Vec3};
+use bevy::prelude::{Entity, Transform};
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::extensions::vrm::HumanoidBone;
+
+/// A rotation applied to the root of the avatar's scene, for files which
+/// aren't Y-up and facing +Z.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum RootCorrection {
+    /// Leave the scene as it is.
+    #[default]
+    None,
+    /// Rotate the scene so that the humanoid's rest pose is Y-up and faces
+    /// +Z, as VRM 1.0 specifies. This also turns VRM 0.x style avatars, which
+    /// face -Z, around.
+    Auto,
+    /// Rotate the scene by YXZ Euler angles, in degrees.
+    Euler([f32; 3]),
+}
+
+// Corrections smaller than this, in radians, are ignored.
+const MIN_CORRECTION: f32 = 1e-3;
+
+/// The rotation which turns a humanoid with the given up and left directions
+/// to be Y-up with its left on +X (and so facing +Z).
+pub fn upright_rotation(up: Vec3, left: Vec3) -> Option<Quat> {
+    let up = up.try_normalize()?;
+    let left = (left - up * left.dot(up)).try_normalize()?;
+    let forward = left.cross(up);
+    let current = Quat::from_mat3(&Mat3::from_cols(left, up, forward));
+    Some(current.inverse())
+}
+
+/// The rest position of an entity in the scene, relative to the scene root.
+fn rest_position(world: &World, entity: Entity) -> Option<Vec3> {
+    let mut transform = *world.get::<Transform>(entity)?;
+    let mut current = entity;
+    while let Some(parent) = world.get::<Parent>(current) {
+        current = parent.get();
+        transform = world.get::<Transform>(current)?.mul_transform(transform);
+    }
+    Some(transform.translation)
+}
+
+impl RootCorrection {
+    /// The rotation for the scene root, if any.
+    pub fn rotation(&self, world: &World, bones: &HashMap<HumanoidBone, Entity>) -> Option<Quat> {
+        let rotation = match *self {
+            RootCorrection::None => return None,
+            RootCorrection::Euler([x, y, z]) => {
+                Quat::from_euler(EulerRot::YXZ, y.to_radians(), x.to_radians(), z.to_radians())
+            }
+            RootCorrection::Auto => {
+                let position = |bone| bones.get(&bone).and_then(|&entity| rest_position(world, entity));
+                let up = position(HumanoidBone::Head)? - position(HumanoidBone::Hips)?;
+                let left = position(HumanoidBone::LeftUpperArm)
+                    .zip(position(HumanoidBone::RightUpperArm))
+                    .or_else(|| position(HumanoidBone::LeftUpperLeg).zip(position(HumanoidBone::RightUpperLeg)))
+                    .map(|(left, right)| left - right)?;
+                upright_rotation(up, left)?
+            }
+        };
+
+        (rotation.angle_between(Quat::IDENTITY) > MIN_CORRECTION).then_some(rotation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upright_is_identity() {
+        let rotation = upright_rotation(Vec3::Y, Vec3::X).unwrap();
+        assert!(rotation.angle_between(Quat::IDENTITY) < 1e-5);
+    }
+
+    #[test]
+    fn turns_backwards_avatars_around() {
+        // Facing -Z, so the avatar's left is on -X.
+        let rotation = upright_rotation(Vec3::Y, Vec3::NEG_X).unwrap();
+        assert!((rotation * Vec3::NEG_X).distance(Vec3::X) < 1e-5);
+        assert!((rotation * Vec3::Y).distance(Vec3::Y) < 1e-5);
+    }
+
+    #[test]
+    fn stands_up_lying_avatars() {
+        // Lying on its back with its head towards -Z.
+        let rotation = upright_rotation(Vec3::NEG_Z, Vec3::X).unwrap();
+        assert!((rotation * Vec3::NEG_Z).distance(Vec3::Y) < 1e-5);
+        assert!((rotation * Vec3::X).distance(Vec3::X) < 1e-5);
+    }
+}
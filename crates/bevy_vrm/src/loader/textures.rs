@@ -0,0 +1,201 @@
+use bevy::color::Srgba;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::TextureFormat;
+use bevy::render::texture::{CompressedImageFormats, Image, ImageFormat, ImageSampler, ImageType, TextureError};
+
+const KTX2_MIME_TYPE: &str = "image/ktx2";
+const KTX2_EXTENSION: &str = "ktx2";
+
+/// Convert an `f32` to the bits of the nearest half-precision float.
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        // Infinity or NaN.
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        // Subnormal: shift the implicit leading bit into the mantissa.
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        let half = mantissa >> shift;
+        let round = (mantissa >> (shift - 1)) & 1;
+        return sign | (half + round) as u16;
+    }
+
+    // Rounding may carry into the exponent, which is still correct.
+    let half = ((exponent as u32) << 10) | (mantissa >> 13);
+    let round = (mantissa >> 12) & 1;
+    sign | (half + round) as u16
+}
+
+fn read_u16s(data: &[u8]) -> impl Iterator<Item = f32> + '_ {
+    data.chunks_exact(2)
+        .map(|c| u16::from_ne_bytes([c[0], c[1]]) as f32 / u16::MAX as f32)
+}
+
+fn read_f32s(data: &[u8]) -> impl Iterator<Item = f32> + '_ {
+    data.chunks_exact(4)
+        .map(|c| f32::from_ne_bytes([c[0], c[1], c[2], c[3]]))
+}
+
+/// Convert 16 bit and floating point images to `Rgba16Float`.
+///
+/// Bevy decodes 16 bit greyscale images to integer formats which can't be
+/// sampled as colours, 16 bit colour images to a normalized format which
+/// isn't available everywhere and has no sRGB variant, and float images to
+/// `Rgba32Float`, which most GPUs can't filter. Half floats keep the extra
+/// precision and can be filtered everywhere, and sRGB sources are decoded to
+/// linear on the way. 8 bit images are left unchanged.
+pub fn convert_high_precision(image: &mut Image, is_srgb: bool) {
+    let data = &image.data;
+    let (channels, values): (usize, Vec<f32>) = match image.texture_descriptor.format {
+        TextureFormat::R16Uint => (1, read_u16s(data).collect()),
+        TextureFormat::Rg16Uint => (2, read_u16s(data).collect()),
+        TextureFormat::Rgba16Unorm => (4, read_u16s(data).collect()),
+        // Floating point images are already linear.
+        TextureFormat::Rgba32Float => (4, read_f32s(data).collect()),
+        _ => return,
+    };
+    let decode = is_srgb && image.texture_descriptor.format != TextureFormat::Rgba32Float;
+    let decode = |value: f32| if decode { Srgba::gamma_function(value) } else { value };
+
+    let mut converted = Vec::with_capacity(values.len() / channels * 4 * 2);
+    for pixel in values.chunks_exact(channels) {
+        let rgba = match *pixel {
+            [l] => [decode(l), decode(l), decode(l), 1.],
+            [l, a] => [decode(l), decode(l), decode(l), a],
+            [r, g, b, a] => [decode(r), decode(g), decode(b), a],
+            _ => unreachable!(),
+        };
+        for value in rgba {
+            converted.extend_from_slice(&f32_to_f16(value).to_ne_bytes());
+        }
+    }
+
+    image.data = converted;
+    image.texture_descriptor.format = TextureFormat::Rgba16Float;
+}
+
+/// Work out an image's format from its mime type or, failing that, the
+/// extension of its URI.
+///
+/// KTX2 is matched here rather than left to Bevy, so that avatars using it
+/// get a clear error when the `ktx2` feature is disabled.
+pub fn image_format(mime_type: Option<&str>, extension: Option<&str>) -> Result<ImageFormat, TextureError> {
+    let is_ktx2 = mime_type.map_or_else(
+        || extension.map_or(false, |extension| extension.eq_ignore_ascii_case(KTX2_EXTENSION)),
+        |mime_type| mime_type.eq_ignore_ascii_case(KTX2_MIME_TYPE),
+    );
+    if is_ktx2 {
+        #[cfg(feature = "ktx2")]
+        return Ok(ImageFormat::Ktx2);
+        #[cfg(not(feature = "ktx2"))]
+        return Err(TextureError::InvalidImageMimeType(
+            format!("{KTX2_MIME_TYPE} (enable the ktx2 feature)"),
+        ));
+    }
+
+    match (mime_type, extension) {
+        (Some(mime_type), _) => ImageFormat::from_mime_type(mime_type)
+            .ok_or_else(|| TextureError::InvalidImageMimeType(mime_type.to_string())),
+        (None, Some(extension)) => ImageFormat::from_extension(extension)
+            .ok_or_else(|| TextureError::InvalidImageExtension(extension.to_string())),
+        (None, None) => Err(TextureError::InvalidImageMimeType(String::new())),
+    }
+}
+
+/// Decode an image, transcoding Basis Universal textures to the best of the
+/// `supported_compressed_formats`.
+///
+/// Textures already in a block compressed format (BCn, ETC2 or ASTC) can't
+/// be decompressed on the CPU, so they fail to load if the GPU can't sample
+/// them.
+pub fn decode_image(
+    bytes: &[u8],
+    format: ImageFormat,
+    supported_compressed_formats: CompressedImageFormats,
+    is_srgb: bool,
+    sampler: ImageSampler,
+) -> Result<Image, TextureError> {
+    Image::from_buffer(
+        bytes,
+        ImageType::Format(format),
+        supported_compressed_formats,
+        is_srgb,
+        sampler,
+        RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
+    ).map_err(|err| match err {
+        TextureError::UnsupportedTextureFormat(texture_format) => TextureError::UnsupportedTextureFormat(
+            format!("{texture_format} can't be sampled by this GPU, and only Basis Universal textures are transcoded"),
+        ),
+        err => err,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::render_asset::RenderAssetUsages;
+    use bevy::render::render_resource::{Extent3d, TextureDimension};
+
+    use super::*;
+
+    #[test]
+    fn converts_to_half_floats() {
+        assert_eq!(f32_to_f16(0.), 0);
+        assert_eq!(f32_to_f16(1.), 0x3c00);
+        assert_eq!(f32_to_f16(0.5), 0x3800);
+        assert_eq!(f32_to_f16(-2.), 0xc000);
+        assert_eq!(f32_to_f16(65504.), 0x7bff);
+        assert_eq!(f32_to_f16(1e6), 0x7c00);
+        assert_eq!(f32_to_f16(2f32.powi(-24)), 0x0001);
+        assert_eq!(f32_to_f16(2f32.powi(-14)), 0x0400);
+    }
+
+    #[test]
+    fn expands_greyscale_16() {
+        let size = Extent3d {
+            width: 2,
+            height: 1,
+            depth_or_array_layers: 1,
+        };
+        let data = [0u16, u16::MAX].iter().flat_map(|v| v.to_ne_bytes()).collect();
+        let mut image = Image::new(size, TextureDimension::D2, data, TextureFormat::R16Uint, RenderAssetUsages::all());
+        convert_high_precision(&mut image, false);
+
+        assert_eq!(image.texture_descriptor.format, TextureFormat::Rgba16Float);
+        let halves: Vec<u16> = image.data.chunks_exact(2)
+            .map(|c| u16::from_ne_bytes([c[0], c[1]]))
+            .collect();
+        assert_eq!(halves, [0, 0, 0, 0x3c00, 0x3c00, 0x3c00, 0x3c00, 0x3c00]);
+    }
+
+    #[test]
+    fn leaves_8_bit_images() {
+        let mut image = Image::default();
+        let before = image.clone();
+        convert_high_precision(&mut image, true);
+        assert_eq!(image.data, before.data);
+        assert_eq!(image.texture_descriptor.format, before.texture_descriptor.format);
+    }
+
+    #[test]
+    #[cfg(feature = "ktx2")]
+    fn recognises_ktx2() {
+        assert_eq!(image_format(Some("image/ktx2"), None).unwrap(), ImageFormat::Ktx2);
+        assert_eq!(image_format(None, Some("KTX2")).unwrap(), ImageFormat::Ktx2);
+        // The mime type wins over the extension.
+        assert_eq!(image_format(Some("image/png"), Some("ktx2")).unwrap(), ImageFormat::Png);
+    }
+}
@@ -0,0 +1,78 @@
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
+
+/// Maps morph target names to the canonical expression names which should
+/// drive them, so one expression drives equivalent morphs on every mesh.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MorphAliases {
+    /// Canonical expression name, by morph target name.
+    pub aliases: HashMap<String, String>,
+}
+
+impl Default for MorphAliases {
+    fn default() -> Self {
+        let mut aliases = MorphAliases::vroid();
+        aliases.aliases.extend(MorphAliases::arkit().aliases);
+        aliases
+    }
+}
+
+impl MorphAliases {
+    pub fn empty() -> MorphAliases {
+        MorphAliases {
+            aliases: HashMap::new(),
+        }
+    }
+
+    /// The face morphs of VRoid Studio avatars, aliased to the VRM expression
+    /// presets.
+    pub fn vroid() -> MorphAliases {
+        let aliases = [
+            ("Fcl_MTH_A", "aa"),
+            ("Fcl_MTH_I", "ih"),
+            ("Fcl_MTH_U", "ou"),
+            ("Fcl_MTH_E", "ee"),
+            ("Fcl_MTH_O", "oh"),
+            ("Fcl_EYE_Close", "blink"),
+            ("Fcl_EYE_Close_L", "blinkLeft"),
+            ("Fcl_EYE_Close_R", "blinkRight"),
+            // VRoid's brows move both sides together, so they follow the
+            // stronger side of ARKit's brow blend shapes.
+            ("Fcl_BRW_Angry", "browDown"),
+            ("Fcl_BRW_Sorrow", "browInnerUp"),
+            ("Fcl_BRW_Surprised", "browOuterUp"),
+            ("Fcl_ALL_Joy", "happy"),
+            ("Fcl_ALL_Angry", "angry"),
+            ("Fcl_ALL_Sorrow", "sad"),
+            ("Fcl_ALL_Fun", "relaxed"),
+            ("Fcl_ALL_Surprised", "surprised"),
+        ];
+        MorphAliases {
+            aliases: aliases.into_iter()
+                .map(|(name, canonical)| (name.to_string(), canonical.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Other spellings of the ARKit blend shapes which VRM avatars commonly
+    /// lack, aliased to their ARKit names.
+    pub fn arkit() -> MorphAliases {
+        let aliases = [
+            ("CheekPuff", "cheekPuff"),
+            ("Cheek_Puff", "cheekPuff"),
+            ("cheek_puff", "cheekPuff"),
+            ("TongueOut", "tongueOut"),
+            ("Tongue_Out", "tongueOut"),
+            ("tongue_out", "tongueOut"),
+        ];
+        MorphAliases {
+            aliases: aliases.into_iter()
+                .map(|(name, canonical)| (name.to_string(), canonical.to_string()))
+                .collect(),
+        }
+    }
+
+    pub fn canonical(&self, name: &str) -> Option<&str> {
+        self.aliases.get(name).map(String::as_str)
+    }
+}
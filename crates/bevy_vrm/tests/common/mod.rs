@@ -0,0 +1,101 @@
+//! Helpers shared by the integration tests and the benchmarks.
+
+// Each test binary only uses some of these.
+#![allow(dead_code)]
+
+use std::path::Path;
+
+use bevy::asset::{AssetPlugin, LoadState};
+use bevy::prelude::*;
+use bevy::render::mesh::skinning::SkinnedMeshInverseBindposes;
+use bevy::render::texture::CompressedImageFormats;
+
+use bevy_vrm::{Vrm, VrmLoader};
+use bevy_vrm::extensions::mtoon::{MToonMaterial, MToonOutlineMaterial};
+use bevy_vrm::extensions::vrm::{Humanoid, REQUIRED_BONES};
+
+/// The small VRMs checked in alongside the tests.
+pub const FIXTURES: &str = "tests/fixtures";
+
+/// The assets shipped with bevy_idol. Its avatars are stored in Git LFS.
+pub const IDOL_ASSETS: &str = "../bevy_idol/assets";
+
+const LFS_POINTER: &[u8] = b"version https://git-lfs.github.com/spec/";
+
+/// Creates a headless app which loads VRMs from `asset_root`, relative to
+/// this crate.
+pub fn new_app(asset_root: &str) -> App {
+    let mut app = App::new();
+    app
+        .add_plugins((
+            MinimalPlugins,
+            AssetPlugin {
+                file_path: asset_root.into(),
+                ..default()
+            },
+        ))
+        .init_asset::<Mesh>()
+        .init_asset::<Image>()
+        .init_asset::<StandardMaterial>()
+        .init_asset::<MToonMaterial>()
+        .init_asset::<MToonOutlineMaterial>()
+        .init_asset::<SkinnedMeshInverseBindposes>()
+        .init_asset::<Scene>()
+        .init_asset::<AnimationClip>()
+        .init_asset::<Vrm>()
+        .register_asset_loader(VrmLoader::new(CompressedImageFormats::all()));
+    app
+}
+
+/// Loads the VRM at `path` along with its dependencies, panicking if it
+/// fails.
+pub fn load(app: &mut App, path: &str) -> Handle<Vrm> {
+    let handle: Handle<Vrm> = app.world().resource::<AssetServer>().load(path.to_string());
+    loop {
+        app.update();
+
+        let asset_server = app.world().resource::<AssetServer>();
+        if asset_server.is_loaded_with_dependencies(&handle) {
+            break;
+        }
+
+        if let Some(LoadState::Failed(err)) = asset_server.get_load_state(&handle) {
+            panic!("failed to load {path}: {err}");
+        }
+    }
+    handle
+}
+
+/// Whether the file at `path` under `asset_root` has been checked out,
+/// rather than left as a Git LFS pointer.
+pub fn is_available(asset_root: &str, path: &str) -> bool {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(asset_root).join(path);
+    std::fs::read(path).is_ok_and(|bytes| !bytes.starts_with(LFS_POINTER))
+}
+
+/// Returns the world of the VRM's default scene.
+pub fn default_scene<'a>(app: &'a mut App, handle: &Handle<Vrm>) -> &'a mut World {
+    let vrm = app.world().resource::<Assets<Vrm>>().get(handle).unwrap();
+    let scene_name = vrm.default_scene.as_ref().expect("expected a default scene");
+    let scene_handle = vrm.scenes[scene_name].clone();
+    let scenes = app.world_mut().resource_mut::<Assets<Scene>>().into_inner();
+    let scene = scenes.get_mut(&scene_handle).expect("expected the scene to be loaded");
+    &mut scene.world
+}
+
+/// Returns the humanoid in a scene's `world`, checking that all of the
+/// required bones map to entities in it.
+pub fn humanoid(world: &mut World) -> Humanoid {
+    let humanoid = world.query::<&Humanoid>()
+        .get_single(world)
+        .expect("expected exactly one humanoid")
+        .clone();
+
+    for bone in REQUIRED_BONES {
+        let entity = humanoid.bones.get(bone)
+            .unwrap_or_else(|| panic!("missing required bone {bone:?}"));
+        assert!(world.get_entity(*entity).is_some(), "bone {bone:?} maps to a missing entity");
+    }
+
+    humanoid
+}
@@ -0,0 +1,53 @@
+use bevy::prelude::*;
+
+use bevy_vrm::Vrm;
+use bevy_vrm::extensions::vrm::{Eye, Humanoid, HumanoidBone, LookAtRangeMap, LookAtTarget};
+
+mod common;
+
+const HUMANOID: &str = "humanoid.vrm";
+const DEMO: &str = "avatars/demo.vrm";
+
+fn check_eyes(world: &World, humanoid: &Humanoid) {
+    for bone in [HumanoidBone::LeftEye, HumanoidBone::RightEye] {
+        let Some(entity) = humanoid.bones.get(&bone) else {
+            continue;
+        };
+
+        let eye = world.entity(*entity);
+        assert!(eye.contains::<Eye>(), "{bone:?} is missing Eye");
+        assert!(eye.contains::<LookAtRangeMap>(), "{bone:?} is missing LookAtRangeMap");
+        let target = eye.get::<LookAtTarget>()
+            .unwrap_or_else(|| panic!("{bone:?} is missing LookAtTarget"));
+        assert!(world.get_entity(target.0).is_some(), "{bone:?} look target is missing");
+    }
+}
+
+#[test]
+fn loads_humanoid_and_look_at() {
+    let mut app = common::new_app(common::FIXTURES);
+    let handle = common::load(&mut app, HUMANOID);
+
+    let world = common::default_scene(&mut app, &handle);
+    let humanoid = common::humanoid(world);
+    assert!(humanoid.bones.contains_key(&HumanoidBone::LeftEye), "expected the fixture's eyes to load");
+    assert!(humanoid.bones.contains_key(&HumanoidBone::RightEye), "expected the fixture's eyes to load");
+    check_eyes(world, &humanoid);
+}
+
+#[test]
+fn loads_demo_avatar() {
+    if !common::is_available(common::IDOL_ASSETS, DEMO) {
+        eprintln!("Skipping {DEMO}, it's missing or a Git LFS pointer (run `git lfs pull`)");
+        return;
+    }
+
+    let mut app = common::new_app(common::IDOL_ASSETS);
+    let handle = common::load(&mut app, DEMO);
+    let vrm = app.world().resource::<Assets<Vrm>>().get(&handle).unwrap();
+    assert!(!vrm.meshes.is_empty(), "expected the avatar to contain meshes");
+
+    let world = common::default_scene(&mut app, &handle);
+    let humanoid = common::humanoid(world);
+    check_eyes(world, &humanoid);
+}
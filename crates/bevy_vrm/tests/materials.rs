@@ -0,0 +1,76 @@
+use bevy::prelude::*;
+
+use bevy_vrm::extensions::mtoon::{MToonMaterial, MToonOutlineMaterial, OutlineWidthMode};
+
+mod common;
+
+const MATERIALS: &str = "materials.vrm";
+
+fn load_materials() -> App {
+    let mut app = common::new_app(common::FIXTURES);
+    common::load(&mut app, MATERIALS);
+    app
+}
+
+fn material<A: Asset + Clone>(app: &App, label: &str) -> A {
+    let handle = app.world().resource::<AssetServer>()
+        .get_handle::<A>(format!("{MATERIALS}#{label}"))
+        .unwrap_or_else(|| panic!("expected {label} to be loaded"));
+    app.world().resource::<Assets<A>>().get(&handle).unwrap().clone()
+}
+
+fn assert_close(actual: f32, expected: f32) {
+    assert!((actual - expected).abs() < 1e-5, "expected {expected}, got {actual}");
+}
+
+#[test]
+fn maps_mtoon() {
+    let app = load_materials();
+    let mtoon: MToonMaterial = material(&app, "Material0");
+    assert_eq!(mtoon.base_color, LinearRgba::new(1., 0.5, 0.5, 1.));
+    assert_eq!(mtoon.shade_color, LinearRgba::rgb(0.5, 0.25, 0.125));
+    assert_eq!(mtoon.shading_shift_factor, -0.1);
+    assert_eq!(mtoon.shading_toony_factor, 0.5);
+    assert_eq!(mtoon.parametric_rim_color_factor, Vec3::new(0.2, 0.3, 0.4));
+    assert_eq!(mtoon.outline_width_mode, OutlineWidthMode::WorldCoordinates);
+    assert!(mtoon.normal_map_texture.is_some(), "expected a normal map");
+    assert_eq!(mtoon.normal_scale, 0.5);
+
+    let outline: MToonOutlineMaterial = material(&app, "Material0Outline");
+    assert_eq!(outline.extension.width_factor, 0.01);
+    assert_eq!(outline.extension.color_factor, Vec3::X);
+    assert!(outline.extension.width_multiply_texture.is_some(), "expected an outline width texture");
+    assert_eq!(outline.extension.width_multiply_uv_set, 1);
+    assert_eq!(outline.base.shade_color, mtoon.shade_color);
+    assert_eq!(outline.base.normal_scale, 0.5);
+}
+
+#[test]
+fn maps_specular_to_reflectance() {
+    let app = load_materials();
+    let specular: StandardMaterial = material(&app, "Material1");
+    // F0 = 0.04 * 0.5 * 1, and Bevy's F0 is 0.16 * reflectance².
+    assert_close(specular.reflectance, 0.125f32.sqrt());
+    assert_close(specular.perceptual_roughness, 0.3);
+    assert_eq!(specular.clearcoat, 0.);
+}
+
+#[test]
+fn maps_clearcoat() {
+    let app = load_materials();
+    let clearcoat: StandardMaterial = material(&app, "Material2");
+    assert_eq!(clearcoat.clearcoat, 1.);
+    assert_close(clearcoat.clearcoat_perceptual_roughness, 0.2);
+    assert_close(clearcoat.reflectance, 0.5);
+}
+
+#[test]
+fn plain_materials_use_defaults() {
+    let app = load_materials();
+    let plain: StandardMaterial = material(&app, "Material3");
+    assert_close(plain.reflectance, 0.5);
+    assert_eq!(plain.clearcoat, 0.);
+    assert!(app.world().resource::<AssetServer>()
+        .get_handle::<MToonMaterial>(format!("{MATERIALS}#Material3"))
+        .is_none(), "expected a standard material");
+}
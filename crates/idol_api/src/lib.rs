@@ -5,7 +5,7 @@ use axum::Json;
 use axum::response::{IntoResponse, Response};
 
 use bytes::Bytes;
-use glam::{Mat4, Vec3};
+use glam::{Mat4, Quat, Vec3};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
@@ -68,6 +68,14 @@ impl ApiError {
     pub fn unavailable() -> Self {
         Self::with_message(ErrorCategory::Cancelled, "unavailable", "service unavailable")
     }
+
+    pub fn invalid_argument(message: impl Into<Cow<'static, str>>) -> Self {
+        Self::with_message(ErrorCategory::InvalidArgument, "invalid_argument", message)
+    }
+
+    pub fn not_found(message: impl Into<Cow<'static, str>>) -> Self {
+        Self::with_message(ErrorCategory::NotFound, "not_found", message)
+    }
 }
 
 impl IntoResponse for ApiError {
@@ -98,6 +106,259 @@ pub struct SetFacesRequest {
     pub faces: Vec<Face>,
 }
 
+/// A tracked body pose.
+///
+/// The landmarks are MediaPipe pose world landmarks: metres, relative to the
+/// centre of the hips, with Y pointing down.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Pose {
+    pub landmarks: Vec<FaceLandmark>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetPosesRequest {
+    pub poses: Vec<Pose>,
+}
+
+/// Where the avatar should look.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SetLookAtRequest {
+    /// Look straight ahead, relative to the head.
+    Default,
+    /// Look at a point in world space.
+    Point { position: Vec3 },
+    /// Follow the entity with the given name.
+    Entity { name: String },
+}
+
+/// Fade a named expression pose in or out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetExpressionPoseRequest {
+    pub name: String,
+    pub weight: f32,
+}
+
+/// Start (or with no path, stop) playing a phoneme timeline.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayPhonemesRequest {
+    /// Path of the timeline within the assets directory.
+    pub path: Option<String>,
+    /// Position to start playing from, in seconds.
+    #[serde(default)]
+    pub offset: f32,
+}
+
+fn default_gamma() -> f32 {
+    1.
+}
+
+/// Exposure and gamma of the output camera. The defaults leave the output
+/// unchanged.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetOutputColorRequest {
+    /// Exposure, in stops.
+    #[serde(default)]
+    pub exposure: f32,
+    #[serde(default = "default_gamma")]
+    pub gamma: f32,
+}
+
+/// Named trade-offs between smoothing and latency for tracking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SmoothingPreset {
+    Off,
+    Responsive,
+    Balanced,
+    Smooth,
+}
+
+/// A group of tracked channels which is smoothed independently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SmoothingGroup {
+    /// Blend shapes, other than gaze.
+    Expressions,
+    /// The face transform.
+    Head,
+    /// The `eyeLook*` gaze blend shapes.
+    LookAt,
+}
+
+/// Select a smoothing preset and/or override individual parameters. The
+/// preset, if any, is applied first.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetSmoothingRequest {
+    /// The group to change, or every group if omitted.
+    pub group: Option<SmoothingGroup>,
+    pub preset: Option<SmoothingPreset>,
+    /// Cutoff frequency when still, in Hz.
+    pub min_cutoff: Option<f32>,
+    /// How much the cutoff rises with speed.
+    pub beta: Option<f32>,
+    /// How far ahead to extrapolate, in seconds.
+    pub extrapolation: Option<f32>,
+}
+
+/// Set the local rotation of a single humanoid bone.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetBoneRequest {
+    /// The humanoid bone, as named in the VRM specification.
+    pub bone: String,
+    /// Rotation relative to the bone's parent.
+    pub rotation: Quat,
+}
+
+/// Show or hide a named node within the loaded avatars.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetPartRequest {
+    pub visible: bool,
+}
+
+/// Fade the avatar towards an opacity.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetFadeRequest {
+    /// Target opacity, from 0 (invisible) to 1.
+    pub opacity: f32,
+    /// Time to fade fully in or out, in seconds. Defaults to the last
+    /// duration used.
+    #[serde(default)]
+    pub duration: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetAvatarRequest {
+    pub index: usize,
+}
+
+fn default_scale() -> Vec3 {
+    Vec3::ONE
+}
+
+/// A prop attached to one of the avatar's humanoid bones.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Accessory {
+    /// Unique name of the accessory, used to replace or remove it.
+    pub name: String,
+    /// Path of a glTF or OBJ file within the assets directory.
+    pub path: String,
+    /// The humanoid bone to attach to, as named in the VRM specification.
+    pub bone: String,
+    /// Offset from the bone.
+    #[serde(default)]
+    pub translation: Vec3,
+    #[serde(default)]
+    pub rotation: Quat,
+    #[serde(default = "default_scale")]
+    pub scale: Vec3,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RigTransform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RigBone {
+    /// The humanoid bone, as named in the VRM specification (e.g. `leftUpperArm`).
+    pub bone: String,
+    pub entity: u64,
+    pub name: Option<String>,
+    /// The nearest ancestor which is also a humanoid bone.
+    pub parent: Option<String>,
+    pub local: RigTransform,
+    pub global: RigTransform,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RigAvatar {
+    pub entity: u64,
+    pub name: Option<String>,
+    pub bones: Vec<RigBone>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthResponse {
+    /// Whether a face is currently being tracked.
+    pub tracking: bool,
+}
+
+/// Sent as a `tracking` server-sent event whenever tracking starts or stops,
+/// and once when subscribing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackingEvent {
+    pub active: bool,
+}
+
+/// The blend shape names received from tracking this session, and those
+/// which have never driven a morph target on any avatar.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetBlendShapesResponse {
+    pub tracked: Vec<String>,
+    pub unmatched: Vec<String>,
+}
+
+/// The humanoid rigs of every loaded avatar.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetRigResponse {
+    pub avatars: Vec<RigAvatar>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Calibrate tracking for the current camera setup, saving the result to the
+/// active calibration profile.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CalibrateRequest {
+    /// Take the current head pose as the neutral pose.
+    #[serde(default = "default_true")]
+    pub neutral: bool,
+    /// Scale of tracked head movement. Left unchanged when absent.
+    #[serde(default)]
+    pub translation_scale: Option<f32>,
+}
+
+/// Switch to a calibration profile, keyed by a name or camera id. Profiles
+/// which haven't been saved start uncalibrated.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetCalibrationProfileRequest {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetCalibrationProfilesResponse {
+    /// The saved profiles.
+    pub profiles: Vec<String>,
+    /// The profile which calibration is saved to.
+    pub active: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct SetCameraRequest {
     pub width: u32,